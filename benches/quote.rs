@@ -0,0 +1,186 @@
+//! Latency of the quote math per curve type, and of `quote`/`update` against the recorded
+//! fixtures in `tests/fixtures`, individually and for large batches of pools.
+//!
+//! Run with `cargo bench`, criterion keeps the previous run to report regressions.
+
+use std::path::Path;
+use std::sync::Arc;
+
+use anchor_lang::AccountDeserialize;
+use bytemuck::Zeroable;
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion};
+use hyperplane::curve::base::{CurveType, SwapCurve};
+use hyperplane::curve::calculator::{CurveCalculator, TradeDirection};
+use hyperplane::curve::constant_price::ConstantPriceCurve;
+use hyperplane::curve::constant_product::ConstantProductCurve;
+use hyperplane::curve::fees::Fees;
+use hyperplane::curve::offset::OffsetCurve;
+use hyperplane::curve::stable::StableCurve;
+use hyperplane::state::{SwapPool, SwapState};
+use jupiter_rarefish::amm::{Amm, KeyedAccount, QuoteParams, SwapMode};
+use jupiter_rarefish::fixture::PoolFixture;
+use jupiter_rarefish::registry::MarketRegistry;
+use jupiter_rarefish::JupiterRarefish;
+use solana_sdk::pubkey::Pubkey;
+
+const BATCH_SIZES: [usize; 3] = [10, 100, 1_000];
+
+fn load_fixtures() -> Vec<PoolFixture> {
+    let directory = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures");
+    let mut paths: Vec<_> = std::fs::read_dir(directory)
+        .map(|entries| entries.filter_map(|entry| entry.ok().map(|entry| entry.path())))
+        .into_iter()
+        .flatten()
+        .filter(|path| {
+            path.extension()
+                .map_or(false, |extension| extension == "json" || extension == "bin")
+        })
+        .collect();
+    paths.sort();
+    paths
+        .iter()
+        .map(|path| PoolFixture::load(path).unwrap())
+        .collect()
+}
+
+fn fixture_pool(fixture: &PoolFixture) -> SwapPool {
+    let account = fixture.account(&fixture.pool).unwrap();
+    AccountDeserialize::try_deserialize(&mut account.data.as_ref()).unwrap()
+}
+
+fn curves() -> Vec<SwapCurve> {
+    let constant_product: ConstantProductCurve = Zeroable::zeroed();
+    let mut constant_price: ConstantPriceCurve = Zeroable::zeroed();
+    constant_price.token_b_price = 10;
+    let mut offset: OffsetCurve = Zeroable::zeroed();
+    offset.token_b_offset = 1_000_000_000;
+    let mut stable: StableCurve = Zeroable::zeroed();
+    stable.amp = 100;
+    stable.token_a_factor = 1;
+    stable.token_b_factor = 1;
+    let calculators: [(CurveType, Arc<dyn CurveCalculator + Sync + Send>); 4] = [
+        (CurveType::ConstantProduct, Arc::new(constant_product)),
+        (CurveType::ConstantPrice, Arc::new(constant_price)),
+        (CurveType::Offset, Arc::new(offset)),
+        (CurveType::Stable, Arc::new(stable)),
+    ];
+    calculators
+        .into_iter()
+        .map(|(curve_type, calculator)| SwapCurve {
+            curve_type,
+            calculator,
+        })
+        .collect()
+}
+
+fn bench_curve_swap(c: &mut Criterion) {
+    let fees = Fees {
+        trade_fee_numerator: 25,
+        trade_fee_denominator: 10_000,
+        owner_trade_fee_numerator: 5,
+        owner_trade_fee_denominator: 10_000,
+        ..Fees::default()
+    };
+    let mut group = c.benchmark_group("curve_swap");
+    for curve in curves() {
+        group.bench_function(format!("{:?}", curve.curve_type), |b| {
+            b.iter(|| {
+                curve.swap(
+                    black_box(1_000_000_000),
+                    black_box(1_000_000_000_000),
+                    black_box(1_000_000_000_000),
+                    TradeDirection::AtoB,
+                    &fees,
+                )
+            })
+        });
+    }
+    group.finish();
+}
+
+fn bench_fixtures(c: &mut Criterion) {
+    let fixtures = load_fixtures();
+
+    let mut group = c.benchmark_group("quote");
+    for fixture in &fixtures {
+        let mut jupiter_rarefish =
+            JupiterRarefish::new_from_keyed_account(&fixture.keyed_account().unwrap()).unwrap();
+        jupiter_rarefish.update(&fixture.account_map()).unwrap();
+        let mints = jupiter_rarefish.get_reserve_mints();
+        let quote_params = QuoteParams {
+            input_mint: mints[0],
+            output_mint: mints[1],
+            amount: 1_000_000,
+            swap_mode: SwapMode::ExactIn,
+        };
+        let id = BenchmarkId::new(
+            format!("{:?}", fixture_pool(fixture).curve_type()),
+            fixture.pool,
+        );
+        group.bench_with_input(id, &quote_params, |b, quote_params| {
+            b.iter(|| jupiter_rarefish.quote(black_box(quote_params)))
+        });
+    }
+    group.finish();
+
+    let mut group = c.benchmark_group("update");
+    for fixture in &fixtures {
+        let mut jupiter_rarefish =
+            JupiterRarefish::new_from_keyed_account(&fixture.keyed_account().unwrap()).unwrap();
+        let accounts_map = fixture.account_map();
+        let id = BenchmarkId::new(
+            format!("{:?}", fixture_pool(fixture).curve_type()),
+            fixture.pool,
+        );
+        group.bench_with_input(id, &accounts_map, |b, accounts_map| {
+            b.iter(|| jupiter_rarefish.update(black_box(accounts_map)))
+        });
+    }
+    group.finish();
+
+    let Some(fixture) = fixtures.first() else {
+        return;
+    };
+    let mut group = c.benchmark_group("registry");
+    for batch_size in BATCH_SIZES {
+        // Copies of the same pool under distinct keys, sharing vaults and mints
+        let mut registry = MarketRegistry::new();
+        let keyed_account = fixture.keyed_account().unwrap();
+        for _ in 0..batch_size {
+            registry.insert(
+                JupiterRarefish::new_from_keyed_account(&KeyedAccount {
+                    key: Pubkey::new_unique(),
+                    ..keyed_account.clone()
+                })
+                .unwrap(),
+            );
+        }
+        let accounts_map = fixture.account_map();
+        group.bench_with_input(
+            BenchmarkId::new("update", batch_size),
+            &accounts_map,
+            |b, accounts_map| {
+                b.iter_batched_ref(
+                    || registry.clone(),
+                    |registry| registry.update(black_box(accounts_map)),
+                    BatchSize::LargeInput,
+                )
+            },
+        );
+
+        registry.update(&accounts_map).unwrap();
+        let pools: Vec<Pubkey> = registry.markets().map(|market| market.key()).collect();
+        let input_mint = fixture_pool(fixture).token_a_mint;
+        group.bench_function(BenchmarkId::new("quote", batch_size), |b| {
+            b.iter(|| {
+                for pool in &pools {
+                    let _ = registry.quote(pool, &input_mint, black_box(1_000_000));
+                }
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_curve_swap, bench_fixtures);
+criterion_main!(benches);
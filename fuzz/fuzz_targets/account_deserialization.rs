@@ -0,0 +1,86 @@
+#![no_main]
+
+use anchor_lang::Discriminator;
+use arbitrary::Arbitrary;
+use hyperplane::state::SwapPool;
+use jupiter_rarefish::amm::{AccountMap, Amm, KeyedAccount, QuoteParams, SwapMode};
+use jupiter_rarefish::JupiterRarefish;
+use libfuzzer_sys::fuzz_target;
+use solana_sdk::account::Account;
+use solana_sdk::pubkey::Pubkey;
+
+#[derive(Arbitrary, Debug)]
+struct Input {
+    /// Prepend the SwapPool discriminator so most inputs get past the account type check
+    pool_discriminator: bool,
+    pool: Vec<u8>,
+    token_a_vault: Option<Vec<u8>>,
+    token_b_vault: Option<Vec<u8>>,
+    token_a_mint: Option<Vec<u8>>,
+    token_b_mint: Option<Vec<u8>>,
+    token_a_fees_vault: Option<Vec<u8>>,
+    token_b_fees_vault: Option<Vec<u8>>,
+    amount: u64,
+}
+
+fn account(data: Vec<u8>, owner: Pubkey) -> Account {
+    Account {
+        lamports: 1,
+        data,
+        owner,
+        executable: false,
+        rent_epoch: 0,
+    }
+}
+
+fuzz_target!(|input: Input| {
+    let mut pool_data = vec![];
+    if input.pool_discriminator {
+        pool_data.extend_from_slice(&SwapPool::DISCRIMINATOR);
+    }
+    pool_data.extend_from_slice(&input.pool);
+    let Ok(mut jupiter_rarefish) = JupiterRarefish::new_from_keyed_account(&KeyedAccount {
+        key: Pubkey::new_unique(),
+        account: account(pool_data, hyperplane::id()),
+        params: None,
+    }) else {
+        return;
+    };
+
+    let keys = jupiter_rarefish.get_accounts_to_update();
+    // Vaults are owned by the token program of their mint, the mints' owner is not checked
+    let owners = [
+        jupiter_rarefish.token_a_program(),
+        jupiter_rarefish.token_b_program(),
+        Pubkey::default(),
+        Pubkey::default(),
+        jupiter_rarefish.token_a_program(),
+        jupiter_rarefish.token_b_program(),
+    ];
+    let mut accounts_map = AccountMap::new();
+    for ((key, owner), data) in keys.iter().zip(owners).zip([
+        input.token_a_vault,
+        input.token_b_vault,
+        input.token_a_mint,
+        input.token_b_mint,
+        input.token_a_fees_vault,
+        input.token_b_fees_vault,
+    ]) {
+        if let Some(data) = data {
+            accounts_map.insert(*key, account(data, owner));
+        }
+    }
+    if jupiter_rarefish.update(&accounts_map).is_err() || jupiter_rarefish.reserves().is_none() {
+        return;
+    }
+
+    let mints = jupiter_rarefish.get_reserve_mints();
+    for (input_mint, output_mint) in [(mints[0], mints[1]), (mints[1], mints[0])] {
+        let _ = jupiter_rarefish.quote(&QuoteParams {
+            input_mint,
+            output_mint,
+            amount: input.amount,
+            swap_mode: SwapMode::ExactIn,
+        });
+    }
+});
@@ -0,0 +1,38 @@
+/* C API of jupiter-rarefish, built with `--features ffi`, see src/ffi.rs */
+
+#ifndef RAREFISH_H
+#define RAREFISH_H
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+#define RAREFISH_OK 0
+/* A required pointer was null */
+#define RAREFISH_ERROR_NULL_POINTER 1
+/* The pool cannot be quoted yet, the reserves were never set */
+#define RAREFISH_ERROR_NOT_READY 2
+/* The curve rejected the swap, e.g. an amount too large for the reserves */
+#define RAREFISH_ERROR_QUOTE 3
+
+typedef struct RarefishAdapter RarefishAdapter;
+
+typedef struct RarefishQuote {
+    uint64_t in_amount;
+    uint64_t out_amount;
+    uint64_t fee_amount;
+} RarefishQuote;
+
+/* Creates an adapter from the 32 byte pool address and the pool account data, null on invalid data */
+RarefishAdapter *rarefish_adapter_new(const uint8_t *pool_key, const uint8_t *pool_data, size_t pool_data_len);
+
+/* Frees an adapter, null is ignored */
+void rarefish_adapter_free(RarefishAdapter *adapter);
+
+/* Sets the token A and token B vault balances the adapter quotes against */
+int32_t rarefish_adapter_update_reserves(RarefishAdapter *adapter, uint64_t token_a_amount, uint64_t token_b_amount);
+
+/* Quotes an exact-in swap of amount_in, selling token A when a_to_b is true */
+int32_t rarefish_adapter_quote(const RarefishAdapter *adapter, bool a_to_b, uint64_t amount_in, RarefishQuote *quote);
+
+#endif /* RAREFISH_H */
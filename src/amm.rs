@@ -0,0 +1,53 @@
+//! The Jupiter `Amm` interface the adapter implements, from the jupiter-amm-interface version
+//! selected with features so integrators on different Jupiter releases can use the same crate
+//! version:
+//! - 0.3, re-exported by `jupiter-core`, by default
+//! - 0.4 with `amm-interface-0-4`, where `from_keyed_account` also takes an `AmmContext`
+//!
+//! Import the interface types from here rather than from the interface crate directly.
+
+#[cfg(not(feature = "amm-interface-0-4"))]
+pub use jupiter_core::amm::*;
+
+#[cfg(feature = "amm-interface-0-4")]
+pub use jupiter_amm_interface_0_4::*;
+
+use solana_sdk::pubkey::Pubkey;
+
+/// The `Swap` variant returned by `get_swap_and_account_metas`.
+///
+/// The interface has no hyperplane variant, so the adapter keeps the `TokenSwapV2` it has
+/// always returned. Whether the router's handler for it executes Rarefish swaps is not
+/// verified here: only the account metas are, which are those of the swap instruction in the
+/// program's order.
+pub const ROUTER_SWAP: Swap = Swap::TokenSwapV2;
+
+/// Stands in for the Jupiter program id of swaps built outside of a Jupiter route
+static NO_JUPITER_PROGRAM_ID: Pubkey = Pubkey::new_from_array([0; 32]);
+
+/// Parameters of a swap built outside of a Jupiter route, with the route-only fields left
+/// empty whatever the interface version
+pub fn swap_params(
+    in_amount: u64,
+    out_amount: u64,
+    source_mint: Pubkey,
+    destination_mint: Pubkey,
+    source_token_account: Pubkey,
+    destination_token_account: Pubkey,
+    token_transfer_authority: Pubkey,
+) -> SwapParams<'static, 'static> {
+    SwapParams {
+        in_amount,
+        out_amount,
+        source_mint,
+        destination_mint,
+        source_token_account,
+        destination_token_account,
+        token_transfer_authority,
+        open_order_address: None,
+        quote_mint_to_referrer: None,
+        jupiter_program_id: &NO_JUPITER_PROGRAM_ID,
+        #[cfg(feature = "amm-interface-0-4")]
+        missing_dynamic_accounts_as_default: false,
+    }
+}
@@ -0,0 +1,676 @@
+//! Valuation of pool reserves for dashboards and risk monitoring, per pool and aggregated
+//! across a registry, and estimates derived from a time series of pool observations.
+
+use std::collections::{HashMap, VecDeque};
+
+use anyhow::{anyhow, Result};
+use hyperplane::state::SwapState;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use solana_sdk::pubkey::Pubkey;
+
+use crate::price::{decimals_scale, fee_rate};
+use crate::registry::MarketRegistry;
+use crate::ui::to_ui_amount;
+use crate::{JupiterRarefish, TradeDirection};
+
+/// How reserves are priced
+#[derive(Clone, Copy, Debug)]
+pub enum Pricing<'a> {
+    /// Prices per UI unit of each mint in a common quote currency, e.g. USD from an oracle
+    External(&'a HashMap<Pubkey, Decimal>),
+    /// The pool's own spot price, valuing both sides in UI units of token B
+    Pool,
+}
+
+/// Value of the reserves of a pool
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PoolValuation {
+    pub pool: Pubkey,
+    pub token_a_mint: Pubkey,
+    pub token_b_mint: Pubkey,
+    pub token_a_amount: u64,
+    pub token_b_amount: u64,
+    pub token_a_value: Decimal,
+    pub token_b_value: Decimal,
+    /// Sum of both sides
+    pub tvl: Decimal,
+}
+
+/// Balances of a pool at a point in time
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Observation {
+    /// Unix timestamp in seconds
+    pub timestamp: i64,
+    pub token_a_amount: u64,
+    pub token_b_amount: u64,
+    /// Balance of the token A fees vault, if it was updated
+    pub token_a_fees: Option<u64>,
+    /// Balance of the token B fees vault, if it was updated
+    pub token_b_fees: Option<u64>,
+}
+
+/// Bounded time series of the observations of a pool, oldest first
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PoolHistory {
+    observations: VecDeque<Observation>,
+    capacity: usize,
+}
+
+impl PoolHistory {
+    /// History keeping the latest `capacity` observations
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            observations: VecDeque::with_capacity(capacity),
+            capacity: capacity.max(1),
+        }
+    }
+
+    /// Appends an observation, dropping the oldest one when full. Observations not newer
+    /// than the latest one are ignored and `false` is returned.
+    pub fn record(&mut self, observation: Observation) -> bool {
+        if self
+            .observations
+            .back()
+            .map_or(false, |latest| observation.timestamp <= latest.timestamp)
+        {
+            return false;
+        }
+        if self.observations.len() == self.capacity {
+            self.observations.pop_front();
+        }
+        self.observations.push_back(observation);
+        true
+    }
+
+    pub fn observations(&self) -> &VecDeque<Observation> {
+        &self.observations
+    }
+
+    pub fn len(&self) -> usize {
+        self.observations.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.observations.is_empty()
+    }
+
+    /// Fees accrued in the fees vaults between the first and last observations with fees.
+    ///
+    /// Only increases count, so withdrawals from the fees vaults by the admin do not offset
+    /// the fees accrued before them.
+    pub fn fee_accrual(&self) -> Option<FeeAccrual> {
+        let mut fees = self.observations.iter().filter_map(|observation| {
+            Some((
+                observation.timestamp,
+                observation.token_a_fees?,
+                observation.token_b_fees?,
+            ))
+        });
+        let (start, mut previous_a, mut previous_b) = fees.next()?;
+        let mut accrual = FeeAccrual {
+            token_a_fees: 0,
+            token_b_fees: 0,
+            elapsed: 0,
+        };
+        for (timestamp, token_a_fees, token_b_fees) in fees {
+            accrual.token_a_fees = accrual
+                .token_a_fees
+                .saturating_add(token_a_fees.saturating_sub(previous_a));
+            accrual.token_b_fees = accrual
+                .token_b_fees
+                .saturating_add(token_b_fees.saturating_sub(previous_b));
+            accrual.elapsed = timestamp - start;
+            (previous_a, previous_b) = (token_a_fees, token_b_fees);
+        }
+        (accrual.elapsed > 0).then_some(accrual)
+    }
+
+    /// Swap volume reconstructed from the vault deltas between consecutive observations,
+    /// summed into buckets of `interval` seconds aligned to the unix epoch, oldest first.
+    ///
+    /// A delta where one vault grows while the other shrinks is counted as the net swap
+    /// between the two observations. Deltas where both vaults move the same way are deposits
+    /// or withdrawals and are ignored, and swaps in opposite directions between two
+    /// observations cancel out, so the more often a pool is observed the closer the estimate.
+    pub fn volume(&self, interval: i64) -> Vec<Volume> {
+        let interval = interval.max(1);
+        let mut volumes: Vec<Volume> = vec![];
+        for (previous, next) in self
+            .observations
+            .iter()
+            .zip(self.observations.iter().skip(1))
+        {
+            let Some((trade_direction, in_amount, out_amount)) = net_swap(previous, next) else {
+                continue;
+            };
+            let start = next.timestamp.div_euclid(interval) * interval;
+            if volumes.last().map_or(true, |volume| volume.start != start) {
+                volumes.push(Volume {
+                    start,
+                    interval,
+                    ..Volume::default()
+                });
+            }
+            let volume = volumes.last_mut().unwrap();
+            match trade_direction {
+                TradeDirection::AtoB => {
+                    volume.a_to_b_in = volume.a_to_b_in.saturating_add(in_amount);
+                    volume.a_to_b_out = volume.a_to_b_out.saturating_add(out_amount);
+                }
+                TradeDirection::BtoA => {
+                    volume.b_to_a_in = volume.b_to_a_in.saturating_add(in_amount);
+                    volume.b_to_a_out = volume.b_to_a_out.saturating_add(out_amount);
+                }
+            }
+            volume.swaps += 1;
+        }
+        volumes
+    }
+}
+
+/// Net swap between two observations: the direction, the input including the owner fee sent
+/// to the fees vault, and the output
+fn net_swap(previous: &Observation, next: &Observation) -> Option<(TradeDirection, u64, u64)> {
+    let fees_delta = |previous: Option<u64>, next: Option<u64>| {
+        previous
+            .zip(next)
+            .map_or(0, |(previous, next)| next.saturating_sub(previous))
+    };
+    if next.token_a_amount > previous.token_a_amount
+        && next.token_b_amount < previous.token_b_amount
+    {
+        Some((
+            TradeDirection::AtoB,
+            (next.token_a_amount - previous.token_a_amount)
+                .saturating_add(fees_delta(previous.token_a_fees, next.token_a_fees)),
+            previous.token_b_amount - next.token_b_amount,
+        ))
+    } else if next.token_b_amount > previous.token_b_amount
+        && next.token_a_amount < previous.token_a_amount
+    {
+        Some((
+            TradeDirection::BtoA,
+            (next.token_b_amount - previous.token_b_amount)
+                .saturating_add(fees_delta(previous.token_b_fees, next.token_b_fees)),
+            previous.token_a_amount - next.token_a_amount,
+        ))
+    } else {
+        None
+    }
+}
+
+/// Histories of every pool of a registry
+#[derive(Clone, Debug, Default)]
+pub struct HistoryTracker {
+    histories: HashMap<Pubkey, PoolHistory>,
+    capacity: usize,
+}
+
+impl HistoryTracker {
+    /// Tracker keeping the latest `capacity` observations of each pool
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            histories: HashMap::new(),
+            capacity,
+        }
+    }
+
+    /// Records an observation of every updated pool of the registry at `timestamp`, in unix
+    /// seconds. Pools removed from the registry are forgotten.
+    pub fn record(&mut self, registry: &MarketRegistry, timestamp: i64) {
+        self.histories
+            .retain(|pool, _| registry.get(pool).is_some());
+        for market in registry.markets() {
+            if let Some(observation) = market.observe(timestamp) {
+                self.histories
+                    .entry(market.market_key)
+                    .or_insert_with(|| PoolHistory::new(self.capacity))
+                    .record(observation);
+            }
+        }
+    }
+
+    pub fn history(&self, pool: &Pubkey) -> Option<&PoolHistory> {
+        self.histories.get(pool)
+    }
+
+    pub fn histories(&self) -> impl Iterator<Item = (&Pubkey, &PoolHistory)> {
+        self.histories.iter()
+    }
+}
+
+/// Approximate swap volume of a pool over an interval, in raw units
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Volume {
+    /// Unix timestamp of the start of the interval, in seconds
+    pub start: i64,
+    /// Length of the interval, in seconds
+    pub interval: i64,
+    /// Token A sold into the pool
+    pub a_to_b_in: u64,
+    /// Token B bought from the pool with token A
+    pub a_to_b_out: u64,
+    /// Token B sold into the pool
+    pub b_to_a_in: u64,
+    /// Token A bought from the pool with token B
+    pub b_to_a_out: u64,
+    /// Number of observed net swaps
+    pub swaps: usize,
+}
+
+/// Owner fees accrued in the fees vaults of a pool, in raw units
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FeeAccrual {
+    pub token_a_fees: u64,
+    pub token_b_fees: u64,
+    /// Seconds between the first and last observations
+    pub elapsed: i64,
+}
+
+impl FeeAccrual {
+    /// Token A and token B fees accrued per second, in raw units
+    pub fn per_second(&self) -> (Decimal, Decimal) {
+        let elapsed = Decimal::from(self.elapsed);
+        (
+            Decimal::from(self.token_a_fees) / elapsed,
+            Decimal::from(self.token_b_fees) / elapsed,
+        )
+    }
+}
+
+/// Estimated LP fee earnings of a pool
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FeeApr {
+    /// Owner fees observed in the fees vaults
+    pub accrual: FeeAccrual,
+    /// Estimated LP fees over the same period, in raw units of token A
+    pub lp_fees_a: Decimal,
+    /// Estimated LP fees over the same period, in raw units of token B
+    pub lp_fees_b: Decimal,
+    /// Value of the estimated LP fees
+    pub lp_fees_value: Decimal,
+    /// The LP fees annualized over the current TVL, as a fraction
+    pub apr: Decimal,
+}
+
+/// Valuation of every pool of a registry that could be priced
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RegistryValuation {
+    /// Valued pools, largest TVL first
+    pub pools: Vec<PoolValuation>,
+    /// Pools without reserves, decimals or a price for one of their mints
+    pub unpriced: Vec<Pubkey>,
+    /// Sum of the TVL of every valued pool
+    pub tvl: Decimal,
+}
+
+impl JupiterRarefish {
+    /// Values the reserves of the pool. Requires the vaults and mints to have been updated.
+    pub fn valuation(&self, pricing: Pricing) -> Result<PoolValuation> {
+        let (token_a_amount, token_b_amount) = self
+            .reserves()
+            .ok_or_else(|| anyhow!("These token accounts should be updated first"))?;
+        let [token_a_mint, token_b_mint] = self.reserve_mints();
+        let (token_a_value, token_b_value) =
+            self.value_amounts(pricing, token_a_amount, token_b_amount)?;
+        Ok(PoolValuation {
+            pool: self.market_key,
+            token_a_mint,
+            token_b_mint,
+            token_a_amount,
+            token_b_amount,
+            token_a_value,
+            token_b_value,
+            tvl: token_a_value
+                .checked_add(token_b_value)
+                .ok_or_else(|| anyhow!("TVL of pool {} overflows", self.market_key))?,
+        })
+    }
+
+    /// Balances of the pool at `timestamp`, in unix seconds, once the vaults have been updated
+    pub fn observe(&self, timestamp: i64) -> Option<Observation> {
+        let (token_a_amount, token_b_amount) = self.reserves()?;
+        Some(Observation {
+            timestamp,
+            token_a_amount,
+            token_b_amount,
+            token_a_fees: self.token_a_fees,
+            token_b_fees: self.token_b_fees,
+        })
+    }
+
+    /// Estimates the fees earned by LPs over the recorded `history` and the APR they make
+    /// at the current TVL.
+    ///
+    /// Only the owner share of the trade fee reaches the fees vaults, the LP share stays in
+    /// the pool vaults where it cannot be told apart from swaps. The LP fees are derived from
+    /// the owner fees with the ratio of the trade and owner fee rates, so pools without an
+    /// owner fee cannot be estimated. Host fees are paid out of the owner fees and are not
+    /// observed, which makes the estimate a lower bound for swaps routed with a host.
+    pub fn fee_apr(&self, history: &PoolHistory, pricing: Pricing) -> Result<FeeApr> {
+        let accrual = history.fee_accrual().ok_or_else(|| {
+            anyhow!(
+                "Pool {} needs two observations of its fees vaults",
+                self.market_key
+            )
+        })?;
+        let fees = self.pool.fees();
+        let owner_fee_rate = fee_rate(
+            fees.owner_trade_fee_numerator,
+            fees.owner_trade_fee_denominator,
+        );
+        if owner_fee_rate.is_zero() {
+            return Err(anyhow!(
+                "Pool {} does not charge an owner fee",
+                self.market_key
+            ));
+        }
+        let lp_fee_ratio =
+            fee_rate(fees.trade_fee_numerator, fees.trade_fee_denominator) / owner_fee_rate;
+        let (owner_fees_a_value, owner_fees_b_value) =
+            self.value_amounts(pricing, accrual.token_a_fees, accrual.token_b_fees)?;
+        let lp_fees_value = (owner_fees_a_value + owner_fees_b_value) * lp_fee_ratio;
+        let tvl = self.valuation(pricing)?.tvl;
+        let apr = if tvl.is_zero() {
+            Decimal::ZERO
+        } else {
+            lp_fees_value / tvl * Decimal::from(SECONDS_PER_YEAR) / Decimal::from(accrual.elapsed)
+        };
+        Ok(FeeApr {
+            accrual,
+            lp_fees_a: Decimal::from(accrual.token_a_fees) * lp_fee_ratio,
+            lp_fees_b: Decimal::from(accrual.token_b_fees) * lp_fee_ratio,
+            lp_fees_value,
+            apr,
+        })
+    }
+
+    /// Time-weighted average of the spot price over the last `window` seconds of `history`,
+    /// in destination tokens per source token in raw units, before fees.
+    ///
+    /// Each observation's price holds until the next observation, and the window ends at the
+    /// latest one. A single swap moves the average in proportion to how long the price stays
+    /// there, which makes it a manipulation-resistant reference as long as the pool is observed
+    /// regularly. The prices use the current curve parameters of the pool.
+    pub fn twap(
+        &self,
+        history: &PoolHistory,
+        window: i64,
+        trade_direction: TradeDirection,
+    ) -> Result<Decimal> {
+        let prices = history
+            .observations()
+            .iter()
+            .map(|observation| {
+                Ok((
+                    observation.timestamp,
+                    self.spot_price_at(
+                        observation.token_a_amount,
+                        observation.token_b_amount,
+                        trade_direction,
+                        false,
+                    )?,
+                ))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        time_weighted_average(&prices, window).ok_or_else(|| {
+            anyhow!(
+                "Pool {} needs two observations within the last {} seconds",
+                self.market_key,
+                window
+            )
+        })
+    }
+
+    /// Value of the input side of `volume`, the token A sold plus the token B sold
+    pub fn volume_value(&self, volume: &Volume, pricing: Pricing) -> Result<Decimal> {
+        let (token_a_value, token_b_value) =
+            self.value_amounts(pricing, volume.a_to_b_in, volume.b_to_a_in)?;
+        Ok(token_a_value + token_b_value)
+    }
+
+    /// Values raw amounts of token A and token B with `pricing`
+    fn value_amounts(
+        &self,
+        pricing: Pricing,
+        token_a_amount: u64,
+        token_b_amount: u64,
+    ) -> Result<(Decimal, Decimal)> {
+        let (token_a_decimals, token_b_decimals) = self
+            .token_a_decimals()
+            .zip(self.token_b_decimals())
+            .ok_or_else(|| anyhow!("These mint accounts should be updated first"))?;
+        let [token_a_mint, token_b_mint] = self.reserve_mints();
+        let (token_a_price, token_b_price) = match pricing {
+            Pricing::External(prices) => (
+                mint_price(prices, &token_a_mint)?,
+                mint_price(prices, &token_b_mint)?,
+            ),
+            Pricing::Pool => {
+                let price = self.spot_price(TradeDirection::AtoB, false)?
+                    * decimals_scale(token_a_decimals, token_b_decimals)?;
+                (price, Decimal::ONE)
+            }
+        };
+        Ok((
+            reserve_value(token_a_amount, token_a_decimals, token_a_price)?,
+            reserve_value(token_b_amount, token_b_decimals, token_b_price)?,
+        ))
+    }
+}
+
+/// Values every pool of the registry with external `prices`, per UI unit of each mint
+pub fn registry_valuation(
+    registry: &MarketRegistry,
+    prices: &HashMap<Pubkey, Decimal>,
+) -> RegistryValuation {
+    let mut valuation = RegistryValuation::default();
+    for market in registry.markets() {
+        match market.valuation(Pricing::External(prices)) {
+            Ok(pool) => {
+                valuation.tvl = valuation.tvl.saturating_add(pool.tvl);
+                valuation.pools.push(pool);
+            }
+            Err(_) => valuation.unpriced.push(market.market_key),
+        }
+    }
+    valuation.pools.sort_by(|a, b| b.tvl.cmp(&a.tvl));
+    valuation
+}
+
+/// Sum of the reserves of every pool holding `mint`, in UI units. Pools not updated yet are
+/// skipped, a mint with more decimals than a Decimal holds is an error.
+pub fn mint_liquidity(registry: &MarketRegistry, mint: &Pubkey) -> Result<Decimal> {
+    registry
+        .markets()
+        .filter_map(|market| {
+            let (token_a_amount, token_b_amount) = market.reserves()?;
+            let [token_a_mint, token_b_mint] = market.reserve_mints();
+            if token_a_mint == *mint {
+                Some(to_ui_amount(token_a_amount, market.token_a_decimals()?))
+            } else if token_b_mint == *mint {
+                Some(to_ui_amount(token_b_amount, market.token_b_decimals()?))
+            } else {
+                None
+            }
+        })
+        .try_fold(Decimal::ZERO, |liquidity, amount| {
+            Ok(liquidity.saturating_add(amount?))
+        })
+}
+
+const SECONDS_PER_YEAR: i64 = 365 * 24 * 60 * 60;
+
+/// Average of `prices`, each weighted by the time until the next one, over the `window` seconds
+/// ending at the last one
+fn time_weighted_average(prices: &[(i64, Decimal)], window: i64) -> Option<Decimal> {
+    let end = prices.last()?.0;
+    let start = end.saturating_sub(window);
+    let mut weighted_sum = Decimal::ZERO;
+    let mut total_weight = 0_i64;
+    for ((timestamp, price), (next_timestamp, _)) in prices.iter().zip(prices.iter().skip(1)) {
+        if *next_timestamp <= start {
+            continue;
+        }
+        let weight = next_timestamp - (*timestamp).max(start);
+        weighted_sum = weighted_sum.checked_add(price.checked_mul(Decimal::from(weight))?)?;
+        total_weight += weight;
+    }
+    (total_weight > 0).then(|| weighted_sum / Decimal::from(total_weight))
+}
+
+fn mint_price(prices: &HashMap<Pubkey, Decimal>, mint: &Pubkey) -> Result<Decimal> {
+    prices
+        .get(mint)
+        .copied()
+        .ok_or_else(|| anyhow!("No price for mint {}", mint))
+}
+
+fn reserve_value(amount: u64, decimals: u8, price: Decimal) -> Result<Decimal> {
+    to_ui_amount(amount, decimals)?
+        .checked_mul(price)
+        .ok_or_else(|| anyhow!("Value of {} at price {} overflows", amount, price))
+}
+
+#[cfg(test)]
+mod tests {
+    use rust_decimal::Decimal;
+
+    use super::{
+        reserve_value, time_weighted_average, FeeAccrual, Observation, PoolHistory, Volume,
+    };
+
+    fn observation(timestamp: i64, token_a_fees: u64, token_b_fees: u64) -> Observation {
+        Observation {
+            timestamp,
+            token_a_amount: 1_000_000,
+            token_b_amount: 1_000_000,
+            token_a_fees: Some(token_a_fees),
+            token_b_fees: Some(token_b_fees),
+        }
+    }
+
+    #[test]
+    fn test_reserve_value() {
+        // 1.5 tokens with 6 decimals at 2 per token
+        assert_eq!(
+            reserve_value(1_500_000, 6, Decimal::from(2)).unwrap(),
+            Decimal::from(3)
+        );
+        // Fractional prices keep full precision
+        assert_eq!(
+            reserve_value(250_000_000, 9, Decimal::new(4, 1)).unwrap(),
+            Decimal::new(1, 1)
+        );
+        assert_eq!(
+            reserve_value(0, 9, Decimal::from(100)).unwrap(),
+            Decimal::ZERO
+        );
+        assert!(reserve_value(u64::MAX, 0, Decimal::MAX).is_err());
+        assert!(reserve_value(1, 29, Decimal::ONE).is_err());
+    }
+
+    #[test]
+    fn test_pool_history_capacity() {
+        let mut history = PoolHistory::new(2);
+        assert!(history.record(observation(1, 0, 0)));
+        assert!(history.record(observation(2, 0, 0)));
+        // Out of order observations are ignored
+        assert!(!history.record(observation(2, 0, 0)));
+        assert!(history.record(observation(3, 0, 0)));
+        let timestamps: Vec<i64> = history.observations().iter().map(|o| o.timestamp).collect();
+        assert_eq!(timestamps, vec![2, 3]);
+    }
+
+    #[test]
+    fn test_fee_accrual() {
+        let mut history = PoolHistory::new(10);
+        history.record(observation(100, 1_000, 50));
+        assert_eq!(history.fee_accrual(), None);
+        history.record(observation(160, 1_600, 80));
+        // The admin withdraws the fees, then they accrue again
+        history.record(observation(220, 0, 0));
+        history.record(observation(280, 400, 20));
+        assert_eq!(
+            history.fee_accrual(),
+            Some(FeeAccrual {
+                token_a_fees: 1_000,
+                token_b_fees: 50,
+                elapsed: 180,
+            })
+        );
+        // Observations without the fees vaults are skipped
+        history.record(Observation {
+            token_a_fees: None,
+            ..observation(340, 0, 0)
+        });
+        assert_eq!(history.fee_accrual().unwrap().elapsed, 180);
+    }
+
+    #[test]
+    fn test_volume() {
+        let reserves =
+            |timestamp: i64, token_a_amount: u64, token_b_amount: u64, fees: u64| Observation {
+                timestamp,
+                token_a_amount,
+                token_b_amount,
+                ..observation(timestamp, fees, 0)
+            };
+        let mut history = PoolHistory::new(10);
+        history.record(reserves(0, 1_000, 1_000, 0));
+        // A to B with an owner fee of 1 sent to the fees vault
+        history.record(reserves(10, 1_100, 910, 1));
+        // A deposit is not volume
+        history.record(reserves(20, 2_200, 1_820, 1));
+        // B to A, in the next interval
+        history.record(reserves(70, 2_000, 2_040, 1));
+        assert_eq!(
+            history.volume(60),
+            vec![
+                Volume {
+                    start: 0,
+                    interval: 60,
+                    a_to_b_in: 101,
+                    a_to_b_out: 90,
+                    swaps: 1,
+                    ..Volume::default()
+                },
+                Volume {
+                    start: 60,
+                    interval: 60,
+                    b_to_a_in: 220,
+                    b_to_a_out: 200,
+                    swaps: 1,
+                    ..Volume::default()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_time_weighted_average() {
+        let prices = [
+            (0, Decimal::from(10)),
+            (60, Decimal::from(20)),
+            // A short spike barely moves the average
+            (100, Decimal::from(1_000)),
+            (101, Decimal::from(20)),
+            (120, Decimal::from(20)),
+        ];
+        // 40s at 20, 1s at 1000 and 19s at 20 over the last minute
+        assert_eq!(
+            time_weighted_average(&prices, 60),
+            Some(Decimal::from(59 * 20 + 1_000) / Decimal::from(60))
+        );
+        // The price before the window holds from its start
+        assert_eq!(
+            time_weighted_average(&prices[..2], 30),
+            Some(Decimal::from(10))
+        );
+        assert_eq!(time_weighted_average(&prices[..1], 60), None);
+        assert_eq!(time_weighted_average(&[], 60), None);
+    }
+}
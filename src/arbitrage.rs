@@ -0,0 +1,319 @@
+//! Detection of cyclic price discrepancies between Rarefish pools sharing mints, for
+//! monitoring and searchers. Only cycles that are profitable after all fees are reported.
+
+use anyhow::Result;
+use solana_sdk::pubkey::Pubkey;
+
+use crate::registry::MarketRegistry;
+use crate::router::RouteHop;
+
+/// A cycle of swaps returning more of the starting mint than it takes
+#[derive(Clone, Debug)]
+pub struct Opportunity {
+    /// The mint the cycle starts and ends with
+    pub mint: Pubkey,
+    /// The size maximizing the profit
+    pub in_amount: u64,
+    pub out_amount: u64,
+    pub profit: u64,
+    pub hops: Vec<RouteHop>,
+}
+
+/// A cycle of pools, each swap selling the output of the previous one
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Cycle {
+    /// (pool, input mint, output mint) of every swap
+    swaps: Vec<(Pubkey, Pubkey, Pubkey)>,
+}
+
+impl Cycle {
+    /// Output of the cycle for `amount`, with the quote of every swap
+    fn quote(&self, registry: &MarketRegistry, amount: u64) -> Result<Vec<RouteHop>> {
+        let mut hops = Vec::with_capacity(self.swaps.len());
+        let mut amount = amount;
+        for (pool, input_mint, output_mint) in &self.swaps {
+            let quote = registry.quote(pool, input_mint, amount)?;
+            amount = quote.out_amount;
+            hops.push(RouteHop {
+                pool: *pool,
+                input_mint: *input_mint,
+                output_mint: *output_mint,
+                quote,
+            });
+        }
+        Ok(hops)
+    }
+
+    fn out_amount(&self, registry: &MarketRegistry, amount: u64) -> Option<u64> {
+        let hops = self.quote(registry, amount).ok()?;
+        hops.last().map(|hop| hop.quote.out_amount)
+    }
+}
+
+/// Finds every profitable cycle of two pools of the same pair or three pools of a triangle of
+/// mints, sized to the most profitable amount
+pub fn find_opportunities(registry: &MarketRegistry) -> Vec<Opportunity> {
+    let mut opportunities: Vec<Opportunity> = cycles(registry)
+        .into_iter()
+        .filter_map(|cycle| {
+            let (pool, mint, _) = cycle.swaps[0];
+            let (token_a_amount, token_b_amount) = registry.get(&pool)?.reserves()?;
+            let max_amount = if registry.get(&pool)?.reserve_mints()[0] == mint {
+                token_a_amount
+            } else {
+                token_b_amount
+            };
+            let (in_amount, profit) =
+                best_size(|amount| cycle.out_amount(registry, amount), max_amount)?;
+            let hops = cycle.quote(registry, in_amount).ok()?;
+            Some(Opportunity {
+                mint,
+                in_amount,
+                out_amount: in_amount + profit,
+                profit,
+                hops,
+            })
+        })
+        .collect();
+    opportunities.sort_by(|a, b| b.profit.cmp(&a.profit));
+    opportunities
+}
+
+/// Every cycle of 2 or 3 distinct pools, in both directions. Rotations of the same cycle are
+/// listed once, starting with the lowest pool key.
+fn cycles(registry: &MarketRegistry) -> Vec<Cycle> {
+    let mut pools: Vec<(Pubkey, [Pubkey; 2])> = registry
+        .markets()
+        .map(|market| (market.market_key, market.reserve_mints()))
+        .collect();
+    pools.sort();
+    let other = |mints: &[Pubkey; 2], mint: &Pubkey| {
+        if mints[0] == *mint {
+            Some(mints[1])
+        } else if mints[1] == *mint {
+            Some(mints[0])
+        } else {
+            None
+        }
+    };
+
+    let mut cycles = vec![];
+    for (first_index, (first, first_mints)) in pools.iter().enumerate() {
+        for start in first_mints {
+            let middle = other(first_mints, start).unwrap();
+            for (second, second_mints) in &pools[first_index + 1..] {
+                // Two pools of the same pair
+                if other(second_mints, &middle) == Some(*start) {
+                    cycles.push(Cycle {
+                        swaps: vec![(*first, *start, middle), (*second, middle, *start)],
+                    });
+                }
+                // Three pools of a triangle of mints
+                let Some(last) = other(second_mints, &middle) else {
+                    continue;
+                };
+                if last == *start {
+                    continue;
+                }
+                for (third, third_mints) in &pools[first_index + 1..] {
+                    if third != second && other(third_mints, &last) == Some(*start) {
+                        cycles.push(Cycle {
+                            swaps: vec![
+                                (*first, *start, middle),
+                                (*second, middle, last),
+                                (*third, last, *start),
+                            ],
+                        });
+                    }
+                }
+            }
+        }
+    }
+    cycles
+}
+
+/// The amount up to `max_amount` maximizing `out_amount(amount) - amount` and that profit,
+/// or `None` if no amount is profitable. The profit of a cycle through convex curves is
+/// concave in the amount, so a ternary search finds the maximum.
+fn best_size(out_amount: impl Fn(u64) -> Option<u64>, max_amount: u64) -> Option<(u64, u64)> {
+    let profit = |amount: u64| {
+        out_amount(amount)
+            .map(|out| i128::from(out) - i128::from(amount))
+            .unwrap_or(i128::MIN)
+    };
+    let (mut low, mut high) = (1_u64, max_amount.max(1));
+    while high - low > 2 {
+        let third = (high - low) / 3;
+        let (left, right) = (low + third, high - third);
+        if profit(left) < profit(right) {
+            low = left + 1;
+        } else {
+            high = right;
+        }
+    }
+    (low..=high)
+        .map(|amount| (amount, profit(amount)))
+        .max_by_key(|(_, profit)| *profit)
+        .filter(|(_, profit)| *profit > 0)
+        .map(|(amount, profit)| (amount, profit as u64))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::best_size;
+
+    /// A cycle through two constant product pools without fees, the second one pricing
+    /// the output `premium` percent higher
+    fn two_pool_cycle(premium: u128) -> impl Fn(u64) -> Option<u64> {
+        move |amount| {
+            let (source, destination) = (1_000_000_000_u128, 1_000_000_000_u128);
+            let out = u128::from(amount) * destination / (source + u128::from(amount));
+            let (source, destination) = (1_000_000_000_u128, 1_000_000_000 * (100 + premium) / 100);
+            Some((out * destination / (source + out)) as u64)
+        }
+    }
+
+    #[test]
+    fn test_best_size() {
+        let (amount, profit) = best_size(two_pool_cycle(10), 1_000_000_000).unwrap();
+        assert!(profit > 0);
+        // The profit is lower on either side of the optimum
+        for other in [amount / 2, amount * 2] {
+            let other_profit = two_pool_cycle(10)(other).unwrap() as i128 - other as i128;
+            assert!(other_profit < profit as i128);
+        }
+
+        assert_eq!(best_size(two_pool_cycle(0), 1_000_000_000), None);
+        assert_eq!(best_size(|_| None, 1_000_000_000), None);
+    }
+
+    #[cfg(feature = "client")]
+    #[tokio::test]
+    async fn test_cycles() {
+        use crate::registry::MarketRegistry;
+        use crate::test_utils::{LocalPool, LocalPoolConfig};
+
+        use super::cycles;
+
+        let mut local_pool = LocalPool::new(LocalPoolConfig::default()).await;
+        let (token_a_mint, token_b_mint) = (local_pool.token_a_mint, local_pool.token_b_mint);
+        let intermediate_mint = local_pool.add_mint(6).await;
+        let mut registry = MarketRegistry::new();
+        registry.insert(local_pool.adapter().await);
+        for (token_a_mint, token_b_mint) in [
+            (token_a_mint, token_b_mint),
+            (token_a_mint, intermediate_mint),
+            (intermediate_mint, token_b_mint),
+        ] {
+            let pool = local_pool
+                .add_pool(token_a_mint, token_b_mint, LocalPoolConfig::default())
+                .await;
+            registry.insert(local_pool.pool_adapter(pool).await);
+        }
+
+        // Both directions of the pair and of the two triangles, one through each A/B pool
+        let cycles = cycles(&registry);
+        assert_eq!(cycles.len(), 6);
+        assert_eq!(
+            cycles.iter().filter(|cycle| cycle.swaps.len() == 2).count(),
+            2
+        );
+        for cycle in &cycles {
+            let (first_pool, start, _) = cycle.swaps[0];
+            assert_eq!(cycle.swaps.last().unwrap().2, start);
+            for (swap, next) in cycle.swaps.iter().zip(&cycle.swaps[1..]) {
+                assert_eq!(swap.2, next.1);
+            }
+            assert!(cycle.swaps.iter().all(|(pool, ..)| *pool >= first_pool));
+            let pools: std::collections::HashSet<_> =
+                cycle.swaps.iter().map(|(pool, ..)| pool).collect();
+            assert_eq!(pools.len(), cycle.swaps.len());
+        }
+        for (index, cycle) in cycles.iter().enumerate() {
+            assert!(!cycles[index + 1..].contains(cycle));
+        }
+    }
+
+    #[cfg(feature = "client")]
+    #[tokio::test]
+    async fn test_find_opportunities() {
+        use std::collections::HashMap;
+
+        use solana_sdk::signer::Signer;
+
+        use crate::registry::MarketRegistry;
+        use crate::router::Route;
+        use crate::test_utils::{LocalPool, LocalPoolConfig};
+
+        use super::find_opportunities;
+
+        let mut local_pool = LocalPool::new(LocalPoolConfig::default()).await;
+        let (token_a_mint, token_b_mint) = (local_pool.token_a_mint, local_pool.token_b_mint);
+        let mut registry = MarketRegistry::new();
+        registry.insert(local_pool.adapter().await);
+        // A second pool of the pair at the same price has nothing to arbitrage after fees
+        let same_price = local_pool
+            .add_pool(token_a_mint, token_b_mint, LocalPoolConfig::default())
+            .await;
+        registry.insert(local_pool.pool_adapter(same_price).await);
+        assert!(find_opportunities(&registry).is_empty());
+
+        // A third one pricing A 10% higher
+        let premium = local_pool
+            .add_pool(
+                token_a_mint,
+                token_b_mint,
+                LocalPoolConfig {
+                    initial_supply_b: 1_100_000_000_000,
+                    ..LocalPoolConfig::default()
+                },
+            )
+            .await;
+        registry.insert(local_pool.pool_adapter(premium).await);
+        let opportunities = find_opportunities(&registry);
+        assert!(!opportunities.is_empty());
+        assert!(opportunities
+            .windows(2)
+            .all(|pair| pair[0].profit >= pair[1].profit));
+        let best = &opportunities[0];
+        // A is sold on the premium pool and bought back on one of the others
+        assert!(best
+            .hops
+            .iter()
+            .any(|hop| hop.pool == premium && hop.input_mint == token_a_mint));
+        assert_eq!(best.out_amount, best.in_amount + best.profit);
+        assert_eq!(best.hops.last().unwrap().quote.out_amount, best.out_amount);
+
+        // The cycle executes for the reported profit
+        let user = if best.mint == token_a_mint {
+            local_pool.create_user(best.in_amount, 0).await
+        } else {
+            local_pool.create_user(0, best.in_amount).await
+        };
+        let start_account = if best.mint == token_a_mint {
+            user.token_a
+        } else {
+            user.token_b
+        };
+        let route = Route {
+            in_amount: best.in_amount,
+            out_amount: best.out_amount,
+            hops: best.hops.clone(),
+        };
+        let token_accounts =
+            HashMap::from([(token_a_mint, user.token_a), (token_b_mint, user.token_b)]);
+        let ixns = route
+            .instructions(
+                &registry,
+                &user.keypair.pubkey(),
+                &token_accounts,
+                best.out_amount,
+            )
+            .unwrap();
+        local_pool.execute(&ixns, &user.keypair).await.unwrap();
+        assert_eq!(
+            local_pool.token_balance(&start_account).await,
+            best.out_amount + best.in_amount - best.hops[0].quote.in_amount
+        );
+    }
+}
@@ -0,0 +1,141 @@
+//! Replays of recorded pool states, to evaluate what a strategy would have done at past
+//! slots with the same quote math as production.
+//!
+//! Snapshots are [`PoolFixture`]s, as written by `rarefish record-fixture`: one pool with
+//! its vaults and mints at a slot. A pool keeps the state of its latest snapshot until the
+//! next one, so pools recorded at different slots can be replayed together.
+
+use std::fs;
+use std::path::Path;
+
+use anyhow::{anyhow, Result};
+use solana_sdk::pubkey::Pubkey;
+
+use crate::amm::Amm;
+use crate::fixture::PoolFixture;
+use crate::registry::MarketRegistry;
+use crate::router::{quote_route, Route};
+use crate::JupiterRarefish;
+
+/// The outcome of a strategy at a slot of the replay
+#[derive(Clone, Debug)]
+pub struct BacktestStep<T> {
+    pub slot: u64,
+    pub outcome: T,
+}
+
+/// Recorded pool states, in slot order
+#[derive(Clone, Debug, Default)]
+pub struct Backtest {
+    snapshots: Vec<PoolFixture>,
+}
+
+impl Backtest {
+    pub fn new(mut snapshots: Vec<PoolFixture>) -> Self {
+        // Stable so snapshots of a pool at the same slot apply in the given order
+        snapshots.sort_by_key(|snapshot| snapshot.slot);
+        Self { snapshots }
+    }
+
+    /// Loads every `.json` and `.bin` snapshot of `directory`
+    pub fn load_dir(directory: impl AsRef<Path>) -> Result<Self> {
+        let directory = directory.as_ref();
+        let mut snapshots = Vec::new();
+        for entry in fs::read_dir(directory)
+            .map_err(|e| anyhow!("Failed to read snapshots {}: {}", directory.display(), e))?
+        {
+            let path = entry?.path();
+            if path
+                .extension()
+                .map_or(false, |extension| extension == "json" || extension == "bin")
+            {
+                snapshots.push(
+                    PoolFixture::load(&path).map_err(|e| {
+                        anyhow!("Failed to load snapshot {}: {}", path.display(), e)
+                    })?,
+                );
+            }
+        }
+        Ok(Self::new(snapshots))
+    }
+
+    pub fn snapshots(&self) -> &[PoolFixture] {
+        &self.snapshots
+    }
+
+    /// The slots with at least one snapshot, in order
+    pub fn slots(&self) -> Vec<u64> {
+        let mut slots: Vec<_> = self
+            .snapshots
+            .iter()
+            .map(|snapshot| snapshot.slot)
+            .collect();
+        slots.dedup();
+        slots
+    }
+
+    /// The markets as of `slot`: the latest snapshot of each pool at or before it
+    pub fn registry_at(&self, slot: u64) -> Result<MarketRegistry> {
+        let mut registry = MarketRegistry::new();
+        for snapshot in self
+            .snapshots
+            .iter()
+            .take_while(|snapshot| snapshot.slot <= slot)
+        {
+            registry.insert(market(snapshot)?);
+        }
+        Ok(registry)
+    }
+
+    /// The adapter of `pool` as of `slot`, from its latest snapshot at or before it
+    pub fn market_at(&self, pool: &Pubkey, slot: u64) -> Result<JupiterRarefish> {
+        self.snapshots
+            .iter()
+            .take_while(|snapshot| snapshot.slot <= slot)
+            .filter(|snapshot| snapshot.pool == *pool)
+            .last()
+            .ok_or_else(|| anyhow!("No snapshot of pool {} at or before slot {}", pool, slot))
+            .and_then(market)
+    }
+
+    /// Evaluates `strategy` at every slot with a snapshot, in order, with the markets as of
+    /// that slot
+    pub fn run<T>(
+        &self,
+        mut strategy: impl FnMut(u64, &MarketRegistry) -> T,
+    ) -> Result<Vec<BacktestStep<T>>> {
+        let mut registry = MarketRegistry::new();
+        let mut steps = Vec::new();
+        for (index, snapshot) in self.snapshots.iter().enumerate() {
+            registry.insert(market(snapshot)?);
+            let last_of_slot = self
+                .snapshots
+                .get(index + 1)
+                .map_or(true, |next| next.slot != snapshot.slot);
+            if last_of_slot {
+                steps.push(BacktestStep {
+                    slot: snapshot.slot,
+                    outcome: strategy(snapshot.slot, &registry),
+                });
+            }
+        }
+        Ok(steps)
+    }
+
+    /// What selling `amount` of `input_mint` through `pools` would have given at every slot
+    pub fn replay_route(
+        &self,
+        pools: &[Pubkey],
+        input_mint: &Pubkey,
+        amount: u64,
+    ) -> Result<Vec<BacktestStep<Result<Route>>>> {
+        self.run(|_, registry| quote_route(registry, pools, input_mint, amount))
+    }
+}
+
+/// The ready-to-quote adapter of a snapshot
+fn market(snapshot: &PoolFixture) -> Result<JupiterRarefish> {
+    let mut market = JupiterRarefish::new_from_keyed_account(&snapshot.keyed_account()?)?;
+    market.update(&snapshot.account_map())?;
+    Ok(market)
+}
@@ -0,0 +1,47 @@
+use anyhow::{anyhow, Result};
+use hyperplane::state::SwapPool;
+use jupiter_rarefish::amm::Amm;
+use jupiter_rarefish::JupiterRarefish;
+use solana_client::rpc_client::RpcClient;
+use solana_sdk::pubkey::Pubkey;
+
+/// Loads the pool and its vaults into a ready-to-quote adapter
+pub fn load_amm(rpc: &RpcClient, pool_key: &Pubkey) -> Result<(JupiterRarefish, SwapPool)> {
+    let jupiter_rarefish = JupiterRarefish::load(rpc, pool_key)?;
+    let pool = jupiter_rarefish.pool().clone();
+    Ok((jupiter_rarefish, pool))
+}
+
+/// Token A and token B decimals of a loaded adapter
+pub fn amm_decimals(jupiter_rarefish: &JupiterRarefish) -> Result<(u8, u8)> {
+    jupiter_rarefish
+        .token_a_decimals()
+        .zip(jupiter_rarefish.token_b_decimals())
+        .ok_or_else(|| {
+            anyhow!(
+                "Mint decimals of pool {} not loaded",
+                jupiter_rarefish.key()
+            )
+        })
+}
+
+/// Decimals of `mint`, token A or token B of a loaded adapter
+pub fn mint_decimals(jupiter_rarefish: &JupiterRarefish, mint: &Pubkey) -> Result<u8> {
+    let (token_a_decimals, token_b_decimals) = amm_decimals(jupiter_rarefish)?;
+    let [token_a_mint, token_b_mint] = jupiter_rarefish.reserve_mints();
+    if *mint == token_a_mint {
+        Ok(token_a_decimals)
+    } else if *mint == token_b_mint {
+        Ok(token_b_decimals)
+    } else {
+        Err(anyhow!(
+            "Mint {} is not traded by pool {}",
+            mint,
+            jupiter_rarefish.key()
+        ))
+    }
+}
+
+pub fn ui_amount(amount: u64, decimals: u8) -> f64 {
+    amount as f64 / 10.0_f64.powi(decimals as i32)
+}
@@ -0,0 +1,68 @@
+use anyhow::Result;
+use jupiter_rarefish::decode::decode_transaction_transfers;
+use solana_client::rpc_client::RpcClient;
+use solana_client::rpc_config::RpcTransactionConfig;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Signature;
+use solana_transaction_status::UiTransactionEncoding;
+
+use crate::accounts::{load_amm, mint_decimals, ui_amount};
+
+pub fn run(rpc: &RpcClient, signature: &Signature, program_id: &Pubkey) -> Result<()> {
+    let transaction = rpc.get_transaction_with_config(
+        signature,
+        RpcTransactionConfig {
+            encoding: Some(UiTransactionEncoding::Base64),
+            commitment: None,
+            max_supported_transaction_version: Some(0),
+        },
+    )?;
+    let swaps = decode_transaction_transfers(program_id, &transaction)?;
+    println!("Transaction {} (slot {})", signature, transaction.slot);
+    if swaps.is_empty() {
+        println!("No Rarefish swaps found");
+        return Ok(());
+    }
+    for executed in swaps {
+        let swap = &executed.swap;
+        let (jupiter_rarefish, _) = load_amm(rpc, &swap.pool)?;
+        let direction = if swap.source_mint == jupiter_rarefish.reserve_mints()[0] {
+            "AtoB"
+        } else {
+            "BtoA"
+        };
+        let source_decimals = mint_decimals(&jupiter_rarefish, &swap.source_mint)?;
+        let destination_decimals = mint_decimals(&jupiter_rarefish, &swap.destination_mint)?;
+        let amount_in = executed.amount_in();
+
+        println!();
+        println!(
+            "Swap in instruction {}{}",
+            executed.instruction_index,
+            if executed.inner { " (CPI)" } else { "" }
+        );
+        println!("  Pool:               {} ({})", swap.pool, direction);
+        println!("  User:               {}", swap.user);
+        println!("  Source mint:        {}", swap.source_mint);
+        println!("  Destination mint:   {}", swap.destination_mint);
+        println!(
+            "  Amount in:          {} (requested {})",
+            ui_amount(amount_in, source_decimals),
+            ui_amount(swap.amount_in, source_decimals)
+        );
+        println!(
+            "  Amount out:         {} (minimum {})",
+            ui_amount(executed.amount_out, destination_decimals),
+            ui_amount(swap.minimum_amount_out, destination_decimals)
+        );
+        println!(
+            "  Owner fee:          {}",
+            ui_amount(executed.owner_fee, source_decimals)
+        );
+        println!(
+            "  Host fee:           {}",
+            ui_amount(executed.host_fee, source_decimals)
+        );
+    }
+    Ok(())
+}
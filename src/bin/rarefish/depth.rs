@@ -0,0 +1,105 @@
+use std::fs::File;
+use std::io::{self, Write};
+
+use anyhow::Result;
+use jupiter_rarefish::amm::{Amm, QuoteParams, SwapMode};
+use jupiter_rarefish::JupiterRarefish;
+use solana_client::rpc_client::RpcClient;
+use solana_sdk::pubkey::Pubkey;
+
+use crate::accounts::{amm_decimals, load_amm, ui_amount};
+
+pub fn run(rpc: &RpcClient, pool_key: &Pubkey, sizes: &[f64], output: Option<&str>) -> Result<()> {
+    let (jupiter_rarefish, pool) = load_amm(rpc, pool_key)?;
+    let (token_a_decimals, token_b_decimals) = amm_decimals(&jupiter_rarefish)?;
+
+    let mut writer: Box<dyn Write> = match output {
+        Some(path) => Box::new(File::create(path)?),
+        None => Box::new(io::stdout()),
+    };
+    writeln!(
+        writer,
+        "direction,input_mint,output_mint,in_amount,out_amount,price,price_impact_pct"
+    )?;
+    for (direction, input_mint, output_mint, input_decimals, output_decimals) in [
+        (
+            "AtoB",
+            pool.token_a_mint,
+            pool.token_b_mint,
+            token_a_decimals,
+            token_b_decimals,
+        ),
+        (
+            "BtoA",
+            pool.token_b_mint,
+            pool.token_a_mint,
+            token_b_decimals,
+            token_a_decimals,
+        ),
+    ] {
+        let side = Side {
+            jupiter_rarefish: &jupiter_rarefish,
+            input_mint,
+            output_mint,
+            input_decimals,
+            output_decimals,
+        };
+        // The price of a trade no larger than one unit is used as the reference spot price
+        let reference_amount = side.raw_amount(sizes.iter().copied().fold(1.0, f64::min));
+        let spot_price = side.price(reference_amount, side.quote(reference_amount)?);
+        for size in sizes {
+            let in_amount = side.raw_amount(*size);
+            let out_amount = side.quote(in_amount)?;
+            let price = side.price(in_amount, out_amount);
+            let price_impact_pct = if spot_price > 0.0 {
+                (1.0 - price / spot_price) * 100.0
+            } else {
+                0.0
+            };
+            writeln!(
+                writer,
+                "{},{},{},{},{},{},{}",
+                direction,
+                input_mint,
+                output_mint,
+                ui_amount(in_amount, input_decimals),
+                ui_amount(out_amount, output_decimals),
+                price,
+                price_impact_pct
+            )?;
+        }
+    }
+    Ok(())
+}
+
+struct Side<'a> {
+    jupiter_rarefish: &'a JupiterRarefish,
+    input_mint: Pubkey,
+    output_mint: Pubkey,
+    input_decimals: u8,
+    output_decimals: u8,
+}
+
+impl Side<'_> {
+    fn raw_amount(&self, size: f64) -> u64 {
+        (size * 10.0_f64.powi(self.input_decimals as i32)) as u64
+    }
+
+    fn quote(&self, amount: u64) -> Result<u64> {
+        let quote = self.jupiter_rarefish.quote(&QuoteParams {
+            input_mint: self.input_mint,
+            output_mint: self.output_mint,
+            amount,
+            swap_mode: SwapMode::ExactIn,
+        })?;
+        Ok(quote.out_amount)
+    }
+
+    /// Output tokens received per input token, in UI units
+    fn price(&self, in_amount: u64, out_amount: u64) -> f64 {
+        if in_amount == 0 {
+            return 0.0;
+        }
+        ui_amount(out_amount, self.output_decimals) / ui_amount(in_amount, self.input_decimals)
+    }
+}
@@ -0,0 +1,147 @@
+use anyhow::{anyhow, Result};
+use hyperplane::state::{SwapPool, SwapState};
+use jupiter_rarefish::amm::Amm;
+use jupiter_rarefish::CurveParams;
+use solana_client::rpc_client::RpcClient;
+use solana_sdk::pubkey::Pubkey;
+
+use crate::accounts::{amm_decimals, load_amm, ui_amount};
+
+pub fn run(rpc: &RpcClient, pool_key: &Pubkey) -> Result<()> {
+    let (jupiter_rarefish, pool) = load_amm(rpc, pool_key)?;
+    let (token_a_decimals, token_b_decimals) = amm_decimals(&jupiter_rarefish)?;
+    let (token_a_amount, token_b_amount) = jupiter_rarefish
+        .reserves()
+        .ok_or_else(|| anyhow!("Vaults of pool {} not loaded", pool_key))?;
+    let fees_balances = jupiter_rarefish.fees_balances();
+
+    println!("Pool:               {}", pool_key);
+    println!("Owner program:      {}", jupiter_rarefish.program_id());
+    println!("Admin:              {}", pool.admin);
+    println!("Pool authority:     {}", pool.pool_authority);
+    println!("LP mint:            {}", pool.pool_token_mint);
+    println!();
+    print_side(
+        "Token A",
+        &pool.token_a_mint,
+        token_a_decimals,
+        &jupiter_rarefish.token_a_program(),
+        (&pool.token_a_vault, token_a_amount),
+        (
+            &pool.token_a_fees_vault,
+            fees_balances.map(|(token_a_fees, _)| token_a_fees),
+        ),
+    );
+    print_side(
+        "Token B",
+        &pool.token_b_mint,
+        token_b_decimals,
+        &jupiter_rarefish.token_b_program(),
+        (&pool.token_b_vault, token_b_amount),
+        (
+            &pool.token_b_fees_vault,
+            fees_balances.map(|(_, token_b_fees)| token_b_fees),
+        ),
+    );
+    print_fees(&pool);
+    print_curve(&pool)?;
+    Ok(())
+}
+
+fn print_side(
+    name: &str,
+    mint: &Pubkey,
+    decimals: u8,
+    token_program: &Pubkey,
+    (vault, amount): (&Pubkey, u64),
+    (fees_vault, fees): (&Pubkey, Option<u64>),
+) {
+    println!("{}", name);
+    println!("  Mint:             {} ({} decimals)", mint, decimals);
+    println!("  Token program:    {}", token_program);
+    println!(
+        "  Vault:            {} ({} / {})",
+        vault,
+        amount,
+        ui_amount(amount, decimals)
+    );
+    match fees {
+        Some(fees) => println!(
+            "  Fees vault:       {} ({} / {})",
+            fees_vault,
+            fees,
+            ui_amount(fees, decimals)
+        ),
+        None => println!("  Fees vault:       {} (not loaded)", fees_vault),
+    }
+    println!();
+}
+
+fn print_fees(pool: &SwapPool) {
+    let fees = pool.fees();
+    println!("Fees");
+    print_fee(
+        "Trade fee",
+        fees.trade_fee_numerator,
+        fees.trade_fee_denominator,
+    );
+    print_fee(
+        "Owner trade fee",
+        fees.owner_trade_fee_numerator,
+        fees.owner_trade_fee_denominator,
+    );
+    print_fee(
+        "Owner withdraw fee",
+        fees.owner_withdraw_fee_numerator,
+        fees.owner_withdraw_fee_denominator,
+    );
+    print_fee(
+        "Host fee",
+        fees.host_fee_numerator,
+        fees.host_fee_denominator,
+    );
+    println!();
+}
+
+fn print_fee(name: &str, numerator: u64, denominator: u64) {
+    let bps = if denominator == 0 {
+        0.0
+    } else {
+        numerator as f64 * 10_000.0 / denominator as f64
+    };
+    println!(
+        "  {:<19} {}/{} ({} bps)",
+        format!("{}:", name),
+        numerator,
+        denominator,
+        bps
+    );
+}
+
+fn print_curve(pool: &SwapPool) -> Result<()> {
+    println!("Curve");
+    match CurveParams::from_pool(pool)? {
+        CurveParams::ConstantProduct => {
+            println!("  Type:             ConstantProduct");
+        }
+        CurveParams::ConstantPrice { token_b_price } => {
+            println!("  Type:             ConstantPrice");
+            println!("  Token B price:    {}", token_b_price);
+        }
+        CurveParams::Offset { token_b_offset } => {
+            println!("  Type:             Offset");
+            println!("  Token B offset:   {}", token_b_offset);
+        }
+        CurveParams::Stable {
+            amp,
+            token_a_factor,
+            token_b_factor,
+        } => {
+            println!("  Type:             Stable");
+            println!("  Amplification:    {}", amp);
+            println!("  Token A factor:   {}", token_a_factor);
+            println!("  Token B factor:   {}", token_b_factor);
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,297 @@
+use std::sync::Arc;
+
+use anyhow::Result;
+use clap::{Parser, Subcommand};
+use jupiter_rarefish::failover::FailoverSender;
+use jupiter_rarefish::rate_limit::{RateLimitedSender, RateLimiter};
+use solana_client::http_sender::HttpSender;
+use solana_client::rpc_client::{RpcClient, RpcClientConfig};
+use solana_client::rpc_sender::RpcSender;
+use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Signature;
+
+mod accounts;
+mod decode_tx;
+mod depth;
+mod inspect;
+mod record_fixture;
+#[cfg(any(feature = "server", feature = "grpc", feature = "price-feed"))]
+mod serve;
+mod simulate;
+mod swap;
+mod watch;
+
+#[derive(Parser, Debug)]
+#[command(
+    name = "rarefish",
+    about = "Tools for inspecting and quoting Rarefish pools"
+)]
+struct Cli {
+    /// The RPC endpoint used to fetch accounts
+    #[arg(
+        long,
+        env = "RPC_URL",
+        default_value = "https://api.mainnet-beta.solana.com/"
+    )]
+    rpc_url: String,
+    /// Endpoints used in turn when the RPC endpoint fails or falls behind, comma separated
+    #[arg(long, env = "RPC_FALLBACK_URLS", value_delimiter = ',')]
+    rpc_fallback_urls: Vec<String>,
+    /// Limits the requests sent to each RPC endpoint, for public endpoints
+    #[arg(long, env = "RPC_REQUESTS_PER_SECOND")]
+    rpc_requests_per_second: Option<u32>,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Decode a SwapPool account into human-readable output
+    Inspect {
+        /// The pubkey of the SwapPool account
+        pool: Pubkey,
+    },
+    /// Simulate a swap and compare the realized output with the adapter's quote
+    Simulate {
+        /// The pubkey of the SwapPool account
+        pool: Pubkey,
+        /// Raw amount of the input token to swap
+        amount: u64,
+        /// The mint being sold, defaults to token A of the pool
+        #[arg(long)]
+        input_mint: Option<Pubkey>,
+        /// Keypair owning the source and destination token accounts
+        #[arg(long, default_value = "keypair.json")]
+        keypair: String,
+        /// Simulate from this wallet's token accounts without its keypair, skipping
+        /// signature verification
+        #[arg(long, conflicts_with = "keypair")]
+        owner: Option<Pubkey>,
+        /// Account paying the fee of a simulation with `--owner`, defaults to the owner
+        #[arg(long, requires = "owner")]
+        payer: Option<Pubkey>,
+    },
+    /// Swap from the associated token accounts of a keypair and print the realized amounts
+    Swap {
+        /// The pubkey of the SwapPool account
+        pool: Pubkey,
+        /// Raw amount of the input token to swap
+        amount: u64,
+        /// The mint being sold, defaults to token A of the pool
+        #[arg(long)]
+        input_mint: Option<Pubkey>,
+        /// Keypair owning the source and destination token accounts
+        #[arg(long, default_value = "keypair.json")]
+        keypair: String,
+        /// Largest shortfall of the output from the quote accepted, in basis points
+        #[arg(long, default_value_t = 50)]
+        slippage_bps: u16,
+        /// Wrap SOL sold and unwrap SOL bought, instead of using the wrapped SOL account
+        #[arg(long)]
+        native_sol: bool,
+        /// Pay this percentile of the recent priority fees of the pool
+        #[arg(long)]
+        priority_fee_percentile: Option<u8>,
+    },
+    /// Quote a ladder of sizes in both directions and write a CSV of the pool depth
+    Depth {
+        /// The pubkey of the SwapPool account
+        pool: Pubkey,
+        /// Comma separated trade sizes in UI units of the input token
+        #[arg(
+            long,
+            value_delimiter = ',',
+            default_value = "1,10,100,1000,10000,100000"
+        )]
+        sizes: Vec<f64>,
+        /// CSV file to write, defaults to stdout
+        #[arg(long, short)]
+        output: Option<String>,
+    },
+    /// Record a pool and its vaults and mints as a fixture for offline tests
+    RecordFixture {
+        /// The pubkey of the SwapPool account
+        pool: Pubkey,
+        /// Directory the fixture is written to
+        #[arg(long, default_value = "tests/fixtures")]
+        directory: String,
+        /// Write bincode instead of JSON
+        #[arg(long)]
+        bincode: bool,
+    },
+    /// Decode the Rarefish swaps executed in a transaction
+    DecodeTx {
+        /// The signature of the transaction
+        signature: Signature,
+        /// The hyperplane deployment whose swaps are decoded, defaults to the mainnet program
+        #[arg(long)]
+        program_id: Option<Pubkey>,
+    },
+    /// Stream the spot price and reserves of a pool as its vaults change
+    Watch {
+        /// The pubkey of the SwapPool account
+        pool: Pubkey,
+        /// The websocket endpoint, derived from the RPC url if not set
+        #[arg(long, env = "WS_URL")]
+        ws_url: Option<String>,
+        /// Print one JSON object per update instead of a table
+        #[arg(long)]
+        json: bool,
+    },
+    /// Serve quotes for a set of pools over HTTP
+    #[cfg(feature = "server")]
+    Serve {
+        /// The pubkeys of the SwapPool accounts to serve
+        #[arg(required = true)]
+        pools: Vec<Pubkey>,
+        /// Address the server listens on
+        #[arg(long, default_value = "0.0.0.0:8080")]
+        listen: std::net::SocketAddr,
+        /// Interval between refreshes of the pools, in milliseconds
+        #[arg(long, default_value_t = 1000)]
+        refresh_interval_ms: u64,
+    },
+    /// Serve quotes for a set of pools over gRPC
+    #[cfg(feature = "grpc")]
+    ServeGrpc {
+        /// The pubkeys of the SwapPool accounts to serve
+        #[arg(required = true)]
+        pools: Vec<Pubkey>,
+        /// Address the server listens on
+        #[arg(long, default_value = "0.0.0.0:50051")]
+        listen: std::net::SocketAddr,
+        /// Interval between refreshes of the pools, in milliseconds
+        #[arg(long, default_value_t = 1000)]
+        refresh_interval_ms: u64,
+    },
+    /// Publish price and reserve updates for a set of pools over a websocket
+    #[cfg(feature = "price-feed")]
+    PriceFeed {
+        /// The pubkeys of the SwapPool accounts to publish
+        #[arg(required = true)]
+        pools: Vec<Pubkey>,
+        /// Address the websocket server listens on
+        #[arg(long, default_value = "0.0.0.0:8081")]
+        listen: std::net::SocketAddr,
+        /// Interval between refreshes of the pools, in milliseconds
+        #[arg(long, default_value_t = 400)]
+        refresh_interval_ms: u64,
+    },
+}
+
+fn main() -> Result<()> {
+    // Background refreshes report their failures through the log facade
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("warn")).init();
+    let cli = Cli::parse();
+    let rpc = rpc_client(
+        cli.rpc_url,
+        cli.rpc_fallback_urls,
+        cli.rpc_requests_per_second,
+    );
+    match cli.command {
+        Command::Inspect { pool } => inspect::run(&rpc, &pool),
+        Command::Simulate {
+            pool,
+            amount,
+            input_mint,
+            keypair,
+            owner,
+            payer,
+        } => {
+            let wallet = match owner {
+                Some(owner) => simulate::Wallet::Unsigned { owner, payer },
+                None => simulate::Wallet::Keypair(keypair),
+            };
+            simulate::run(&rpc, &pool, input_mint, amount, wallet)
+        }
+        Command::Swap {
+            pool,
+            amount,
+            input_mint,
+            keypair,
+            slippage_bps,
+            native_sol,
+            priority_fee_percentile,
+        } => swap::run(
+            &rpc,
+            &pool,
+            amount,
+            swap::SwapOptions {
+                input_mint,
+                keypair,
+                slippage_bps,
+                native_sol,
+                priority_fee_percentile,
+            },
+        ),
+        Command::Depth {
+            pool,
+            sizes,
+            output,
+        } => depth::run(&rpc, &pool, &sizes, output.as_deref()),
+        Command::RecordFixture {
+            pool,
+            directory,
+            bincode,
+        } => record_fixture::run(&rpc, &pool, &directory, bincode),
+        Command::DecodeTx {
+            signature,
+            program_id,
+        } => decode_tx::run(&rpc, &signature, &program_id.unwrap_or_else(hyperplane::id)),
+        Command::Watch { pool, ws_url, json } => {
+            let ws_url = ws_url.unwrap_or_else(|| ws_url_from_rpc_url(&rpc.url()));
+            watch::run(&rpc, &ws_url, &pool, json)
+        }
+        #[cfg(feature = "server")]
+        Command::Serve {
+            pools,
+            listen,
+            refresh_interval_ms,
+        } => serve::http(rpc, &pools, listen, refresh_interval_ms),
+        #[cfg(feature = "grpc")]
+        Command::ServeGrpc {
+            pools,
+            listen,
+            refresh_interval_ms,
+        } => serve::grpc(rpc, &pools, listen, refresh_interval_ms),
+        #[cfg(feature = "price-feed")]
+        Command::PriceFeed {
+            pools,
+            listen,
+            refresh_interval_ms,
+        } => serve::price_feed(rpc, &pools, listen, refresh_interval_ms),
+    }
+}
+
+fn rpc_client(
+    rpc_url: String,
+    fallback_urls: Vec<String>,
+    requests_per_second: Option<u32>,
+) -> RpcClient {
+    let sender = |url: String| -> Box<dyn RpcSender + Send + Sync> {
+        let sender = HttpSender::new(url);
+        match requests_per_second {
+            Some(requests_per_second) => Box::new(RateLimitedSender::new(
+                sender,
+                Arc::new(RateLimiter::new(requests_per_second)),
+            )),
+            None => Box::new(sender),
+        }
+    };
+    // A single endpoint is used as is, failures are returned
+    let endpoints = std::iter::once(rpc_url)
+        .chain(fallback_urls)
+        .map(sender)
+        .collect();
+    RpcClient::new_sender(
+        FailoverSender::new(endpoints),
+        RpcClientConfig::with_commitment(CommitmentConfig::default()),
+    )
+}
+
+fn ws_url_from_rpc_url(rpc_url: &str) -> String {
+    rpc_url
+        .replacen("https://", "wss://", 1)
+        .replacen("http://", "ws://", 1)
+}
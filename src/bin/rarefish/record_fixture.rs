@@ -0,0 +1,50 @@
+use std::fs;
+use std::path::Path;
+
+use anyhow::{anyhow, Result};
+use jupiter_rarefish::fixture::PoolFixture;
+use solana_client::rpc_client::RpcClient;
+use solana_sdk::pubkey::Pubkey;
+
+use crate::accounts::load_amm;
+
+pub fn run(rpc: &RpcClient, pool_key: &Pubkey, directory: &str, bincode: bool) -> Result<()> {
+    let (_, pool) = load_amm(rpc, pool_key)?;
+    let keys = [
+        *pool_key,
+        pool.token_a_vault,
+        pool.token_b_vault,
+        pool.token_a_fees_vault,
+        pool.token_b_fees_vault,
+        pool.token_a_mint,
+        pool.token_b_mint,
+    ];
+    // Fetch everything in a single call so the recorded accounts are consistent with each other
+    let response = rpc.get_multiple_accounts_with_commitment(&keys, rpc.commitment())?;
+    let accounts = keys
+        .iter()
+        .zip(response.value)
+        .map(|(key, account)| {
+            account
+                .map(|account| (*key, account))
+                .ok_or_else(|| anyhow!("Account {} not found", key))
+        })
+        .collect::<Result<_>>()?;
+    let fixture = PoolFixture {
+        pool: *pool_key,
+        slot: response.context.slot,
+        accounts,
+    };
+
+    fs::create_dir_all(directory)?;
+    let extension = if bincode { "bin" } else { "json" };
+    let path = Path::new(directory).join(format!("{}.{}", pool_key, extension));
+    fixture.save(&path)?;
+    println!(
+        "Recorded pool {} at slot {} to {}",
+        pool_key,
+        fixture.slot,
+        path.display()
+    );
+    Ok(())
+}
@@ -0,0 +1,68 @@
+use std::net::SocketAddr;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use anyhow::Result;
+use jupiter_rarefish::registry::{MarketRegistry, SharedRegistry};
+use solana_client::rpc_client::RpcClient;
+use solana_sdk::pubkey::Pubkey;
+
+fn load_registry(rpc: &RpcClient, pools: &[Pubkey]) -> Result<SharedRegistry> {
+    Ok(Arc::new(RwLock::new(MarketRegistry::load(rpc, pools)?)))
+}
+
+#[cfg(feature = "server")]
+pub fn http(
+    rpc: RpcClient,
+    pools: &[Pubkey],
+    listen: SocketAddr,
+    refresh_interval_ms: u64,
+) -> Result<()> {
+    let registry = load_registry(&rpc, pools)?;
+    jupiter_rarefish::registry::spawn_refresh(
+        registry.clone(),
+        rpc,
+        Duration::from_millis(refresh_interval_ms),
+    );
+    println!("Serving {} pools on {}", pools.len(), listen);
+    tokio::runtime::Runtime::new()?.block_on(jupiter_rarefish::server::serve(listen, registry))
+}
+
+#[cfg(feature = "grpc")]
+pub fn grpc(
+    rpc: RpcClient,
+    pools: &[Pubkey],
+    listen: SocketAddr,
+    refresh_interval_ms: u64,
+) -> Result<()> {
+    let registry = load_registry(&rpc, pools)?;
+    jupiter_rarefish::registry::spawn_refresh(
+        registry.clone(),
+        rpc,
+        Duration::from_millis(refresh_interval_ms),
+    );
+    println!("Serving {} pools over gRPC on {}", pools.len(), listen);
+    tokio::runtime::Runtime::new()?.block_on(jupiter_rarefish::grpc::serve(listen, registry))
+}
+
+#[cfg(feature = "price-feed")]
+pub fn price_feed(
+    rpc: RpcClient,
+    pools: &[Pubkey],
+    listen: SocketAddr,
+    refresh_interval_ms: u64,
+) -> Result<()> {
+    use jupiter_rarefish::price_feed::{spawn_publisher, PriceFeedPublisher};
+
+    let registry = load_registry(&rpc, pools)?;
+    let publisher = PriceFeedPublisher::new();
+    spawn_publisher(
+        registry,
+        publisher.clone(),
+        rpc,
+        Duration::from_millis(refresh_interval_ms),
+    );
+    println!("Publishing {} pools on ws://{}/ws", pools.len(), listen);
+    tokio::runtime::Runtime::new()?
+        .block_on(jupiter_rarefish::price_feed::serve(listen, &publisher))
+}
@@ -0,0 +1,81 @@
+use anyhow::{anyhow, Result};
+use jupiter_rarefish::simulation::{simulate_swap, simulate_swap_unsigned};
+use solana_client::rpc_client::RpcClient;
+use solana_sdk::pubkey::Pubkey;
+
+use crate::accounts::{load_amm, mint_decimals, ui_amount};
+
+/// Wallet the swap is simulated from
+pub enum Wallet {
+    /// Path of a keypair signing the transaction
+    Keypair(String),
+    /// Public keys only, the simulation skips signature verification
+    Unsigned {
+        owner: Pubkey,
+        payer: Option<Pubkey>,
+    },
+}
+
+pub fn run(
+    rpc: &RpcClient,
+    pool_key: &Pubkey,
+    input_mint: Option<Pubkey>,
+    amount: u64,
+    wallet: Wallet,
+) -> Result<()> {
+    let (jupiter_rarefish, pool) = load_amm(rpc, pool_key)?;
+    let input_mint = input_mint.unwrap_or(pool.token_a_mint);
+
+    let simulation = match wallet {
+        Wallet::Keypair(keypair_path) => {
+            let signer = solana_sdk::signature::read_keypair_file(&keypair_path)
+                .map_err(|e| anyhow!("Failed to read keypair {}: {}", keypair_path, e))?;
+            simulate_swap(rpc, &jupiter_rarefish, &signer, &input_mint, amount)?
+        }
+        Wallet::Unsigned { owner, payer } => simulate_swap_unsigned(
+            rpc,
+            &jupiter_rarefish,
+            &owner,
+            payer.as_ref(),
+            &input_mint,
+            amount,
+        )?,
+    };
+    for log in &simulation.logs {
+        println!("  {}", log);
+    }
+    if let Some(err) = &simulation.error {
+        return Err(anyhow!("Simulation failed: {:?}", err));
+    }
+
+    let output_mint = simulation.output_mint;
+    let input_decimals = mint_decimals(&jupiter_rarefish, &input_mint)?;
+    let output_decimals = mint_decimals(&jupiter_rarefish, &output_mint)?;
+
+    let source = simulation.source;
+    let destination = simulation.destination;
+    println!();
+    println!(
+        "Source      {}: {} -> {} ({})",
+        source.account,
+        ui_amount(source.pre, input_decimals),
+        ui_amount(source.post, input_decimals),
+        -(ui_amount(source.pre.saturating_sub(source.post), input_decimals))
+    );
+    println!(
+        "Destination {}: {} -> {} (+{})",
+        destination.account,
+        ui_amount(destination.pre, output_decimals),
+        ui_amount(destination.post, output_decimals),
+        ui_amount(
+            destination.post.saturating_sub(destination.pre),
+            output_decimals
+        )
+    );
+
+    println!();
+    println!("Quoted out:   {}", simulation.quote.out_amount);
+    println!("Realized out: {}", simulation.realized_out().unwrap_or(0));
+    println!("Difference:   {}", simulation.difference().unwrap_or(0));
+    Ok(())
+}
@@ -0,0 +1,60 @@
+use anyhow::{anyhow, Result};
+use jupiter_rarefish::amm::{Amm, QuoteParams, SwapMode};
+use jupiter_rarefish::sender::SwapSender;
+use jupiter_rarefish::transaction::SwapTransactionBuilder;
+use solana_client::rpc_client::RpcClient;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signer::Signer;
+
+use crate::accounts::load_amm;
+
+pub struct SwapOptions {
+    pub input_mint: Option<Pubkey>,
+    pub keypair: String,
+    pub slippage_bps: u16,
+    pub native_sol: bool,
+    pub priority_fee_percentile: Option<u8>,
+}
+
+pub fn run(rpc: &RpcClient, pool_key: &Pubkey, amount: u64, options: SwapOptions) -> Result<()> {
+    let signer = solana_sdk::signature::read_keypair_file(&options.keypair)
+        .map_err(|e| anyhow!("Failed to read keypair {}: {}", options.keypair, e))?;
+    let (jupiter_rarefish, pool) = load_amm(rpc, pool_key)?;
+    let input_mint = options.input_mint.unwrap_or(pool.token_a_mint);
+
+    let builder =
+        SwapTransactionBuilder::new(&jupiter_rarefish, signer.pubkey(), input_mint, amount)?
+            .with_native_sol(options.native_sol)
+            .with_missing_token_accounts(rpc)?;
+    let quote = jupiter_rarefish.quote(&QuoteParams {
+        input_mint,
+        output_mint: builder.output_mint(),
+        amount,
+        swap_mode: SwapMode::ExactIn,
+    })?;
+    let minimum_out_amount = (u128::from(quote.out_amount)
+        * u128::from(10_000 - options.slippage_bps.min(10_000))
+        / 10_000) as u64;
+    let mut builder = builder.with_minimum_out_amount(minimum_out_amount);
+    if let Some(percentile) = options.priority_fee_percentile {
+        builder = builder.with_estimated_priority_fee(rpc, percentile)?;
+    }
+    println!(
+        "Quoted out: {}, minimum out: {}",
+        quote.out_amount, minimum_out_amount
+    );
+
+    let sent = SwapSender::new(rpc).send(&builder, &[&signer])?;
+    println!("Signature: {}", sent.signature);
+    println!("Slot:      {}", sent.slot);
+    for executed in sent.swaps {
+        println!(
+            "Swapped {} for {} (owner fee {}, host fee {})",
+            executed.amount_in(),
+            executed.amount_out,
+            executed.owner_fee,
+            executed.host_fee
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,104 @@
+use std::sync::mpsc;
+use std::thread;
+
+use anyhow::{anyhow, Result};
+use jupiter_rarefish::amm::Amm;
+use jupiter_rarefish::registry::fetch_accounts;
+use jupiter_rarefish::TradeDirection;
+use rust_decimal::prelude::ToPrimitive;
+use serde_json::json;
+use solana_account_decoder::UiAccountEncoding;
+use solana_client::pubsub_client::PubsubClient;
+use solana_client::rpc_client::RpcClient;
+use solana_client::rpc_config::RpcAccountInfoConfig;
+use solana_sdk::account::Account;
+use solana_sdk::pubkey::Pubkey;
+
+use crate::accounts::{amm_decimals, load_amm, ui_amount};
+
+pub fn run(rpc: &RpcClient, ws_url: &str, pool_key: &Pubkey, json_lines: bool) -> Result<()> {
+    let (mut jupiter_rarefish, _) = load_amm(rpc, pool_key)?;
+    let (token_a_decimals, token_b_decimals) = amm_decimals(&jupiter_rarefish)?;
+
+    let vaults = jupiter_rarefish.get_accounts_to_update();
+    let mut accounts_map = fetch_accounts(rpc, &vaults)?;
+    let (sender, receiver) = mpsc::channel::<(Pubkey, u64, Account)>();
+    let mut subscriptions = vec![];
+    for vault in vaults.iter().copied() {
+        let (subscription, notifications) = PubsubClient::account_subscribe(
+            ws_url,
+            &vault,
+            Some(RpcAccountInfoConfig {
+                encoding: Some(UiAccountEncoding::Base64),
+                commitment: Some(rpc.commitment()),
+                ..RpcAccountInfoConfig::default()
+            }),
+        )?;
+        subscriptions.push(subscription);
+        let sender = sender.clone();
+        thread::spawn(move || {
+            for notification in notifications {
+                if let Some(account) = notification.value.decode() {
+                    if sender
+                        .send((vault, notification.context.slot, account))
+                        .is_err()
+                    {
+                        break;
+                    }
+                }
+            }
+        });
+    }
+    drop(sender);
+
+    let reserves = |jupiter_rarefish: &jupiter_rarefish::JupiterRarefish| {
+        jupiter_rarefish.reserves().unwrap_or_default()
+    };
+    let mut previous = reserves(&jupiter_rarefish);
+    let mut slot = rpc.get_slot()?;
+    // Keep the subscriptions alive for as long as we are receiving notifications
+    let _subscriptions = subscriptions;
+    loop {
+        // Token B per token A in UI units
+        let spot_price = jupiter_rarefish
+            .spot_price(TradeDirection::AtoB, false)?
+            .to_f64()
+            .unwrap_or_default()
+            * 10.0_f64.powi(token_a_decimals as i32 - token_b_decimals as i32);
+        let current = reserves(&jupiter_rarefish);
+        let delta_a = current.0 as i128 - previous.0 as i128;
+        let delta_b = current.1 as i128 - previous.1 as i128;
+        if json_lines {
+            println!(
+                "{}",
+                json!({
+                    "slot": slot,
+                    "pool": pool_key.to_string(),
+                    "spotPrice": spot_price,
+                    "reserveA": current.0,
+                    "reserveB": current.1,
+                    "deltaA": delta_a,
+                    "deltaB": delta_b,
+                })
+            );
+        } else {
+            println!(
+                "slot {:>10} | price {:>16.8} | reserves {} / {} | delta {:+} / {:+}",
+                slot,
+                spot_price,
+                ui_amount(current.0, token_a_decimals),
+                ui_amount(current.1, token_b_decimals),
+                delta_a as f64 / 10.0_f64.powi(token_a_decimals as i32),
+                delta_b as f64 / 10.0_f64.powi(token_b_decimals as i32),
+            );
+        }
+        previous = current;
+
+        let (key, notification_slot, account) = receiver
+            .recv()
+            .map_err(|_| anyhow!("Account subscriptions were closed"))?;
+        slot = notification_slot;
+        accounts_map.insert(key, account);
+        jupiter_rarefish.update(&accounts_map)?;
+    }
+}
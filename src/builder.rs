@@ -0,0 +1,110 @@
+//! Construction of an adapter with options, extensible without breaking
+//! `new_from_keyed_account`.
+
+use anyhow::Result;
+use solana_sdk::account::Account;
+use solana_sdk::pubkey::Pubkey;
+
+use crate::amm::{AccountMap, Amm, KeyedAccount};
+use crate::clock::SharedClock;
+use crate::{JupiterRarefish, PoolParams};
+
+/// Builds a [`JupiterRarefish`] from the pool account, with the options of [`PoolParams`]
+/// set in code instead of JSON, and optionally the vault and mint accounts so the adapter
+/// is ready to quote once built.
+pub struct JupiterRarefishBuilder {
+    key: Pubkey,
+    account: Account,
+    params: PoolParams,
+    accounts_map: AccountMap,
+    clock: Option<SharedClock>,
+}
+
+impl JupiterRarefishBuilder {
+    pub fn new(key: Pubkey, account: Account) -> Self {
+        Self {
+            key,
+            account,
+            params: PoolParams::default(),
+            accounts_map: AccountMap::new(),
+            clock: None,
+        }
+    }
+
+    /// Starts from a keyed account, keeping its params
+    pub fn from_keyed_account(keyed_account: &KeyedAccount) -> Result<Self> {
+        let params = keyed_account
+            .params
+            .as_ref()
+            .map(PoolParams::from_value)
+            .transpose()?
+            .unwrap_or_default();
+        Ok(Self::new(keyed_account.key, keyed_account.account.clone()).with_params(params))
+    }
+
+    /// Replaces every option with `params`
+    pub fn with_params(mut self, params: PoolParams) -> Self {
+        self.params = params;
+        self
+    }
+
+    /// The hyperplane deployment owning the pool, instead of the mainnet program
+    pub fn with_program_id(mut self, program_id: Pubkey) -> Self {
+        self.params.program_id = Some(program_id);
+        self
+    }
+
+    pub fn with_strict(mut self, strict: bool) -> Self {
+        self.params.strict = strict;
+        self
+    }
+
+    /// Appended to the "Rarefish" label
+    pub fn with_label_suffix(mut self, label_suffix: impl Into<String>) -> Self {
+        self.params.label_suffix = Some(label_suffix.into());
+        self
+    }
+
+    /// Token accounts receiving the host share of the owner fee of A to B and B to A swaps
+    pub fn with_host_fees_accounts(
+        mut self,
+        token_a_host_fees_account: Option<Pubkey>,
+        token_b_host_fees_account: Option<Pubkey>,
+    ) -> Self {
+        self.params.token_a_host_fees_account = token_a_host_fees_account;
+        self.params.token_b_host_fees_account = token_b_host_fees_account;
+        self
+    }
+
+    /// Vault, mint and fees vault accounts applied with `update` once built
+    pub fn with_accounts(mut self, accounts_map: AccountMap) -> Self {
+        self.accounts_map = accounts_map;
+        self
+    }
+
+    pub fn with_clock(mut self, clock: SharedClock) -> Self {
+        self.clock = Some(clock);
+        self
+    }
+
+    pub fn build(self) -> Result<JupiterRarefish> {
+        let mut jupiter_rarefish = JupiterRarefish::new_from_keyed_account(&KeyedAccount {
+            key: self.key,
+            account: self.account,
+            params: Some(self.params.to_value()),
+        })?;
+        if !self.accounts_map.is_empty() {
+            jupiter_rarefish.update(&self.accounts_map)?;
+        }
+        if let Some(clock) = self.clock {
+            jupiter_rarefish = jupiter_rarefish.with_clock(clock);
+        }
+        Ok(jupiter_rarefish)
+    }
+}
+
+impl JupiterRarefish {
+    pub fn builder(key: Pubkey, account: Account) -> JupiterRarefishBuilder {
+        JupiterRarefishBuilder::new(key, account)
+    }
+}
@@ -0,0 +1,95 @@
+//! Cluster clock shared with the adapter, so slot and epoch dependent logic needs no RPC call.
+
+use std::fmt;
+use std::sync::atomic::{AtomicI64, AtomicU64, Ordering};
+use std::sync::Arc;
+
+use solana_sdk::clock::Clock;
+
+/// Slot, epoch and timestamp of the cluster, updated in place by its owner and read by every
+/// adapter holding a clone.
+///
+/// Built from the `ClockRef` of Jupiter's `AmmContext` with `amm-interface-0-4`, the clock
+/// then follows Jupiter's updates. Other callers create one and [`SharedClock::update`] it
+/// from the clock sysvar.
+#[derive(Clone, Default)]
+pub struct SharedClock {
+    slot: Arc<AtomicU64>,
+    epoch: Arc<AtomicU64>,
+    unix_timestamp: Arc<AtomicI64>,
+}
+
+impl SharedClock {
+    pub fn new(clock: &Clock) -> Self {
+        let shared_clock = Self::default();
+        shared_clock.update(clock);
+        shared_clock
+    }
+
+    pub fn update(&self, clock: &Clock) {
+        self.slot.store(clock.slot, Ordering::Relaxed);
+        self.epoch.store(clock.epoch, Ordering::Relaxed);
+        self.unix_timestamp
+            .store(clock.unix_timestamp, Ordering::Relaxed);
+    }
+
+    pub fn slot(&self) -> u64 {
+        self.slot.load(Ordering::Relaxed)
+    }
+
+    pub fn epoch(&self) -> u64 {
+        self.epoch.load(Ordering::Relaxed)
+    }
+
+    pub fn unix_timestamp(&self) -> i64 {
+        self.unix_timestamp.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(feature = "amm-interface-0-4")]
+impl From<&crate::amm::ClockRef> for SharedClock {
+    fn from(clock_ref: &crate::amm::ClockRef) -> Self {
+        Self {
+            slot: clock_ref.slot.clone(),
+            epoch: clock_ref.epoch.clone(),
+            unix_timestamp: clock_ref.unix_timestamp.clone(),
+        }
+    }
+}
+
+impl fmt::Debug for SharedClock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SharedClock")
+            .field("slot", &self.slot())
+            .field("epoch", &self.epoch())
+            .field("unix_timestamp", &self.unix_timestamp())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_sdk::clock::Clock;
+
+    use super::SharedClock;
+
+    #[test]
+    fn test_shared_clock() {
+        let clock = SharedClock::new(&Clock {
+            slot: 100,
+            epoch: 2,
+            unix_timestamp: 1_700_000_000,
+            ..Clock::default()
+        });
+        let adapter_clock = clock.clone();
+        clock.update(&Clock {
+            slot: 101,
+            epoch: 2,
+            unix_timestamp: 1_700_000_001,
+            ..Clock::default()
+        });
+        assert_eq!(adapter_clock.slot(), 101);
+        assert_eq!(adapter_clock.epoch(), 2);
+        assert_eq!(adapter_clock.unix_timestamp(), 1_700_000_001);
+    }
+}
@@ -0,0 +1,165 @@
+//! Explicit market whitelists read from a local TOML file, for operators who decide pool by
+//! pool what they serve:
+//! ```toml
+//! [[pools]]
+//! address = "3uqKSr5gZzZSJXgrdikPeWGp1SnEqEayFABwzDQ3vRWe"
+//! label_suffix = "SOL-USDC"
+//! token_b_host_fees_account = "<base58>"
+//! strict = true
+//!
+//! [[pools]]
+//! address = "HcCyVwmtcYKLQYCgfQPv8LVRxW3XDkbop4WZRShGCvK4"
+//! enabled = false
+//! ```
+//! Unlike the hosted manifest, the file is trusted: a pool that fails to load fails the load.
+
+use std::fs;
+use std::path::Path;
+
+use anyhow::{anyhow, Result};
+use serde::Deserialize;
+use solana_client::rpc_client::RpcClient;
+use solana_sdk::pubkey::Pubkey;
+
+use crate::amm::KeyedAccount;
+use crate::registry::{fetch_accounts, MarketRegistry};
+use crate::{JupiterRarefish, PoolParams};
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MarketConfig {
+    #[serde(default)]
+    pub pools: Vec<PoolConfig>,
+}
+
+/// A pool of the config, with the options of its [`PoolParams`]
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PoolConfig {
+    pub address: String,
+    /// Disabled pools are kept in the file but not loaded
+    #[serde(default = "enabled")]
+    pub enabled: bool,
+    pub label_suffix: Option<String>,
+    pub program_id: Option<String>,
+    pub token_a_host_fees_account: Option<String>,
+    pub token_b_host_fees_account: Option<String>,
+    #[serde(default)]
+    pub strict: bool,
+}
+
+fn enabled() -> bool {
+    true
+}
+
+impl MarketConfig {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let toml = fs::read_to_string(path)
+            .map_err(|e| anyhow!("Failed to read market config {}: {}", path.display(), e))?;
+        Self::from_toml(&toml)
+    }
+
+    pub fn from_toml(toml: &str) -> Result<Self> {
+        toml::from_str(toml).map_err(|e| anyhow!("Invalid market config: {}", e))
+    }
+
+    pub fn enabled_pools(&self) -> impl Iterator<Item = &PoolConfig> {
+        self.pools.iter().filter(|pool| pool.enabled)
+    }
+
+    /// Fetches the enabled pools and their vaults, returning a ready-to-quote registry
+    pub fn load_registry(&self, rpc: &RpcClient) -> Result<MarketRegistry> {
+        let pools = self
+            .enabled_pools()
+            .map(|pool| Ok((pool.key()?, pool.params()?)))
+            .collect::<Result<Vec<_>>>()?;
+        let keys: Vec<_> = pools.iter().map(|(key, _)| *key).collect();
+        let accounts_map = fetch_accounts(rpc, &keys)?;
+        let mut registry = MarketRegistry::new();
+        for (key, params) in pools {
+            let account = accounts_map
+                .get(&key)
+                .ok_or_else(|| anyhow!("Pool {} not found", key))?;
+            registry.insert(JupiterRarefish::new_from_keyed_account(&KeyedAccount {
+                key,
+                account: account.clone(),
+                params: Some(params.to_value()),
+            })?);
+        }
+        registry.refresh(rpc)?;
+        Ok(registry)
+    }
+}
+
+impl PoolConfig {
+    pub fn key(&self) -> Result<Pubkey> {
+        parse_pubkey("address", &self.address)
+    }
+
+    pub fn params(&self) -> Result<PoolParams> {
+        let parse_optional = |field, value: &Option<String>| {
+            value
+                .as_deref()
+                .map(|value| parse_pubkey(field, value))
+                .transpose()
+        };
+        Ok(PoolParams {
+            label_suffix: self.label_suffix.clone(),
+            program_id: parse_optional("program_id", &self.program_id)?,
+            token_a_host_fees_account: parse_optional(
+                "token_a_host_fees_account",
+                &self.token_a_host_fees_account,
+            )?,
+            token_b_host_fees_account: parse_optional(
+                "token_b_host_fees_account",
+                &self.token_b_host_fees_account,
+            )?,
+            strict: self.strict,
+        })
+    }
+}
+
+fn parse_pubkey(field: &str, value: &str) -> Result<Pubkey> {
+    value
+        .parse()
+        .map_err(|_| anyhow!("Invalid {} {} in market config", field, value))
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_sdk::pubkey::Pubkey;
+
+    use super::MarketConfig;
+
+    #[test]
+    fn test_market_config() {
+        let host_fees_account = Pubkey::new_unique();
+        let config = MarketConfig::from_toml(&format!(
+            r#"
+            [[pools]]
+            address = "3uqKSr5gZzZSJXgrdikPeWGp1SnEqEayFABwzDQ3vRWe"
+            label_suffix = "SOL-USDC"
+            token_b_host_fees_account = "{}"
+            strict = true
+
+            [[pools]]
+            address = "HcCyVwmtcYKLQYCgfQPv8LVRxW3XDkbop4WZRShGCvK4"
+            enabled = false
+            "#,
+            host_fees_account
+        ))
+        .unwrap();
+        let enabled: Vec<_> = config.enabled_pools().collect();
+        assert_eq!(enabled.len(), 1);
+        assert_eq!(enabled[0].key().unwrap(), crate::pools::SOL_USDC.key);
+        let params = enabled[0].params().unwrap();
+        assert_eq!(params.label_suffix.as_deref(), Some("SOL-USDC"));
+        assert_eq!(params.token_b_host_fees_account, Some(host_fees_account));
+        assert!(params.strict);
+
+        assert!(MarketConfig::from_toml("[[pools]]\naddress = \"x\"\nlabel = \"typo\"").is_err());
+        let invalid = MarketConfig::from_toml("[[pools]]\naddress = \"not a pubkey\"").unwrap();
+        assert!(invalid.pools[0].key().is_err());
+    }
+}
@@ -0,0 +1,202 @@
+use anyhow::{anyhow, Result};
+use hyperplane::curve::base::CurveType;
+use hyperplane::curve::{
+    constant_price::ConstantPriceCurve, offset::OffsetCurve, stable::StableCurve,
+};
+use hyperplane::state::{SwapPool, SwapState};
+
+/// Curve type and parameters of a pool, decoded from `swap_curve_data`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CurveParams {
+    /// `x * y = k` over the vault balances
+    ConstantProduct,
+    /// 1 token B always trades for `token_b_price` token A
+    ConstantPrice { token_b_price: u64 },
+    /// Constant product with `token_b_offset` virtual token B added to the vault balance
+    Offset { token_b_offset: u64 },
+    /// StableSwap with amplification `amp`, balances are multiplied by the token factors to
+    /// compare amounts of different decimals
+    Stable {
+        amp: u64,
+        token_a_factor: u64,
+        token_b_factor: u64,
+    },
+}
+
+impl CurveParams {
+    pub fn from_pool(pool: &SwapPool) -> Result<Self> {
+        Ok(match pool.curve_type() {
+            CurveType::ConstantProduct => CurveParams::ConstantProduct,
+            CurveType::ConstantPrice => {
+                let curve: &ConstantPriceCurve = curve_data(&pool.swap_curve_data)?;
+                CurveParams::ConstantPrice {
+                    token_b_price: curve.token_b_price,
+                }
+            }
+            CurveType::Offset => {
+                let curve: &OffsetCurve = curve_data(&pool.swap_curve_data)?;
+                CurveParams::Offset {
+                    token_b_offset: curve.token_b_offset,
+                }
+            }
+            CurveType::Stable => {
+                let curve: &StableCurve = curve_data(&pool.swap_curve_data)?;
+                CurveParams::Stable {
+                    amp: curve.amp,
+                    token_a_factor: curve.token_a_factor,
+                    token_b_factor: curve.token_b_factor,
+                }
+            }
+        })
+    }
+}
+
+/// Curve parameters are stored as a zero-copy account (8 byte discriminator followed by the curve struct)
+fn curve_data<T: bytemuck::Pod>(data: &[u8]) -> Result<&T> {
+    data.get(8..8 + std::mem::size_of::<T>())
+        .and_then(|bytes| bytemuck::try_from_bytes(bytes).ok())
+        .ok_or_else(|| anyhow!("Invalid curve data"))
+}
+
+/// StableSwap invariant `D` for two normalized balances, solved with Newton's method
+pub(crate) fn stable_invariant(amp: f64, x: f64, y: f64) -> f64 {
+    let sum = x + y;
+    if sum == 0.0 {
+        return 0.0;
+    }
+    let ann = amp * 4.0;
+    let mut d = sum;
+    for _ in 0..255 {
+        let d_p = d * d * d / (4.0 * x * y);
+        let previous = d;
+        d = (ann * sum + 2.0 * d_p) * d / ((ann - 1.0) * d + 3.0 * d_p);
+        if (d - previous).abs() <= d * 1e-12 {
+            break;
+        }
+    }
+    d
+}
+
+/// Marginal price of `y` in terms of `x` (dy/dx) on the StableSwap curve
+pub(crate) fn stable_marginal_price(amp: f64, x: f64, y: f64) -> f64 {
+    let d = stable_invariant(amp, x, y);
+    let ann = amp * 4.0;
+    let d3 = d * d * d;
+    (ann + d3 / (4.0 * x * x * y)) / (ann + d3 / (4.0 * x * y * y))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use bytemuck::Zeroable;
+    use hyperplane::curve::base::{CurveType, SwapCurve};
+    use hyperplane::curve::calculator::{CurveCalculator, TradeDirection};
+    use hyperplane::curve::constant_product::ConstantProductCurve;
+    use hyperplane::curve::fees::Fees;
+    use hyperplane::curve::{
+        constant_price::ConstantPriceCurve, offset::OffsetCurve, stable::StableCurve,
+    };
+    use proptest::prelude::*;
+
+    use super::{stable_invariant, stable_marginal_price};
+
+    fn swap_curve(
+        curve_type: CurveType,
+        calculator: Arc<dyn CurveCalculator + Sync + Send>,
+    ) -> SwapCurve {
+        SwapCurve {
+            curve_type,
+            calculator,
+        }
+    }
+
+    fn curves(param: u64) -> Vec<SwapCurve> {
+        let constant_product: ConstantProductCurve = Zeroable::zeroed();
+        let mut constant_price: ConstantPriceCurve = Zeroable::zeroed();
+        constant_price.token_b_price = param.max(1);
+        let mut offset: OffsetCurve = Zeroable::zeroed();
+        offset.token_b_offset = param;
+        let mut stable: StableCurve = Zeroable::zeroed();
+        stable.amp = param.clamp(1, 10_000);
+        stable.token_a_factor = 1;
+        stable.token_b_factor = 1;
+        vec![
+            swap_curve(CurveType::ConstantProduct, Arc::new(constant_product)),
+            swap_curve(CurveType::ConstantPrice, Arc::new(constant_price)),
+            swap_curve(CurveType::Offset, Arc::new(offset)),
+            swap_curve(CurveType::Stable, Arc::new(stable)),
+        ]
+    }
+
+    fn fees(trade_fee_numerator: u64, owner_trade_fee_numerator: u64) -> Fees {
+        Fees {
+            trade_fee_numerator,
+            trade_fee_denominator: 10_000,
+            owner_trade_fee_numerator,
+            owner_trade_fee_denominator: 10_000,
+            ..Fees::default()
+        }
+    }
+
+    proptest! {
+        /// Swapping A to B and straight back to A can never return more than was put in
+        #[test]
+        fn test_round_trip_never_profitable(
+            reserve_a in 1_000_u64..1_000_000_000_000_000,
+            reserve_b in 1_000_u64..1_000_000_000_000_000,
+            amount in 1_u64..1_000_000_000_000,
+            trade_fee_numerator in 0_u64..100,
+            owner_trade_fee_numerator in 0_u64..100,
+            param in 0_u64..1_000_000,
+        ) {
+            let fees = fees(trade_fee_numerator, owner_trade_fee_numerator);
+            for curve in curves(param) {
+                let Ok(there) = curve.swap(
+                    u128::from(amount),
+                    u128::from(reserve_a),
+                    u128::from(reserve_b),
+                    TradeDirection::AtoB,
+                    &fees,
+                ) else {
+                    continue;
+                };
+                let reserve_a = u128::from(reserve_a) + there.source_amount_swapped - there.owner_fee;
+                let reserve_b = u128::from(reserve_b) - there.destination_amount_swapped;
+                let Ok(back) = curve.swap(
+                    there.destination_amount_swapped,
+                    reserve_b,
+                    reserve_a,
+                    TradeDirection::BtoA,
+                    &fees,
+                ) else {
+                    continue;
+                };
+                prop_assert!(
+                    back.destination_amount_swapped <= u128::from(amount),
+                    "{:?} round trip of {} returned {}",
+                    curve.curve_type,
+                    amount,
+                    back.destination_amount_swapped
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_stable_curve_balanced() {
+        let d = stable_invariant(100.0, 1_000_000.0, 1_000_000.0);
+        assert!((d - 2_000_000.0).abs() < 1e-6);
+        let price = stable_marginal_price(100.0, 1_000_000.0, 1_000_000.0);
+        assert!((price - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_stable_curve_imbalanced() {
+        // Selling into the side with more reserves gives a worse price, but far closer to 1
+        // than a constant product pool would
+        let price = stable_marginal_price(100.0, 2_000_000.0, 1_000_000.0);
+        assert!(price < 1.0);
+        assert!(price > 0.5);
+    }
+}
@@ -0,0 +1,528 @@
+use anchor_lang::{AnchorDeserialize, Discriminator};
+use anchor_spl::token::spl_token;
+use anchor_spl::token_2022::spl_token_2022;
+use anyhow::{anyhow, Result};
+use solana_sdk::pubkey::Pubkey;
+use solana_transaction_status::option_serializer::OptionSerializer;
+use solana_transaction_status::{
+    EncodedConfirmedTransactionWithStatusMeta, UiInstruction, UiTransactionStatusMeta,
+    UiTransactionTokenBalance,
+};
+
+use crate::events::{parse_logs, SwapEvent};
+
+/// Instruction tags of the token program, shared by token-2022
+const TRANSFER_TAG: u8 = 3;
+const TRANSFER_CHECKED_TAG: u8 = 12;
+
+/// A hyperplane swap instruction decoded from its account list and data.
+///
+/// Account positions follow the metas built by `get_swap_and_account_metas`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DecodedSwap {
+    pub user: Pubkey,
+    pub pool: Pubkey,
+    pub source_mint: Pubkey,
+    pub destination_mint: Pubkey,
+    pub source_vault: Pubkey,
+    pub destination_vault: Pubkey,
+    pub source_fees_vault: Pubkey,
+    pub source_token_account: Pubkey,
+    pub destination_token_account: Pubkey,
+    /// Account receiving the host share of the owner fee, if the swap passed one
+    pub source_host_fees_account: Option<Pubkey>,
+    pub amount_in: u64,
+    pub minimum_amount_out: u64,
+}
+
+/// A swap found in a confirmed transaction, with the amounts realized on chain
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ExecutedSwap {
+    pub swap: DecodedSwap,
+    /// Index of the top-level instruction the swap was executed in
+    pub instruction_index: usize,
+    /// Whether the swap was executed as a CPI, e.g. as a leg of a Jupiter route
+    pub inner: bool,
+    /// Tokens received by the source vault (excluding the owner and host fees)
+    pub vault_amount_in: u64,
+    /// Tokens sent out of the destination vault
+    pub amount_out: u64,
+    /// Tokens received by the source fees vault
+    pub owner_fee: u64,
+    /// Tokens received by the host fees account
+    pub host_fee: u64,
+}
+
+impl ExecutedSwap {
+    /// Tokens taken from the user: the vault input and the owner and host fees
+    pub fn amount_in(&self) -> u64 {
+        self.vault_amount_in + self.owner_fee + self.host_fee
+    }
+}
+
+/// Decodes the instruction of `program_id` if it is a swap of the hyperplane deployment
+/// `swap_program_id`
+pub fn decode_swap_instruction(
+    swap_program_id: &Pubkey,
+    program_id: &Pubkey,
+    accounts: &[Pubkey],
+    data: &[u8],
+) -> Option<DecodedSwap> {
+    if program_id != swap_program_id || accounts.len() < 10 {
+        return None;
+    }
+    if data.len() < 8 {
+        return None;
+    }
+    let (discriminator, mut args) = data.split_at(8);
+    if discriminator != &hyperplane::instruction::Swap::DISCRIMINATOR[..] {
+        return None;
+    }
+    let hyperplane::instruction::Swap {
+        amount_in,
+        minimum_amount_out,
+    } = AnchorDeserialize::deserialize(&mut args).ok()?;
+    Some(DecodedSwap {
+        user: accounts[0],
+        pool: accounts[1],
+        source_mint: accounts[3],
+        destination_mint: accounts[4],
+        source_vault: accounts[5],
+        destination_vault: accounts[6],
+        source_fees_vault: accounts[7],
+        source_token_account: accounts[8],
+        destination_token_account: accounts[9],
+        // The program id stands for no host fees account
+        source_host_fees_account: accounts
+            .get(10)
+            .filter(|host_fees_account| *host_fees_account != program_id)
+            .copied(),
+        amount_in,
+        minimum_amount_out,
+    })
+}
+
+/// Finds every hyperplane swap in a transaction fetched with base64 or base58 encoding,
+/// including swaps executed through CPI.
+///
+/// Realized amounts are derived from the vault balance changes, so they are aggregated when
+/// the same pool is swapped against more than once in a single transaction.
+pub fn decode_transaction_swaps(
+    program_id: &Pubkey,
+    transaction: &EncodedConfirmedTransactionWithStatusMeta,
+) -> Result<Vec<ExecutedSwap>> {
+    let decoded = DecodedTransaction::new(program_id, transaction)?;
+    let pre_balances: Option<Vec<_>> = decoded.meta.pre_token_balances.clone().into();
+    let post_balances: Option<Vec<_>> = decoded.meta.post_token_balances.clone().into();
+    let pre_balances = pre_balances.unwrap_or_default();
+    let post_balances = post_balances.unwrap_or_default();
+    let balance_change = |key: &Pubkey| -> i128 {
+        let index = decoded.account_keys.iter().position(|k| k == key);
+        let amount = |balances: &[UiTransactionTokenBalance]| {
+            index
+                .and_then(|index| {
+                    balances
+                        .iter()
+                        .find(|balance| balance.account_index as usize == index)
+                })
+                .and_then(|balance| balance.ui_token_amount.amount.parse::<i128>().ok())
+                .unwrap_or(0)
+        };
+        amount(&post_balances) - amount(&pre_balances)
+    };
+
+    Ok(decoded
+        .swaps()
+        .map(|(swap, instruction)| ExecutedSwap {
+            vault_amount_in: balance_change(&swap.source_vault).max(0) as u64,
+            amount_out: (-balance_change(&swap.destination_vault)).max(0) as u64,
+            owner_fee: balance_change(&swap.source_fees_vault).max(0) as u64,
+            host_fee: swap
+                .source_host_fees_account
+                .map_or(0, |host_fees_account| {
+                    balance_change(&host_fees_account).max(0) as u64
+                }),
+            swap,
+            instruction_index: instruction.instruction_index,
+            inner: instruction.inner,
+        })
+        .collect())
+}
+
+/// The balance of the token account `account` before a transaction fetched with base64 or
+/// base58 encoding, `None` if the transaction does not hold the account
+pub fn pre_token_balance(
+    transaction: &EncodedConfirmedTransactionWithStatusMeta,
+    account: &Pubkey,
+) -> Result<Option<u64>> {
+    let decoded = DecodedTransaction::new(&hyperplane::id(), transaction)?;
+    let Some(index) = decoded.account_keys.iter().position(|key| key == account) else {
+        return Ok(None);
+    };
+    let pre_balances: Option<Vec<UiTransactionTokenBalance>> =
+        decoded.meta.pre_token_balances.clone().into();
+    pre_balances
+        .unwrap_or_default()
+        .iter()
+        .find(|balance| balance.account_index as usize == index)
+        .map(|balance| {
+            balance
+                .ui_token_amount
+                .amount
+                .parse()
+                .map_err(|_| anyhow!("Invalid token balance of {}", account))
+        })
+        .transpose()
+}
+
+/// Finds every hyperplane swap in a transaction fetched with base64 or base58 encoding and
+/// reconstructs its realized amounts from the token transfers it made.
+///
+/// Each swap is matched with the first transfers following it that move tokens from the user
+/// into the source vault, fees vault and host fees account and from the destination vault to the user, so the
+/// amounts are exact even when the same pool is swapped against more than once, e.g. by
+/// several legs of a Jupiter route.
+pub fn decode_transaction_transfers(
+    program_id: &Pubkey,
+    transaction: &EncodedConfirmedTransactionWithStatusMeta,
+) -> Result<Vec<ExecutedSwap>> {
+    let decoded = DecodedTransaction::new(program_id, transaction)?;
+    let mut swaps = vec![];
+    for (position, instruction) in decoded.instructions.iter().enumerate() {
+        let Some(swap) = instruction.decode_swap(program_id) else {
+            continue;
+        };
+        let (mut vault_amount_in, mut owner_fee, mut host_fee, mut amount_out) =
+            (None, None, None, None);
+        for transfer in decoded.instructions[position + 1..]
+            .iter()
+            .take_while(|next| next.decode_swap(program_id).is_none())
+            .filter_map(ProgramInstruction::decode_transfer)
+        {
+            if transfer.source == swap.source_token_account {
+                if transfer.destination == swap.source_vault {
+                    vault_amount_in = vault_amount_in.or(Some(transfer.amount));
+                } else if transfer.destination == swap.source_fees_vault {
+                    owner_fee = owner_fee.or(Some(transfer.amount));
+                } else if Some(transfer.destination) == swap.source_host_fees_account {
+                    host_fee = host_fee.or(Some(transfer.amount));
+                }
+            } else if transfer.source == swap.destination_vault
+                && transfer.destination == swap.destination_token_account
+            {
+                amount_out = amount_out.or(Some(transfer.amount));
+            }
+        }
+        swaps.push(ExecutedSwap {
+            vault_amount_in: vault_amount_in.unwrap_or(0),
+            amount_out: amount_out.unwrap_or(0),
+            owner_fee: owner_fee.unwrap_or(0),
+            host_fee: host_fee.unwrap_or(0),
+            swap,
+            instruction_index: instruction.instruction_index,
+            inner: instruction.inner,
+        });
+    }
+    Ok(swaps)
+}
+
+/// A transfer made by an spl-token or token-2022 `Transfer` or `TransferChecked` instruction
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TokenTransfer {
+    pub source: Pubkey,
+    pub destination: Pubkey,
+    pub amount: u64,
+}
+
+pub fn decode_token_transfer(
+    program_id: &Pubkey,
+    accounts: &[Pubkey],
+    data: &[u8],
+) -> Option<TokenTransfer> {
+    if *program_id != spl_token::id() && *program_id != spl_token_2022::id() {
+        return None;
+    }
+    let amount = u64::from_le_bytes(data.get(1..9)?.try_into().ok()?);
+    let (source, destination) = match *data.first()? {
+        TRANSFER_TAG => (accounts.first()?, accounts.get(1)?),
+        TRANSFER_CHECKED_TAG => (accounts.first()?, accounts.get(2)?),
+        _ => return None,
+    };
+    Some(TokenTransfer {
+        source: *source,
+        destination: *destination,
+        amount,
+    })
+}
+
+/// A swap found in a confirmed transaction with the event it emitted
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SwapWithEvent {
+    pub swap: DecodedSwap,
+    /// Index of the top-level instruction the swap was executed in
+    pub instruction_index: usize,
+    /// Whether the swap was executed as a CPI, e.g. as a leg of a Jupiter route
+    pub inner: bool,
+    pub event: SwapEvent,
+}
+
+/// Pairs the swap events logged by a transaction fetched with base64 or base58 encoding with
+/// the swap instructions that emitted them.
+///
+/// Unlike [`decode_transaction_swaps`] the amounts are those of every single swap, even when
+/// the same pool is swapped against more than once. Transactions whose logs were truncated
+/// only yield the swaps logged before the cut.
+pub fn decode_transaction_events(
+    program_id: &Pubkey,
+    transaction: &EncodedConfirmedTransactionWithStatusMeta,
+) -> Result<Vec<SwapWithEvent>> {
+    let decoded = DecodedTransaction::new(program_id, transaction)?;
+    let invocations: Vec<&ProgramInstruction> = decoded.invocations().collect();
+    let logs: Option<Vec<String>> = decoded.meta.log_messages.clone().into();
+    Ok(parse_logs(program_id, &logs.unwrap_or_default())
+        .into_iter()
+        .filter_map(|logged| {
+            let instruction = invocations.get(logged.invocation)?;
+            Some(SwapWithEvent {
+                swap: instruction.decode_swap(program_id)?,
+                instruction_index: instruction.instruction_index,
+                inner: instruction.inner,
+                event: logged.event,
+            })
+        })
+        .collect())
+}
+
+/// An instruction of a transaction, top-level or CPI
+struct ProgramInstruction {
+    program_id: Pubkey,
+    accounts: Vec<Pubkey>,
+    data: Vec<u8>,
+    /// Index of the top-level instruction it was executed in
+    instruction_index: usize,
+    inner: bool,
+}
+
+impl ProgramInstruction {
+    fn decode_swap(&self, swap_program_id: &Pubkey) -> Option<DecodedSwap> {
+        decode_swap_instruction(
+            swap_program_id,
+            &self.program_id,
+            &self.accounts,
+            &self.data,
+        )
+    }
+
+    fn decode_transfer(&self) -> Option<TokenTransfer> {
+        decode_token_transfer(&self.program_id, &self.accounts, &self.data)
+    }
+}
+
+/// The instructions of a transaction, in execution order
+struct DecodedTransaction<'a> {
+    /// The hyperplane deployment whose swaps are decoded
+    program_id: Pubkey,
+    account_keys: Vec<Pubkey>,
+    meta: &'a UiTransactionStatusMeta,
+    instructions: Vec<ProgramInstruction>,
+}
+
+impl<'a> DecodedTransaction<'a> {
+    fn new(
+        program_id: &Pubkey,
+        transaction: &'a EncodedConfirmedTransactionWithStatusMeta,
+    ) -> Result<Self> {
+        let versioned_transaction = transaction
+            .transaction
+            .transaction
+            .decode()
+            .ok_or_else(|| anyhow!("Transaction must be fetched with a binary encoding"))?;
+        let meta = transaction
+            .transaction
+            .meta
+            .as_ref()
+            .ok_or_else(|| anyhow!("Transaction has no status meta"))?;
+        let account_keys = account_keys(&versioned_transaction.message, meta)?;
+        let key_at = |index: u8| account_keys.get(index as usize).copied();
+
+        let inner_instructions: Option<Vec<_>> = meta.inner_instructions.clone().into();
+        let inner_instructions = inner_instructions.unwrap_or_default();
+        let mut instructions = vec![];
+        for (instruction_index, instruction) in versioned_transaction
+            .message
+            .instructions()
+            .iter()
+            .enumerate()
+        {
+            if let Some(program_id) = key_at(instruction.program_id_index) {
+                instructions.push(ProgramInstruction {
+                    program_id,
+                    accounts: instruction
+                        .accounts
+                        .iter()
+                        .filter_map(|i| key_at(*i))
+                        .collect(),
+                    data: instruction.data.clone(),
+                    instruction_index,
+                    inner: false,
+                });
+            }
+            for inner in inner_instructions
+                .iter()
+                .filter(|inner| inner.index as usize == instruction_index)
+            {
+                for instruction in &inner.instructions {
+                    let UiInstruction::Compiled(instruction) = instruction else {
+                        continue;
+                    };
+                    let Some(program_id) = key_at(instruction.program_id_index) else {
+                        continue;
+                    };
+                    instructions.push(ProgramInstruction {
+                        program_id,
+                        accounts: instruction
+                            .accounts
+                            .iter()
+                            .filter_map(|i| key_at(*i))
+                            .collect(),
+                        data: bs58::decode(&instruction.data).into_vec()?,
+                        instruction_index,
+                        inner: true,
+                    });
+                }
+            }
+        }
+        Ok(Self {
+            program_id: *program_id,
+            account_keys,
+            meta,
+            instructions,
+        })
+    }
+
+    fn swaps(&self) -> impl Iterator<Item = (DecodedSwap, &ProgramInstruction)> {
+        self.instructions.iter().filter_map(|instruction| {
+            Some((instruction.decode_swap(&self.program_id)?, instruction))
+        })
+    }
+
+    /// Invocations of the hyperplane program, in the order they appear in the logs
+    fn invocations(&self) -> impl Iterator<Item = &ProgramInstruction> {
+        self.instructions
+            .iter()
+            .filter(move |instruction| instruction.program_id == self.program_id)
+    }
+}
+
+/// Static account keys followed by the keys loaded from address lookup tables
+fn account_keys(
+    message: &solana_sdk::message::VersionedMessage,
+    meta: &UiTransactionStatusMeta,
+) -> Result<Vec<Pubkey>> {
+    let mut keys = message.static_account_keys().to_vec();
+    if let OptionSerializer::Some(loaded_addresses) = &meta.loaded_addresses {
+        for key in loaded_addresses
+            .writable
+            .iter()
+            .chain(loaded_addresses.readonly.iter())
+        {
+            keys.push(key.parse()?);
+        }
+    }
+    Ok(keys)
+}
+
+#[cfg(test)]
+mod tests {
+    use anchor_lang::InstructionData;
+    use solana_sdk::pubkey::Pubkey;
+
+    use super::{decode_swap_instruction, decode_token_transfer, TokenTransfer};
+
+    #[test]
+    fn test_decode_swap_instruction() {
+        let accounts: Vec<Pubkey> = (0..13).map(|_| Pubkey::new_unique()).collect();
+        let data = hyperplane::instruction::Swap {
+            amount_in: 10_000_000,
+            minimum_amount_out: 42,
+        }
+        .data();
+        let program_id = hyperplane::id();
+
+        let swap = decode_swap_instruction(&program_id, &program_id, &accounts, &data).unwrap();
+        assert_eq!(swap.pool, accounts[1]);
+        assert_eq!(swap.source_vault, accounts[5]);
+        assert_eq!(swap.destination_token_account, accounts[9]);
+        assert_eq!(swap.source_host_fees_account, Some(accounts[10]));
+        assert_eq!(swap.amount_in, 10_000_000);
+        assert_eq!(swap.minimum_amount_out, 42);
+
+        let mut without_host_fees = accounts.clone();
+        without_host_fees[10] = program_id;
+        let swap =
+            decode_swap_instruction(&program_id, &program_id, &without_host_fees, &data).unwrap();
+        assert_eq!(swap.source_host_fees_account, None);
+
+        // Swaps of another deployment are only decoded when asked for
+        let other_program_id = Pubkey::new_unique();
+        assert!(
+            decode_swap_instruction(&program_id, &other_program_id, &accounts, &data).is_none()
+        );
+        assert!(
+            decode_swap_instruction(&other_program_id, &other_program_id, &accounts, &data)
+                .is_some()
+        );
+        assert!(decode_swap_instruction(&program_id, &program_id, &accounts, &data[..8]).is_none());
+        assert!(decode_swap_instruction(&program_id, &program_id, &accounts[..9], &data).is_none());
+    }
+
+    #[test]
+    fn test_decode_token_transfer() {
+        use anchor_spl::token::spl_token;
+        use anchor_spl::token_2022::spl_token_2022;
+
+        let accounts: Vec<Pubkey> = (0..4).map(|_| Pubkey::new_unique()).collect();
+        let data = spl_token::instruction::transfer(
+            &spl_token::id(),
+            &accounts[0],
+            &accounts[1],
+            &accounts[2],
+            &[],
+            500,
+        )
+        .unwrap()
+        .data;
+        assert_eq!(
+            decode_token_transfer(&spl_token::id(), &accounts, &data),
+            Some(TokenTransfer {
+                source: accounts[0],
+                destination: accounts[1],
+                amount: 500,
+            })
+        );
+
+        let data = spl_token_2022::instruction::transfer_checked(
+            &spl_token_2022::id(),
+            &accounts[0],
+            &accounts[1],
+            &accounts[2],
+            &accounts[3],
+            &[],
+            700,
+            6,
+        )
+        .unwrap()
+        .data;
+        assert_eq!(
+            decode_token_transfer(&spl_token_2022::id(), &accounts, &data),
+            Some(TokenTransfer {
+                source: accounts[0],
+                destination: accounts[2],
+                amount: 700,
+            })
+        );
+
+        assert!(decode_token_transfer(&Pubkey::new_unique(), &accounts, &data).is_none());
+        assert!(decode_token_transfer(&spl_token::id(), &accounts, &[3]).is_none());
+    }
+}
@@ -0,0 +1,106 @@
+use anyhow::{anyhow, Result};
+use rust_decimal::Decimal;
+
+use crate::{JupiterRarefish, TradeDirection};
+
+/// Default price impact thresholds, in basis points
+pub const DEFAULT_THRESHOLDS_BPS: [u64; 3] = [10, 50, 100];
+
+/// The largest trade a pool can absorb within a price impact threshold
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DepthLevel {
+    pub price_impact_bps: u64,
+    pub in_amount: u64,
+    pub out_amount: u64,
+    /// Destination tokens received per additional source token at this size
+    pub marginal_price: Decimal,
+}
+
+impl JupiterRarefish {
+    /// For each threshold, finds the largest input whose execution price is within
+    /// `threshold` bps of the spot price after fees, in raw token units.
+    pub fn depth(
+        &self,
+        trade_direction: TradeDirection,
+        thresholds_bps: &[u64],
+    ) -> Result<Vec<DepthLevel>> {
+        let reference_price = self.spot_price(trade_direction, true)?;
+        if reference_price.is_zero() {
+            return Err(anyhow!("Pool has no liquidity in this direction"));
+        }
+
+        thresholds_bps
+            .iter()
+            .map(|threshold_bps| {
+                let min_price = reference_price
+                    * (Decimal::ONE - Decimal::from(*threshold_bps) / Decimal::from(10_000));
+                let within_threshold = |amount: u64| {
+                    self.average_price(trade_direction, amount)
+                        .map_or(false, |price| price >= min_price)
+                };
+                let in_amount = largest_amount(within_threshold);
+                let out_amount = self.out_amount(trade_direction, in_amount).unwrap_or(0);
+                Ok(DepthLevel {
+                    price_impact_bps: *threshold_bps,
+                    in_amount,
+                    out_amount,
+                    marginal_price: self.marginal_price(trade_direction, in_amount),
+                })
+            })
+            .collect()
+    }
+
+    fn out_amount(&self, trade_direction: TradeDirection, amount: u64) -> Option<u64> {
+        self.quote_many(trade_direction, &[amount])
+            .ok()
+            .map(|quotes| quotes[0].out_amount)
+    }
+
+    fn average_price(&self, trade_direction: TradeDirection, amount: u64) -> Option<Decimal> {
+        if amount == 0 {
+            return None;
+        }
+        let out_amount = self.out_amount(trade_direction, amount)?;
+        Some(Decimal::from(out_amount) / Decimal::from(amount))
+    }
+
+    fn marginal_price(&self, trade_direction: TradeDirection, amount: u64) -> Decimal {
+        let step = (amount / 10_000).max(1);
+        match (
+            self.out_amount(trade_direction, amount),
+            self.out_amount(trade_direction, amount.saturating_add(step)),
+        ) {
+            (Some(out), Some(out_next)) => {
+                Decimal::from(out_next.saturating_sub(out)) / Decimal::from(step)
+            }
+            _ => Decimal::ZERO,
+        }
+    }
+}
+
+/// Binary search for the largest amount satisfying a predicate that is monotonically
+/// true and then false as the amount grows
+fn largest_amount(predicate: impl Fn(u64) -> bool) -> u64 {
+    let (mut low, mut high) = (0_u64, u64::MAX);
+    while low < high {
+        let mid = low + (high - low) / 2 + 1;
+        if predicate(mid) {
+            low = mid;
+        } else {
+            high = mid - 1;
+        }
+    }
+    low
+}
+
+#[cfg(test)]
+mod tests {
+    use super::largest_amount;
+
+    #[test]
+    fn test_largest_amount() {
+        assert_eq!(largest_amount(|amount| amount <= 1_000), 1_000);
+        assert_eq!(largest_amount(|_| false), 0);
+        assert_eq!(largest_amount(|_| true), u64::MAX);
+    }
+}
@@ -0,0 +1,182 @@
+//! Anchor events emitted by hyperplane, parsed from the logs of a transaction.
+//!
+//! Anchor emits an event as a `Program data: ` log line holding the base64 encoding of the
+//! event discriminator followed by the borsh encoded event.
+
+use anchor_lang::solana_program::hash::hash;
+use anchor_lang::{AnchorDeserialize, AnchorSerialize};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use solana_sdk::pubkey::Pubkey;
+
+const PROGRAM_DATA_PREFIX: &str = "Program data: ";
+
+/// Emitted by every hyperplane swap, mirrors the layout of hyperplane's `event::Swap`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, AnchorSerialize, AnchorDeserialize)]
+pub struct SwapEvent {
+    /// Tokens taken from the user, including the fees
+    pub token_in_amount: u64,
+    /// Tokens sent to the user
+    pub token_out_amount: u64,
+    /// Trade and owner fees, in the input token
+    pub total_fees: u64,
+}
+
+impl SwapEvent {
+    /// Anchor event discriminator, the first 8 bytes of `sha256("event:Swap")`
+    pub fn discriminator() -> [u8; 8] {
+        event_discriminator("Swap")
+    }
+}
+
+/// An event decoded from the logs of a transaction
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LoggedEvent {
+    pub event: SwapEvent,
+    /// Position of the emitting invocation among every invocation of the hyperplane program
+    /// in the transaction, in execution order
+    pub invocation: usize,
+    /// Invocation depth, 1 for a top-level instruction and more for a CPI
+    pub depth: usize,
+}
+
+/// Decodes the events emitted by the hyperplane deployment `program_id` in `logs`.
+///
+/// Events logged by other programs, e.g. a router CPI-ing into hyperplane, are skipped even
+/// when they share a discriminator. Truncated logs yield the events logged before the cut.
+pub fn parse_logs<S: AsRef<str>>(program_id: &Pubkey, logs: &[S]) -> Vec<LoggedEvent> {
+    // The hyperplane invocation index of every open invocation, None for other programs
+    let mut stack: Vec<Option<usize>> = vec![];
+    let mut invocations = 0;
+    let mut events = vec![];
+    for log in logs {
+        let log = log.as_ref();
+        if let Some(data) = log.strip_prefix(PROGRAM_DATA_PREFIX) {
+            if let Some(Some(invocation)) = stack.last() {
+                if let Some(event) = decode_event(data) {
+                    events.push(LoggedEvent {
+                        event,
+                        invocation: *invocation,
+                        depth: stack.len(),
+                    });
+                }
+            }
+        } else if let Some(invoked) = parse_invoke(log) {
+            if invoked == *program_id {
+                stack.push(Some(invocations));
+                invocations += 1;
+            } else {
+                stack.push(None);
+            }
+        } else if is_invocation_end(log) {
+            stack.pop();
+        }
+    }
+    events
+}
+
+/// Decodes a base64 `Program data` payload into a swap event
+pub fn decode_event(data: &str) -> Option<SwapEvent> {
+    let bytes = STANDARD.decode(data.trim()).ok()?;
+    if bytes.len() < 8 {
+        return None;
+    }
+    let (discriminator, mut event) = bytes.split_at(8);
+    if discriminator != SwapEvent::discriminator() {
+        return None;
+    }
+    SwapEvent::deserialize(&mut event).ok()
+}
+
+/// Program of a `Program <id> invoke [<depth>]` line
+fn parse_invoke(log: &str) -> Option<Pubkey> {
+    let mut words = log.split_whitespace();
+    if words.next()? != "Program" {
+        return None;
+    }
+    let program_id = words.next()?.parse().ok()?;
+    (words.next()? == "invoke").then_some(program_id)
+}
+
+/// Whether the line closes the current invocation, `Program <id> success` or
+/// `Program <id> failed: <error>`
+fn is_invocation_end(log: &str) -> bool {
+    let mut words = log.split_whitespace();
+    words.next() == Some("Program")
+        && words.next().is_some()
+        && matches!(words.next(), Some("success") | Some("failed:"))
+}
+
+fn event_discriminator(name: &str) -> [u8; 8] {
+    let mut discriminator = [0; 8];
+    discriminator.copy_from_slice(&hash(format!("event:{}", name).as_bytes()).to_bytes()[..8]);
+    discriminator
+}
+
+#[cfg(test)]
+mod tests {
+    use anchor_lang::AnchorSerialize;
+    use base64::engine::general_purpose::STANDARD;
+    use base64::Engine;
+    use solana_sdk::pubkey::Pubkey;
+
+    use super::{parse_logs, LoggedEvent, SwapEvent};
+
+    fn program_data(event: &SwapEvent) -> String {
+        let mut data = SwapEvent::discriminator().to_vec();
+        event.serialize(&mut data).unwrap();
+        format!("Program data: {}", STANDARD.encode(data))
+    }
+
+    #[test]
+    fn test_parse_logs() {
+        let hyperplane = hyperplane::id();
+        let router = Pubkey::new_unique();
+        let first = SwapEvent {
+            token_in_amount: 1_000,
+            token_out_amount: 990,
+            total_fees: 3,
+        };
+        let second = SwapEvent {
+            token_in_amount: 990,
+            token_out_amount: 42,
+            total_fees: 2,
+        };
+        let logs = vec![
+            format!("Program {} invoke [1]", hyperplane),
+            "Program log: Instruction: Swap".to_string(),
+            program_data(&first),
+            format!(
+                "Program {} consumed 30000 of 200000 compute units",
+                hyperplane
+            ),
+            format!("Program {} success", hyperplane),
+            format!("Program {} invoke [1]", router),
+            // Same payload logged by another program
+            program_data(&first),
+            format!("Program {} invoke [2]", hyperplane),
+            "Program log: Instruction: Swap".to_string(),
+            program_data(&second),
+            format!("Program {} success", hyperplane),
+            format!("Program {} success", router),
+        ];
+        assert_eq!(
+            parse_logs(&hyperplane, &logs),
+            vec![
+                LoggedEvent {
+                    event: first,
+                    invocation: 0,
+                    depth: 1,
+                },
+                LoggedEvent {
+                    event: second,
+                    invocation: 1,
+                    depth: 2,
+                },
+            ]
+        );
+        // The events of a pool of another deployment are those of its program
+        assert!(parse_logs(&Pubkey::new_unique(), &logs).is_empty());
+        assert!(parse_logs(&hyperplane, &["Program data: not base64"]).is_empty());
+    }
+}
@@ -0,0 +1,229 @@
+//! Export of quote curves across pools to CSV, or Parquet with the `parquet` feature, for
+//! analyzing Rarefish liquidity offline.
+
+use std::io::Write;
+
+use anyhow::Result;
+use rust_decimal::Decimal;
+use solana_sdk::pubkey::Pubkey;
+
+use crate::amm::{Amm, QuoteParams, SwapMode};
+use crate::registry::MarketRegistry;
+use crate::ui::{to_raw_amount, to_ui_amount};
+use crate::TradeDirection;
+
+const CSV_HEADER: &str = "pool,direction,input_mint,output_mint,ui_in_amount,in_amount,out_amount,fee_amount,ui_out_amount,price,price_impact_bps";
+
+/// A quote of one size of the grid in one direction of a pool
+#[derive(Clone, Debug, PartialEq)]
+pub struct QuotePoint {
+    pub pool: Pubkey,
+    pub trade_direction: TradeDirection,
+    pub input_mint: Pubkey,
+    pub output_mint: Pubkey,
+    pub ui_in_amount: Decimal,
+    pub in_amount: u64,
+    pub out_amount: u64,
+    pub fee_amount: u64,
+    pub ui_out_amount: Decimal,
+    /// Output per input in UI units, after fees
+    pub price: Decimal,
+    /// How much worse the execution price is than the spot price, in basis points
+    pub price_impact_bps: Decimal,
+}
+
+/// Quotes every size of `ui_sizes`, in UI units of the input token, in both directions of
+/// every updated pool of the registry.
+///
+/// Pools without reserves or decimals are skipped, as are sizes a pool cannot quote, so the
+/// grid of a shallow pool stops at the largest size it can fill.
+pub fn quote_grid(registry: &MarketRegistry, ui_sizes: &[Decimal]) -> Vec<QuotePoint> {
+    let mut points = vec![];
+    for market in registry.markets() {
+        let (Some(token_a_decimals), Some(token_b_decimals)) =
+            (market.token_a_decimals(), market.token_b_decimals())
+        else {
+            continue;
+        };
+        if market.reserves().is_none() {
+            continue;
+        }
+        let [token_a_mint, token_b_mint] = market.reserve_mints();
+        for (trade_direction, input_mint, output_mint, input_decimals, output_decimals) in [
+            (
+                TradeDirection::AtoB,
+                token_a_mint,
+                token_b_mint,
+                token_a_decimals,
+                token_b_decimals,
+            ),
+            (
+                TradeDirection::BtoA,
+                token_b_mint,
+                token_a_mint,
+                token_b_decimals,
+                token_a_decimals,
+            ),
+        ] {
+            for ui_in_amount in ui_sizes {
+                let Ok(in_amount) = to_raw_amount(*ui_in_amount, input_decimals) else {
+                    continue;
+                };
+                if in_amount == 0 {
+                    continue;
+                }
+                let quote_params = QuoteParams {
+                    input_mint,
+                    output_mint,
+                    amount: in_amount,
+                    swap_mode: SwapMode::ExactIn,
+                };
+                let Ok(quote) = market.quote(&quote_params) else {
+                    continue;
+                };
+                let (Ok(ui_in_amount), Ok(ui_out_amount)) = (
+                    to_ui_amount(in_amount, input_decimals),
+                    to_ui_amount(quote.out_amount, output_decimals),
+                ) else {
+                    continue;
+                };
+                let price_impact_bps = market
+                    .execution_price(&quote_params, &quote)
+                    .map_or(Decimal::ZERO, |price| price.slippage_bps);
+                points.push(QuotePoint {
+                    pool: market.key(),
+                    trade_direction,
+                    input_mint,
+                    output_mint,
+                    ui_in_amount,
+                    in_amount,
+                    out_amount: quote.out_amount,
+                    fee_amount: quote.fee_amount,
+                    ui_out_amount,
+                    price: ui_out_amount / ui_in_amount,
+                    price_impact_bps,
+                });
+            }
+        }
+    }
+    points
+}
+
+/// Writes the points as CSV with a header row, decimals at full precision
+pub fn write_csv(points: &[QuotePoint], mut writer: impl Write) -> Result<()> {
+    writeln!(writer, "{}", CSV_HEADER)?;
+    for point in points {
+        writeln!(
+            writer,
+            "{},{},{},{},{},{},{},{},{},{},{}",
+            point.pool,
+            direction_label(point.trade_direction),
+            point.input_mint,
+            point.output_mint,
+            point.ui_in_amount,
+            point.in_amount,
+            point.out_amount,
+            point.fee_amount,
+            point.ui_out_amount,
+            point.price,
+            point.price_impact_bps
+        )?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// Writes the points as a single row group Parquet file, with UI amounts and prices as
+/// doubles and raw amounts as unsigned integers
+#[cfg(feature = "parquet")]
+pub fn write_parquet(points: &[QuotePoint], writer: impl Write + Send) -> Result<()> {
+    use std::sync::Arc;
+
+    use arrow::array::{ArrayRef, Float64Array, StringArray, UInt64Array};
+    use arrow::record_batch::RecordBatch;
+    use parquet::arrow::ArrowWriter;
+    use rust_decimal::prelude::ToPrimitive;
+
+    let strings = |f: fn(&QuotePoint) -> String| -> ArrayRef {
+        Arc::new(StringArray::from_iter_values(points.iter().map(f)))
+    };
+    let integers = |f: fn(&QuotePoint) -> u64| -> ArrayRef {
+        Arc::new(UInt64Array::from_iter_values(points.iter().map(f)))
+    };
+    let doubles = |f: fn(&QuotePoint) -> Decimal| -> ArrayRef {
+        Arc::new(Float64Array::from_iter_values(
+            points
+                .iter()
+                .map(|point| f(point).to_f64().unwrap_or(f64::NAN)),
+        ))
+    };
+    let batch = RecordBatch::try_from_iter([
+        ("pool", strings(|point| point.pool.to_string())),
+        (
+            "direction",
+            strings(|point| direction_label(point.trade_direction).to_string()),
+        ),
+        ("input_mint", strings(|point| point.input_mint.to_string())),
+        (
+            "output_mint",
+            strings(|point| point.output_mint.to_string()),
+        ),
+        ("ui_in_amount", doubles(|point| point.ui_in_amount)),
+        ("in_amount", integers(|point| point.in_amount)),
+        ("out_amount", integers(|point| point.out_amount)),
+        ("fee_amount", integers(|point| point.fee_amount)),
+        ("ui_out_amount", doubles(|point| point.ui_out_amount)),
+        ("price", doubles(|point| point.price)),
+        ("price_impact_bps", doubles(|point| point.price_impact_bps)),
+    ])?;
+    let mut writer = ArrowWriter::try_new(writer, batch.schema(), None)?;
+    writer.write(&batch)?;
+    writer.close()?;
+    Ok(())
+}
+
+fn direction_label(trade_direction: TradeDirection) -> &'static str {
+    match trade_direction {
+        TradeDirection::AtoB => "AtoB",
+        TradeDirection::BtoA => "BtoA",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rust_decimal::Decimal;
+    use solana_sdk::pubkey::Pubkey;
+
+    use super::{write_csv, QuotePoint, CSV_HEADER};
+    use crate::TradeDirection;
+
+    #[test]
+    fn test_write_csv() {
+        let point = QuotePoint {
+            pool: Pubkey::new_unique(),
+            trade_direction: TradeDirection::BtoA,
+            input_mint: Pubkey::new_unique(),
+            output_mint: Pubkey::new_unique(),
+            ui_in_amount: Decimal::new(15, 1),
+            in_amount: 1_500_000,
+            out_amount: 2_990,
+            fee_amount: 3_750,
+            ui_out_amount: Decimal::new(299, 5),
+            price: Decimal::new(19933, 7),
+            price_impact_bps: Decimal::new(25, 0),
+        };
+        let mut csv = vec![];
+        write_csv(&[point.clone()], &mut csv).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines[0], CSV_HEADER);
+        assert_eq!(
+            lines[1],
+            format!(
+                "{},BtoA,{},{},1.5,1500000,2990,3750,0.00299,0.0019933,25",
+                point.pool, point.input_mint, point.output_mint
+            )
+        );
+        assert_eq!(lines.len(), 2);
+    }
+}
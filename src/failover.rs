@@ -0,0 +1,318 @@
+//! Failover between RPC endpoints, so a quoting service keeps refreshing its markets when
+//! its primary endpoint is down or falls behind.
+//!
+//! Like the rate limit, failover is applied by the [`RpcSender`] of the client: every helper
+//! taking an `RpcClient`, including the registry refresh, uses it unchanged.
+
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use serde_json::Value;
+use solana_client::client_error::{ClientError, ClientErrorKind, Result as ClientResult};
+use solana_client::http_sender::HttpSender;
+use solana_client::nonblocking::rpc_client::RpcClient as NonblockingRpcClient;
+use solana_client::rpc_client::{RpcClient, RpcClientConfig};
+use solana_client::rpc_request::{RpcError, RpcRequest};
+use solana_client::rpc_sender::{RpcSender, RpcTransportStats};
+use solana_sdk::commitment_config::CommitmentConfig;
+
+/// Returned by an RPC node that is behind the cluster
+const JSON_RPC_SERVER_ERROR_NODE_UNHEALTHY: i64 = -32005;
+
+struct Endpoint {
+    sender: Box<dyn RpcSender + Send + Sync>,
+    health: Mutex<EndpointHealth>,
+}
+
+#[derive(Default)]
+struct EndpointHealth {
+    consecutive_failures: u32,
+    retry_at: Option<Instant>,
+}
+
+/// Sends each request to the current endpoint, moving on to the next one when it fails or
+/// answers from a slot more than `max_slot_lag` behind the newest slot seen.
+///
+/// A failing endpoint is skipped for a backoff doubling with each consecutive failure, from
+/// `min_backoff` up to `max_backoff`, then tried again. The first endpoint is the current
+/// one until it fails. Errors returned by a healthy node, such as a failed preflight, are
+/// passed through without failing over.
+pub struct FailoverSender {
+    endpoints: Vec<Endpoint>,
+    current: AtomicUsize,
+    highest_slot: AtomicU64,
+    max_slot_lag: u64,
+    min_backoff: Duration,
+    max_backoff: Duration,
+}
+
+impl FailoverSender {
+    /// Fails over between `endpoints`, in order of preference
+    pub fn new(endpoints: Vec<Box<dyn RpcSender + Send + Sync>>) -> Self {
+        assert!(
+            !endpoints.is_empty(),
+            "Failover needs at least one endpoint"
+        );
+        Self {
+            endpoints: endpoints
+                .into_iter()
+                .map(|sender| Endpoint {
+                    sender,
+                    health: Mutex::new(EndpointHealth::default()),
+                })
+                .collect(),
+            current: AtomicUsize::new(0),
+            highest_slot: AtomicU64::new(0),
+            max_slot_lag: 50,
+            min_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(60),
+        }
+    }
+
+    pub fn from_urls<U: ToString>(urls: impl IntoIterator<Item = U>) -> Self {
+        Self::new(
+            urls.into_iter()
+                .map(|url| {
+                    Box::new(HttpSender::new(url.to_string())) as Box<dyn RpcSender + Send + Sync>
+                })
+                .collect(),
+        )
+    }
+
+    pub fn with_max_slot_lag(mut self, max_slot_lag: u64) -> Self {
+        self.max_slot_lag = max_slot_lag;
+        self
+    }
+
+    pub fn with_backoff(mut self, min_backoff: Duration, max_backoff: Duration) -> Self {
+        self.min_backoff = min_backoff;
+        self.max_backoff = max_backoff.max(min_backoff);
+        self
+    }
+
+    /// Endpoints in the order they are tried: from the current one, those not backing off
+    /// first
+    fn candidates(&self) -> Vec<usize> {
+        let now = Instant::now();
+        let current = self.current.load(Ordering::Relaxed);
+        let (mut available, backing_off): (Vec<_>, Vec<_>) = (0..self.endpoints.len())
+            .map(|offset| (current + offset) % self.endpoints.len())
+            .partition(|index| {
+                let health = self.endpoints[*index].health.lock().unwrap();
+                health.retry_at.map_or(true, |retry_at| retry_at <= now)
+            });
+        available.extend(backing_off);
+        available
+    }
+
+    fn record_success(&self, index: usize) {
+        *self.endpoints[index].health.lock().unwrap() = EndpointHealth::default();
+        self.current.store(index, Ordering::Relaxed);
+    }
+
+    fn record_failure(&self, index: usize) {
+        let mut health = self.endpoints[index].health.lock().unwrap();
+        let backoff = self
+            .min_backoff
+            .saturating_mul(1 << health.consecutive_failures.min(16))
+            .min(self.max_backoff);
+        health.consecutive_failures = health.consecutive_failures.saturating_add(1);
+        health.retry_at = Some(Instant::now() + backoff);
+    }
+
+    /// Fails the response of an endpoint behind the newest slot seen
+    fn check_slot(&self, request: RpcRequest, response: &Value) -> ClientResult<()> {
+        let Some(slot) = response_slot(request, response) else {
+            return Ok(());
+        };
+        let highest_slot = self
+            .highest_slot
+            .fetch_max(slot, Ordering::Relaxed)
+            .max(slot);
+        if slot.saturating_add(self.max_slot_lag) < highest_slot {
+            return Err(ClientErrorKind::Custom(format!(
+                "Stale response from slot {}, {} slots behind",
+                slot,
+                highest_slot - slot
+            ))
+            .into());
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl RpcSender for FailoverSender {
+    async fn send(&self, request: RpcRequest, params: Value) -> ClientResult<Value> {
+        let mut last_error = None;
+        for index in self.candidates() {
+            let endpoint = &self.endpoints[index];
+            let error = match endpoint.sender.send(request, params.clone()).await {
+                Ok(response) => match self.check_slot(request, &response) {
+                    Ok(()) => {
+                        self.record_success(index);
+                        return Ok(response);
+                    }
+                    Err(error) => error,
+                },
+                Err(error) if is_endpoint_error(&error) => error,
+                Err(error) => {
+                    self.record_success(index);
+                    return Err(error);
+                }
+            };
+            self.record_failure(index);
+            last_error = Some(error);
+        }
+        Err(last_error.expect("Failover has at least one endpoint"))
+    }
+
+    fn get_transport_stats(&self) -> RpcTransportStats {
+        self.endpoints[self.current.load(Ordering::Relaxed)]
+            .sender
+            .get_transport_stats()
+    }
+
+    fn url(&self) -> String {
+        self.endpoints[self.current.load(Ordering::Relaxed)]
+            .sender
+            .url()
+    }
+}
+
+/// Errors of the endpoint itself, rather than of the request
+fn is_endpoint_error(error: &ClientError) -> bool {
+    match error.kind() {
+        ClientErrorKind::Io(_) | ClientErrorKind::Reqwest(_) => true,
+        ClientErrorKind::RpcError(RpcError::RpcRequestError(_)) => true,
+        ClientErrorKind::RpcError(RpcError::RpcResponseError { code, .. }) => {
+            *code == JSON_RPC_SERVER_ERROR_NODE_UNHEALTHY
+        }
+        _ => false,
+    }
+}
+
+/// The slot a response was served from: the `context` of account and program responses, or
+/// the slot itself for `getSlot`
+fn response_slot(request: RpcRequest, response: &Value) -> Option<u64> {
+    match request {
+        RpcRequest::GetSlot => response.as_u64(),
+        _ => response
+            .get("context")
+            .and_then(|context| context.get("slot"))
+            .and_then(Value::as_u64),
+    }
+}
+
+/// An `RpcClient` failing over between `urls`, in order of preference
+pub fn failover_rpc_client<U: ToString>(
+    urls: impl IntoIterator<Item = U>,
+    commitment: CommitmentConfig,
+) -> RpcClient {
+    RpcClient::new_sender(
+        FailoverSender::from_urls(urls),
+        RpcClientConfig::with_commitment(commitment),
+    )
+}
+
+/// [`failover_rpc_client`] with the nonblocking client
+pub fn failover_nonblocking_rpc_client<U: ToString>(
+    urls: impl IntoIterator<Item = U>,
+    commitment: CommitmentConfig,
+) -> NonblockingRpcClient {
+    NonblockingRpcClient::new_sender(
+        FailoverSender::from_urls(urls),
+        RpcClientConfig::with_commitment(commitment),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use async_trait::async_trait;
+    use serde_json::{json, Value};
+    use solana_client::client_error::{ClientErrorKind, Result as ClientResult};
+    use solana_client::rpc_request::{RpcError, RpcRequest};
+    use solana_client::rpc_sender::{RpcSender, RpcTransportStats};
+
+    use super::FailoverSender;
+
+    /// Answers every request with `response`, counting them
+    struct MockSender {
+        response: Result<Value, String>,
+        requests: Arc<AtomicUsize>,
+    }
+
+    #[async_trait]
+    impl RpcSender for MockSender {
+        async fn send(&self, _request: RpcRequest, _params: Value) -> ClientResult<Value> {
+            self.requests.fetch_add(1, Ordering::Relaxed);
+            self.response.clone().map_err(|message| {
+                ClientErrorKind::RpcError(RpcError::RpcRequestError(message)).into()
+            })
+        }
+
+        fn get_transport_stats(&self) -> RpcTransportStats {
+            RpcTransportStats::default()
+        }
+
+        fn url(&self) -> String {
+            "mock".to_string()
+        }
+    }
+
+    fn endpoint(
+        response: Result<Value, String>,
+    ) -> (Box<dyn RpcSender + Send + Sync>, Arc<AtomicUsize>) {
+        let requests = Arc::new(AtomicUsize::new(0));
+        let sender = MockSender {
+            response,
+            requests: requests.clone(),
+        };
+        (Box::new(sender), requests)
+    }
+
+    fn accounts_response(slot: u64) -> Value {
+        json!({ "context": { "slot": slot }, "value": [] })
+    }
+
+    #[tokio::test]
+    async fn test_failover_sender() {
+        let (down, down_requests) = endpoint(Err("connection refused".to_string()));
+        let (up, up_requests) = endpoint(Ok(accounts_response(1_000)));
+        let sender = FailoverSender::new(vec![down, up])
+            .with_backoff(Duration::from_secs(60), Duration::from_secs(60));
+        let request = RpcRequest::GetMultipleAccounts;
+        assert_eq!(
+            sender.send(request, Value::Null).await.unwrap(),
+            accounts_response(1_000)
+        );
+        // The failed endpoint backs off, requests stay on the healthy one
+        sender.send(request, Value::Null).await.unwrap();
+        assert_eq!(down_requests.load(Ordering::Relaxed), 1);
+        assert_eq!(up_requests.load(Ordering::Relaxed), 2);
+
+        // An endpoint behind the newest slot seen is failed over like a down one
+        let (stale, stale_requests) = endpoint(Ok(accounts_response(900)));
+        let (fresh, fresh_requests) = endpoint(Ok(accounts_response(1_000)));
+        let sender = FailoverSender::new(vec![stale, fresh]).with_max_slot_lag(10);
+        sender.highest_slot.store(1_000, Ordering::Relaxed);
+        assert_eq!(
+            sender.send(request, Value::Null).await.unwrap(),
+            accounts_response(1_000)
+        );
+        assert_eq!(stale_requests.load(Ordering::Relaxed), 1);
+        assert_eq!(fresh_requests.load(Ordering::Relaxed), 1);
+
+        let (down, _) = endpoint(Err("connection refused".to_string()));
+        assert!(FailoverSender::new(vec![down])
+            .send(request, Value::Null)
+            .await
+            .is_err());
+    }
+}
@@ -0,0 +1,128 @@
+//! C API over the quoting core, see `include/rarefish.h`.
+//!
+//! Adapters are created from the raw pool account data, fed the vault balances and then
+//! quoted, so non-Rust systems get exactly the same math as the Jupiter integration.
+
+use std::slice;
+
+use solana_sdk::account::Account;
+use solana_sdk::pubkey::Pubkey;
+
+use crate::amm::KeyedAccount;
+use crate::{JupiterRarefish, TradeDirection};
+
+pub const RAREFISH_OK: i32 = 0;
+/// A required pointer was null
+pub const RAREFISH_ERROR_NULL_POINTER: i32 = 1;
+/// The pool cannot be quoted yet, the reserves were never set
+pub const RAREFISH_ERROR_NOT_READY: i32 = 2;
+/// The curve rejected the swap, e.g. an amount too large for the reserves
+pub const RAREFISH_ERROR_QUOTE: i32 = 3;
+
+/// Opaque handle owning a [`JupiterRarefish`]
+pub struct RarefishAdapter(JupiterRarefish);
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RarefishQuote {
+    pub in_amount: u64,
+    pub out_amount: u64,
+    pub fee_amount: u64,
+}
+
+/// Creates an adapter from the 32 byte pool address and the pool account data.
+/// Returns null if the data is not a valid SwapPool account.
+///
+/// # Safety
+/// `pool_key` must point to 32 readable bytes and `pool_data` to `pool_data_len` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn rarefish_adapter_new(
+    pool_key: *const u8,
+    pool_data: *const u8,
+    pool_data_len: usize,
+) -> *mut RarefishAdapter {
+    if pool_key.is_null() || pool_data.is_null() {
+        return std::ptr::null_mut();
+    }
+    let key = Pubkey::new_from_array(slice::from_raw_parts(pool_key, 32).try_into().unwrap());
+    let keyed_account = KeyedAccount {
+        key,
+        account: Account {
+            lamports: 0,
+            data: slice::from_raw_parts(pool_data, pool_data_len).to_vec(),
+            owner: hyperplane::id(),
+            executable: false,
+            rent_epoch: 0,
+        },
+        params: None,
+    };
+    match JupiterRarefish::new_from_keyed_account(&keyed_account) {
+        Ok(jupiter_rarefish) => Box::into_raw(Box::new(RarefishAdapter(jupiter_rarefish))),
+        Err(_) => std::ptr::null_mut(),
+    }
+}
+
+/// Frees an adapter created by [`rarefish_adapter_new`], null is ignored.
+///
+/// # Safety
+/// `adapter` must be null or a pointer returned by [`rarefish_adapter_new`] that was not freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn rarefish_adapter_free(adapter: *mut RarefishAdapter) {
+    if !adapter.is_null() {
+        drop(Box::from_raw(adapter));
+    }
+}
+
+/// Sets the token A and token B vault balances the adapter quotes against.
+///
+/// # Safety
+/// `adapter` must be a live pointer returned by [`rarefish_adapter_new`].
+#[no_mangle]
+pub unsafe extern "C" fn rarefish_adapter_update_reserves(
+    adapter: *mut RarefishAdapter,
+    token_a_amount: u64,
+    token_b_amount: u64,
+) -> i32 {
+    let Some(adapter) = adapter.as_mut() else {
+        return RAREFISH_ERROR_NULL_POINTER;
+    };
+    adapter.0.update_reserves(token_a_amount, token_b_amount);
+    RAREFISH_OK
+}
+
+/// Quotes an exact-in swap of `amount_in`, selling token A when `a_to_b` is true and token B
+/// otherwise. The result is written to `quote` on success.
+///
+/// # Safety
+/// `adapter` must be a live pointer returned by [`rarefish_adapter_new`] and `quote` must
+/// point to a writable `RarefishQuote`.
+#[no_mangle]
+pub unsafe extern "C" fn rarefish_adapter_quote(
+    adapter: *const RarefishAdapter,
+    a_to_b: bool,
+    amount_in: u64,
+    quote: *mut RarefishQuote,
+) -> i32 {
+    let (Some(adapter), Some(quote)) = (adapter.as_ref(), quote.as_mut()) else {
+        return RAREFISH_ERROR_NULL_POINTER;
+    };
+    if adapter.0.reserves().is_none() {
+        return RAREFISH_ERROR_NOT_READY;
+    }
+    let trade_direction = if a_to_b {
+        TradeDirection::AtoB
+    } else {
+        TradeDirection::BtoA
+    };
+    match adapter.0.quote_many(trade_direction, &[amount_in]) {
+        Ok(quotes) => {
+            *quote = RarefishQuote {
+                in_amount: quotes[0].in_amount,
+                out_amount: quotes[0].out_amount,
+                fee_amount: quotes[0].fee_amount,
+            };
+            RAREFISH_OK
+        }
+        Err(_) => RAREFISH_ERROR_QUOTE,
+    }
+}
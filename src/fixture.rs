@@ -0,0 +1,179 @@
+use std::fs;
+use std::path::Path;
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use solana_account_decoder::{UiAccount, UiAccountEncoding};
+use solana_sdk::account::Account;
+use solana_sdk::pubkey::Pubkey;
+
+use crate::amm::{AccountMap, KeyedAccount};
+
+/// A recorded pool account together with the vault and mint accounts needed to quote it,
+/// used to run deterministic tests without network access.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PoolFixture {
+    pub pool: Pubkey,
+    pub slot: u64,
+    pub accounts: Vec<(Pubkey, Account)>,
+}
+
+/// JSON representation using the same account encoding as the RPC and `solana account --output json`
+#[derive(Serialize, Deserialize)]
+struct JsonPoolFixture {
+    pool: String,
+    slot: u64,
+    accounts: Vec<JsonAccount>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct JsonAccount {
+    pubkey: String,
+    account: UiAccount,
+}
+
+impl PoolFixture {
+    pub fn keyed_account(&self) -> Result<KeyedAccount> {
+        let account = self
+            .account(&self.pool)
+            .ok_or_else(|| anyhow!("Fixture is missing pool account {}", self.pool))?;
+        Ok(KeyedAccount {
+            key: self.pool,
+            account: account.clone(),
+            params: None,
+        })
+    }
+
+    pub fn account(&self, key: &Pubkey) -> Option<&Account> {
+        self.accounts
+            .iter()
+            .find(|(pubkey, _)| pubkey == key)
+            .map(|(_, account)| account)
+    }
+
+    pub fn account_map(&self) -> AccountMap {
+        self.accounts.iter().cloned().collect()
+    }
+
+    pub fn to_json(&self) -> Result<String> {
+        let fixture = JsonPoolFixture {
+            pool: self.pool.to_string(),
+            slot: self.slot,
+            accounts: self
+                .accounts
+                .iter()
+                .map(|(pubkey, account)| JsonAccount {
+                    pubkey: pubkey.to_string(),
+                    account: UiAccount::encode(
+                        pubkey,
+                        account,
+                        UiAccountEncoding::Base64,
+                        None,
+                        None,
+                    ),
+                })
+                .collect(),
+        };
+        Ok(serde_json::to_string_pretty(&fixture)?)
+    }
+
+    pub fn from_json(json: &str) -> Result<Self> {
+        let fixture: JsonPoolFixture = serde_json::from_str(json)?;
+        let accounts = fixture
+            .accounts
+            .into_iter()
+            .map(|JsonAccount { pubkey, account }| {
+                let key: Pubkey = pubkey.parse()?;
+                let account = account
+                    .decode()
+                    .ok_or_else(|| anyhow!("Failed to decode fixture account {}", key))?;
+                Ok((key, account))
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self {
+            pool: fixture.pool.parse()?,
+            slot: fixture.slot,
+            accounts,
+        })
+    }
+
+    pub fn to_bincode(&self) -> Result<Vec<u8>> {
+        Ok(bincode::serialize(self)?)
+    }
+
+    pub fn from_bincode(bytes: &[u8]) -> Result<Self> {
+        Ok(bincode::deserialize(bytes)?)
+    }
+
+    /// Writes the fixture as JSON, or as bincode if the path has a `.bin` extension
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        if is_bincode(path) {
+            fs::write(path, self.to_bincode()?)?;
+        } else {
+            fs::write(path, self.to_json()?)?;
+        }
+        Ok(())
+    }
+
+    /// Reads a fixture written by [`PoolFixture::save`]
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        if is_bincode(path) {
+            Self::from_bincode(&fs::read(path)?)
+        } else {
+            Self::from_json(&fs::read_to_string(path)?)
+        }
+    }
+}
+
+fn is_bincode(path: &Path) -> bool {
+    path.extension()
+        .map_or(false, |extension| extension == "bin")
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_sdk::account::Account;
+    use solana_sdk::pubkey::Pubkey;
+
+    use super::PoolFixture;
+
+    fn fixture() -> PoolFixture {
+        let pool = Pubkey::new_unique();
+        let account = |data: Vec<u8>| Account {
+            lamports: 2_039_280,
+            data,
+            owner: hyperplane::id(),
+            executable: false,
+            rent_epoch: u64::MAX,
+        };
+        PoolFixture {
+            pool,
+            slot: 42,
+            accounts: vec![
+                (pool, account(vec![1, 2, 3])),
+                (Pubkey::new_unique(), account(vec![0; 165])),
+            ],
+        }
+    }
+
+    #[test]
+    fn test_fixture_json_round_trip() {
+        let fixture = fixture();
+        let restored = PoolFixture::from_json(&fixture.to_json().unwrap()).unwrap();
+        assert_eq!(restored, fixture);
+        assert_eq!(
+            restored.keyed_account().unwrap().account.data,
+            vec![1, 2, 3]
+        );
+    }
+
+    #[test]
+    fn test_fixture_bincode_round_trip() {
+        let fixture = fixture();
+        let restored = PoolFixture::from_bincode(&fixture.to_bincode().unwrap()).unwrap();
+        assert_eq!(restored, fixture);
+        assert_eq!(restored.account_map().len(), 2);
+    }
+}
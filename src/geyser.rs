@@ -0,0 +1,106 @@
+use std::collections::HashMap;
+
+use anyhow::{anyhow, Result};
+use futures::StreamExt;
+use solana_sdk::account::Account;
+use solana_sdk::pubkey::Pubkey;
+use yellowstone_grpc_client::GeyserGrpcClient;
+use yellowstone_grpc_proto::prelude::{
+    subscribe_update::UpdateOneof, CommitmentLevel, SubscribeRequest,
+    SubscribeRequestFilterAccounts, SubscribeUpdateAccountInfo,
+};
+
+use crate::amm::AccountMap;
+use crate::registry::SharedRegistry;
+
+/// Keeps a registry up to date from a Yellowstone geyser gRPC account subscription
+pub struct GeyserUpdater {
+    endpoint: String,
+    x_token: Option<String>,
+    commitment: CommitmentLevel,
+    registry: SharedRegistry,
+    accounts_map: AccountMap,
+}
+
+impl GeyserUpdater {
+    pub fn new(endpoint: String, x_token: Option<String>, registry: SharedRegistry) -> Self {
+        Self {
+            endpoint,
+            x_token,
+            commitment: CommitmentLevel::Processed,
+            registry,
+            accounts_map: AccountMap::new(),
+        }
+    }
+
+    pub fn with_commitment(mut self, commitment: CommitmentLevel) -> Self {
+        self.commitment = commitment;
+        self
+    }
+
+    /// Seeds the updater with the current state of the subscribed accounts.
+    ///
+    /// Geyser only sends accounts when they change, so without seeding a market is not
+    /// updated until all of its accounts have been written to at least once.
+    pub fn with_accounts(mut self, accounts_map: AccountMap) -> Self {
+        self.accounts_map = accounts_map;
+        self
+    }
+
+    /// Subscribes to every account the registry depends on and applies updates until the
+    /// stream ends
+    pub async fn run(mut self) -> Result<()> {
+        let keys = self.registry.read().unwrap().get_accounts_to_update();
+        let mut client =
+            GeyserGrpcClient::connect(self.endpoint.clone(), self.x_token.clone(), None)?;
+        let request = SubscribeRequest {
+            accounts: HashMap::from([(
+                "rarefish".to_string(),
+                SubscribeRequestFilterAccounts {
+                    account: keys.iter().map(Pubkey::to_string).collect(),
+                    owner: vec![],
+                    filters: vec![],
+                },
+            )]),
+            commitment: Some(self.commitment as i32),
+            ..SubscribeRequest::default()
+        };
+        let (_sink, mut stream) = client.subscribe_with_request(Some(request)).await?;
+        while let Some(message) = stream.next().await {
+            let Some(UpdateOneof::Account(update)) = message?.update_oneof else {
+                continue;
+            };
+            let Some(info) = update.account else {
+                continue;
+            };
+            let (key, account) = decode_account(info)?;
+            self.accounts_map.insert(key, account);
+            if let Err(e) = self
+                .registry
+                .write()
+                .unwrap()
+                .update_dependents(&key, &self.accounts_map)
+            {
+                log::warn!("Failed to update markets for {}: {}", key, e);
+            }
+        }
+        Err(anyhow!("Geyser account stream closed"))
+    }
+}
+
+fn decode_account(info: SubscribeUpdateAccountInfo) -> Result<(Pubkey, Account)> {
+    let key = Pubkey::try_from(info.pubkey.as_slice())
+        .map_err(|_| anyhow!("Invalid account pubkey in geyser update"))?;
+    let owner = Pubkey::try_from(info.owner.as_slice())
+        .map_err(|_| anyhow!("Invalid owner pubkey in geyser update"))?;
+    Ok((
+        key,
+        Account {
+            lamports: info.lamports,
+            data: info.data,
+            owner,
+            executable: info.executable,
+            rent_epoch: info.rent_epoch,
+        },
+    ))
+}
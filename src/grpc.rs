@@ -0,0 +1,149 @@
+use std::net::SocketAddr;
+use std::pin::Pin;
+
+use anyhow::Result;
+use solana_sdk::pubkey::Pubkey;
+use tokio_stream::Stream;
+use tonic::{Request, Response, Status};
+
+use crate::amm::{Amm, Quote};
+use crate::registry::SharedRegistry;
+
+pub mod proto {
+    tonic::include_proto!("rarefish.v1");
+}
+
+use proto::quoting_server::{Quoting, QuotingServer};
+use proto::{
+    DepthLevel, GetDepthRequest, GetPoolsRequest, GetPoolsResponse, Pool, QuoteRequest,
+    QuoteResponse,
+};
+
+pub struct QuotingService {
+    registry: SharedRegistry,
+}
+
+impl QuotingService {
+    pub fn new(registry: SharedRegistry) -> Self {
+        Self { registry }
+    }
+
+    fn registry_quote(
+        &self,
+        pool: &Pubkey,
+        input_mint: &Pubkey,
+        amount: u64,
+    ) -> Result<Quote, Status> {
+        self.registry
+            .read()
+            .unwrap()
+            .quote(pool, input_mint, amount)
+            .map_err(|e| Status::invalid_argument(e.to_string()))
+    }
+}
+
+fn parse_pubkey(value: &str) -> Result<Pubkey, Status> {
+    value
+        .parse()
+        .map_err(|_| Status::invalid_argument(format!("Invalid pubkey {}", value)))
+}
+
+#[tonic::async_trait]
+impl Quoting for QuotingService {
+    async fn quote(
+        &self,
+        request: Request<QuoteRequest>,
+    ) -> Result<Response<QuoteResponse>, Status> {
+        let request = request.into_inner();
+        let pool = parse_pubkey(&request.pool)?;
+        let input_mint = parse_pubkey(&request.input_mint)?;
+        let output_mint = {
+            let registry = self.registry.read().unwrap();
+            let market = registry
+                .get(&pool)
+                .ok_or_else(|| Status::not_found(format!("Pool {} is not registered", pool)))?;
+            market
+                .reserve_mints()
+                .into_iter()
+                .find(|mint| *mint != input_mint)
+                .unwrap_or_default()
+        };
+        let quote = self.registry_quote(&pool, &input_mint, request.amount)?;
+        Ok(Response::new(QuoteResponse {
+            pool: request.pool,
+            input_mint: request.input_mint,
+            output_mint: output_mint.to_string(),
+            in_amount: quote.in_amount,
+            out_amount: quote.out_amount,
+        }))
+    }
+
+    async fn get_pools(
+        &self,
+        _request: Request<GetPoolsRequest>,
+    ) -> Result<Response<GetPoolsResponse>, Status> {
+        let registry = self.registry.read().unwrap();
+        let pools = registry
+            .markets()
+            .map(|market| Pool {
+                pool: market.key().to_string(),
+                label: market.label(),
+                mints: market
+                    .reserve_mints()
+                    .iter()
+                    .map(Pubkey::to_string)
+                    .collect(),
+            })
+            .collect();
+        Ok(Response::new(GetPoolsResponse { pools }))
+    }
+
+    type GetDepthStream = Pin<Box<dyn Stream<Item = Result<DepthLevel, Status>> + Send>>;
+
+    async fn get_depth(
+        &self,
+        request: Request<GetDepthRequest>,
+    ) -> Result<Response<Self::GetDepthStream>, Status> {
+        let request = request.into_inner();
+        let pool = parse_pubkey(&request.pool)?;
+        let input_mint = parse_pubkey(&request.input_mint)?;
+        let mut amounts = request.amounts;
+        amounts.sort_unstable();
+
+        let mut levels = Vec::with_capacity(amounts.len());
+        let mut reference_price = None;
+        for amount in amounts {
+            let Quote {
+                in_amount,
+                out_amount,
+                ..
+            } = self.registry_quote(&pool, &input_mint, amount)?;
+            let price = if in_amount == 0 {
+                0.0
+            } else {
+                out_amount as f64 / in_amount as f64
+            };
+            let reference_price = *reference_price.get_or_insert(price);
+            let price_impact_pct = if reference_price > 0.0 {
+                (1.0 - price / reference_price) * 100.0
+            } else {
+                0.0
+            };
+            levels.push(Ok(DepthLevel {
+                in_amount,
+                out_amount,
+                price_impact_pct,
+            }));
+        }
+        Ok(Response::new(Box::pin(tokio_stream::iter(levels))))
+    }
+}
+
+/// Serves the quoting service for the registered markets until the server fails
+pub async fn serve(addr: SocketAddr, registry: SharedRegistry) -> Result<()> {
+    tonic::transport::Server::builder()
+        .add_service(QuotingServer::new(QuotingService::new(registry)))
+        .serve(addr)
+        .await?;
+    Ok(())
+}
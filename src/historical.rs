@@ -0,0 +1,105 @@
+//! Adapter state at a past slot, to answer "what was the quote at time T" questions.
+//!
+//! From recorded snapshots, see [`crate::backtest::Backtest::market_at`]. Without snapshots,
+//! [`market_at_slot`] rewinds the vault balances of the current state through the
+//! transaction history of the vaults, which requires an RPC node keeping that history.
+
+use std::str::FromStr;
+
+use anyhow::{anyhow, Result};
+use solana_client::rpc_client::{GetConfirmedSignaturesForAddress2Config, RpcClient};
+use solana_client::rpc_config::RpcTransactionConfig;
+use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Signature;
+use solana_transaction_status::UiTransactionEncoding;
+
+use crate::decode::pre_token_balance;
+use crate::JupiterRarefish;
+
+/// Signatures requested per `getSignaturesForAddress` call, the RPC maximum
+const MAX_SIGNATURES_PER_PAGE: usize = 1_000;
+
+/// The adapter of `pool` as it was at the end of `slot`.
+///
+/// Each vault balance is read from the first transaction touching the vault after `slot`,
+/// the balance before it being the balance at `slot`, so deposits, withdrawals and plain
+/// transfers into the vaults are accounted for along with swaps. The pool account, holding
+/// the curve and fees, is the current one: changes of the pool settings since `slot` are not
+/// rewound.
+pub fn market_at_slot(rpc: &RpcClient, pool: &Pubkey, slot: u64) -> Result<JupiterRarefish> {
+    let mut jupiter_rarefish = JupiterRarefish::load(rpc, pool)?;
+    let swap_pool = jupiter_rarefish.pool().clone();
+    if let Some(amount) = balance_at_slot(rpc, &swap_pool.token_a_vault, slot)? {
+        if let Some(vault) = jupiter_rarefish.token_a_vault.as_mut() {
+            vault.amount = amount;
+        }
+    }
+    if let Some(amount) = balance_at_slot(rpc, &swap_pool.token_b_vault, slot)? {
+        if let Some(vault) = jupiter_rarefish.token_b_vault.as_mut() {
+            vault.amount = amount;
+        }
+    }
+    if let Some(amount) = balance_at_slot(rpc, &swap_pool.token_a_fees_vault, slot)? {
+        jupiter_rarefish.token_a_fees = Some(amount);
+    }
+    if let Some(amount) = balance_at_slot(rpc, &swap_pool.token_b_fees_vault, slot)? {
+        jupiter_rarefish.token_b_fees = Some(amount);
+    }
+    Ok(jupiter_rarefish)
+}
+
+/// The balance of the token account `account` at the end of `slot`, `None` when no
+/// transaction touched it since, the current balance then being the balance at `slot`
+pub fn balance_at_slot(rpc: &RpcClient, account: &Pubkey, slot: u64) -> Result<Option<u64>> {
+    let commitment = CommitmentConfig::confirmed();
+    // Signatures come newest first, the oldest successful one after `slot` holds the balance
+    let mut oldest_after = None;
+    let mut before = None;
+    loop {
+        let signatures = rpc.get_signatures_for_address_with_config(
+            account,
+            GetConfirmedSignaturesForAddress2Config {
+                before,
+                until: None,
+                limit: Some(MAX_SIGNATURES_PER_PAGE),
+                commitment: Some(commitment),
+            },
+        )?;
+        let Some(last) = signatures.last() else {
+            break;
+        };
+        before = Some(parse_signature(&last.signature)?);
+        let mut reached_slot = false;
+        for status in &signatures {
+            if status.slot <= slot {
+                reached_slot = true;
+                break;
+            }
+            if status.err.is_none() {
+                oldest_after = Some(parse_signature(&status.signature)?);
+            }
+        }
+        if reached_slot || signatures.len() < MAX_SIGNATURES_PER_PAGE {
+            break;
+        }
+    }
+    let Some(signature) = oldest_after else {
+        return Ok(None);
+    };
+    let transaction = rpc.get_transaction_with_config(
+        &signature,
+        RpcTransactionConfig {
+            encoding: Some(UiTransactionEncoding::Base64),
+            commitment: Some(commitment),
+            max_supported_transaction_version: Some(0),
+        },
+    )?;
+    pre_token_balance(&transaction, account)?
+        .map(Some)
+        .ok_or_else(|| anyhow!("Transaction {} has no balance for {}", signature, account))
+}
+
+fn parse_signature(signature: &str) -> Result<Signature> {
+    Signature::from_str(signature).map_err(|_| anyhow!("Invalid signature {}", signature))
+}
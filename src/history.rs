@@ -0,0 +1,150 @@
+//! Swap history of a pool fetched from RPC, for backtests and analytics dashboards.
+
+use std::collections::VecDeque;
+use std::str::FromStr;
+
+use anyhow::{anyhow, Result};
+use solana_client::rpc_client::{GetConfirmedSignaturesForAddress2Config, RpcClient};
+use solana_client::rpc_config::RpcTransactionConfig;
+use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Signature;
+use solana_transaction_status::UiTransactionEncoding;
+
+use crate::decode::{decode_transaction_transfers, ExecutedSwap};
+
+/// Signatures requested per `getSignaturesForAddress` call, the RPC maximum
+const MAX_SIGNATURES_PER_PAGE: usize = 1_000;
+
+/// A swap against a pool found in its transaction history
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PoolSwap {
+    pub signature: Signature,
+    pub slot: u64,
+    /// Unix timestamp of the block, when known
+    pub block_time: Option<i64>,
+    pub swap: ExecutedSwap,
+}
+
+/// Iterator over the swaps of a pool, newest first.
+///
+/// Signatures are fetched a page at a time with `getSignaturesForAddress` as the iterator is
+/// consumed, and every successful transaction is fetched and decoded. Swaps against other
+/// pools in the same transactions are skipped.
+pub struct HistoryFetcher<'a> {
+    rpc: &'a RpcClient,
+    pool: Pubkey,
+    program_id: Pubkey,
+    commitment: CommitmentConfig,
+    page_size: usize,
+    before: Option<Signature>,
+    until: Option<Signature>,
+    swaps: VecDeque<PoolSwap>,
+    done: bool,
+}
+
+impl<'a> HistoryFetcher<'a> {
+    pub fn new(rpc: &'a RpcClient, pool: Pubkey) -> Self {
+        Self {
+            rpc,
+            pool,
+            program_id: hyperplane::id(),
+            commitment: CommitmentConfig::confirmed(),
+            page_size: MAX_SIGNATURES_PER_PAGE,
+            before: None,
+            until: None,
+            swaps: VecDeque::new(),
+            done: false,
+        }
+    }
+
+    /// The hyperplane deployment owning the pool, defaults to the mainnet program
+    pub fn with_program_id(mut self, program_id: Pubkey) -> Self {
+        self.program_id = program_id;
+        self
+    }
+
+    pub fn with_commitment(mut self, commitment: CommitmentConfig) -> Self {
+        self.commitment = commitment;
+        self
+    }
+
+    pub fn with_page_size(mut self, page_size: usize) -> Self {
+        self.page_size = page_size.clamp(1, MAX_SIGNATURES_PER_PAGE);
+        self
+    }
+
+    /// Starts the history before this signature instead of at the latest transaction
+    pub fn with_before(mut self, before: Signature) -> Self {
+        self.before = Some(before);
+        self
+    }
+
+    /// Stops the history at this signature, exclusive, e.g. the newest one already indexed
+    pub fn with_until(mut self, until: Signature) -> Self {
+        self.until = Some(until);
+        self
+    }
+
+    /// Fetches and decodes the next page of signatures, returns false once the history is
+    /// exhausted
+    fn fetch_page(&mut self) -> Result<bool> {
+        let signatures = self.rpc.get_signatures_for_address_with_config(
+            &self.pool,
+            GetConfirmedSignaturesForAddress2Config {
+                before: self.before,
+                until: self.until,
+                limit: Some(self.page_size),
+                commitment: Some(self.commitment),
+            },
+        )?;
+        let Some(last) = signatures.last() else {
+            return Ok(false);
+        };
+        self.before = Some(parse_signature(&last.signature)?);
+        for status in signatures.iter().filter(|status| status.err.is_none()) {
+            let signature = parse_signature(&status.signature)?;
+            let transaction = self.rpc.get_transaction_with_config(
+                &signature,
+                RpcTransactionConfig {
+                    encoding: Some(UiTransactionEncoding::Base64),
+                    commitment: Some(self.commitment),
+                    max_supported_transaction_version: Some(0),
+                },
+            )?;
+            for swap in decode_transaction_transfers(&self.program_id, &transaction)? {
+                if swap.swap.pool == self.pool {
+                    self.swaps.push_back(PoolSwap {
+                        signature,
+                        slot: transaction.slot,
+                        block_time: transaction.block_time,
+                        swap,
+                    });
+                }
+            }
+        }
+        Ok(signatures.len() == self.page_size)
+    }
+}
+
+impl Iterator for HistoryFetcher<'_> {
+    type Item = Result<PoolSwap>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.swaps.is_empty() && !self.done {
+            match self.fetch_page() {
+                Ok(more) => self.done = !more,
+                Err(e) => {
+                    self.done = true;
+                    return Some(Err(e));
+                }
+            }
+        }
+        // Swaps within a transaction come out in execution order
+        self.swaps.pop_front().map(Ok)
+    }
+}
+
+fn parse_signature(signature: &str) -> Result<Signature> {
+    Signature::from_str(signature).map_err(|_| anyhow!("Invalid signature {}", signature))
+}
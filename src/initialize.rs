@@ -0,0 +1,295 @@
+//! Creation of new Rarefish pools, so market operators can bootstrap pools from Rust.
+//!
+//! [`PoolInitializer`] builds the `InitializePool` instruction: the program creates the pool,
+//! its vaults, fee vaults and LP mint at the addresses of [`PoolAddresses`], and moves the
+//! initial supply from the admin's token accounts into the vaults. [`create_mint`] and
+//! [`create_pool`] send the transactions of the whole flow.
+
+use anchor_lang::{InstructionData, ToAccountMetas};
+use anchor_spl::associated_token::get_associated_token_address_with_program_id;
+use anchor_spl::token::spl_token;
+use anchor_spl::token_2022::spl_token_2022;
+use anyhow::{anyhow, Result};
+use hyperplane::curve::fees::Fees;
+use hyperplane::utils::seeds;
+use hyperplane::{CurveUserParameters, InitialSupply};
+use solana_client::rpc_client::RpcClient;
+use solana_sdk::instruction::Instruction;
+use solana_sdk::program_pack::Pack;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::{Keypair, Signature};
+use solana_sdk::signer::Signer;
+use solana_sdk::system_instruction;
+use solana_sdk::transaction::Transaction;
+
+use crate::JupiterRarefish;
+
+/// The accounts the program creates for a pool, derived from the pool address
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PoolAddresses {
+    pub pool: Pubkey,
+    pub pool_authority: Pubkey,
+    pub token_a_vault: Pubkey,
+    pub token_b_vault: Pubkey,
+    pub pool_token_mint: Pubkey,
+    pub token_a_fees_vault: Pubkey,
+    pub token_b_fees_vault: Pubkey,
+}
+
+impl PoolAddresses {
+    pub fn derive(pool: &Pubkey, program_id: &Pubkey) -> Self {
+        let pda = |seed: &[u8]| Pubkey::find_program_address(&[seed, pool.as_ref()], program_id).0;
+        Self {
+            pool: *pool,
+            pool_authority: pda(seeds::POOL_AUTHORITY),
+            token_a_vault: pda(seeds::TOKEN_A_VAULT),
+            token_b_vault: pda(seeds::TOKEN_B_VAULT),
+            pool_token_mint: pda(seeds::POOL_TOKEN_MINT),
+            token_a_fees_vault: pda(seeds::TOKEN_A_FEES_VAULT),
+            token_b_fees_vault: pda(seeds::TOKEN_B_FEES_VAULT),
+        }
+    }
+}
+
+/// Builder of the `InitializePool` instruction of a new pool.
+///
+/// Defaults to a fee-less constant product pool of the hyperplane program between spl-token
+/// mints, funded from the admin's associated token accounts. The LP tokens of the initial
+/// supply go to the admin's LP associated token account, created by the program.
+#[derive(Clone, Debug)]
+pub struct PoolInitializer {
+    program_id: Pubkey,
+    admin: Pubkey,
+    pool: Pubkey,
+    token_a_mint: Pubkey,
+    token_b_mint: Pubkey,
+    token_a_program: Pubkey,
+    token_b_program: Pubkey,
+    admin_token_a_account: Option<Pubkey>,
+    admin_token_b_account: Option<Pubkey>,
+    curve_parameters: CurveUserParameters,
+    fees: Fees,
+    initial_supply_a: u64,
+    initial_supply_b: u64,
+}
+
+impl PoolInitializer {
+    /// Pool at the address of the `pool` keypair, which signs the initialization along with
+    /// `admin`
+    pub fn new(
+        admin: Pubkey,
+        pool: Pubkey,
+        token_a_mint: Pubkey,
+        token_b_mint: Pubkey,
+        initial_supply_a: u64,
+        initial_supply_b: u64,
+    ) -> Self {
+        Self {
+            program_id: hyperplane::id(),
+            admin,
+            pool,
+            token_a_mint,
+            token_b_mint,
+            token_a_program: spl_token::id(),
+            token_b_program: spl_token::id(),
+            admin_token_a_account: None,
+            admin_token_b_account: None,
+            curve_parameters: CurveUserParameters::ConstantProduct,
+            fees: Fees {
+                trade_fee_numerator: 0,
+                trade_fee_denominator: 0,
+                owner_trade_fee_numerator: 0,
+                owner_trade_fee_denominator: 0,
+                owner_withdraw_fee_numerator: 0,
+                owner_withdraw_fee_denominator: 0,
+                host_fee_numerator: 0,
+                host_fee_denominator: 0,
+            },
+            initial_supply_a,
+            initial_supply_b,
+        }
+    }
+
+    pub fn with_program_id(mut self, program_id: Pubkey) -> Self {
+        self.program_id = program_id;
+        self
+    }
+
+    /// Token programs of the mints, spl-token or token-2022
+    pub fn with_token_programs(mut self, token_a_program: Pubkey, token_b_program: Pubkey) -> Self {
+        self.token_a_program = token_a_program;
+        self.token_b_program = token_b_program;
+        self
+    }
+
+    /// Admin token accounts the initial supply is taken from, instead of the admin's
+    /// associated token accounts
+    pub fn with_admin_token_accounts(
+        mut self,
+        admin_token_a_account: Pubkey,
+        admin_token_b_account: Pubkey,
+    ) -> Self {
+        self.admin_token_a_account = Some(admin_token_a_account);
+        self.admin_token_b_account = Some(admin_token_b_account);
+        self
+    }
+
+    pub fn with_curve(mut self, curve_parameters: CurveUserParameters) -> Self {
+        self.curve_parameters = curve_parameters;
+        self
+    }
+
+    pub fn with_fees(mut self, fees: Fees) -> Self {
+        self.fees = fees;
+        self
+    }
+
+    pub fn addresses(&self) -> PoolAddresses {
+        PoolAddresses::derive(&self.pool, &self.program_id)
+    }
+
+    /// The admin token accounts the initial supply is taken from
+    pub fn admin_token_accounts(&self) -> (Pubkey, Pubkey) {
+        let token_a = self.admin_token_a_account.unwrap_or_else(|| {
+            get_associated_token_address_with_program_id(
+                &self.admin,
+                &self.token_a_mint,
+                &self.token_a_program,
+            )
+        });
+        let token_b = self.admin_token_b_account.unwrap_or_else(|| {
+            get_associated_token_address_with_program_id(
+                &self.admin,
+                &self.token_b_mint,
+                &self.token_b_program,
+            )
+        });
+        (token_a, token_b)
+    }
+
+    pub fn instruction(&self) -> Instruction {
+        let addresses = self.addresses();
+        let (admin_token_a_ata, admin_token_b_ata) = self.admin_token_accounts();
+        let accounts = hyperplane::accounts::InitializePool {
+            admin: self.admin,
+            pool: self.pool,
+            pool_authority: addresses.pool_authority,
+            token_a_mint: self.token_a_mint,
+            token_b_mint: self.token_b_mint,
+            token_a_vault: addresses.token_a_vault,
+            token_b_vault: addresses.token_b_vault,
+            pool_token_mint: addresses.pool_token_mint,
+            token_a_fees_vault: addresses.token_a_fees_vault,
+            token_b_fees_vault: addresses.token_b_fees_vault,
+            admin_token_a_ata,
+            admin_token_b_ata,
+            admin_pool_token_ata: get_associated_token_address_with_program_id(
+                &self.admin,
+                &addresses.pool_token_mint,
+                &spl_token::id(),
+            ),
+            system_program: solana_sdk::system_program::id(),
+            rent: solana_sdk::sysvar::rent::id(),
+            pool_token_program: spl_token::id(),
+            token_a_token_program: self.token_a_program,
+            token_b_token_program: self.token_b_program,
+        };
+        Instruction {
+            program_id: self.program_id,
+            accounts: accounts.to_account_metas(None),
+            data: hyperplane::instruction::InitializePool {
+                curve_parameters: self.curve_parameters.clone(),
+                fees: self.fees,
+                initial_supply: InitialSupply {
+                    initial_supply_a: self.initial_supply_a,
+                    initial_supply_b: self.initial_supply_b,
+                },
+            }
+            .data(),
+        }
+    }
+}
+
+/// Instructions creating the account of `mint` funded by `payer` and initializing it, for a
+/// mint without extensions
+pub fn create_mint_instructions(
+    payer: &Pubkey,
+    mint: &Pubkey,
+    mint_authority: &Pubkey,
+    decimals: u8,
+    token_program: &Pubkey,
+    rent_lamports: u64,
+) -> Result<Vec<Instruction>> {
+    Ok(vec![
+        system_instruction::create_account(
+            payer,
+            mint,
+            rent_lamports,
+            spl_token::state::Mint::LEN as u64,
+            token_program,
+        ),
+        spl_token_2022::instruction::initialize_mint2(
+            token_program,
+            mint,
+            mint_authority,
+            None,
+            decimals,
+        )?,
+    ])
+}
+
+/// Creates the mint at the address of the `mint` keypair, paid by `payer`
+pub fn create_mint(
+    rpc: &RpcClient,
+    payer: &Keypair,
+    mint: &Keypair,
+    mint_authority: &Pubkey,
+    decimals: u8,
+    token_program: &Pubkey,
+) -> Result<Signature> {
+    let rent_lamports = rpc.get_minimum_balance_for_rent_exemption(spl_token::state::Mint::LEN)?;
+    let instructions = create_mint_instructions(
+        &payer.pubkey(),
+        &mint.pubkey(),
+        mint_authority,
+        decimals,
+        token_program,
+        rent_lamports,
+    )?;
+    send(rpc, &instructions, payer, &[mint])
+}
+
+/// Initializes the pool of `initializer`, paid by `admin`, and returns its ready-to-quote
+/// adapter. The admin token accounts must hold the initial supply.
+pub fn create_pool(
+    rpc: &RpcClient,
+    admin: &Keypair,
+    pool: &Keypair,
+    initializer: &PoolInitializer,
+) -> Result<JupiterRarefish> {
+    if admin.pubkey() != initializer.admin || pool.pubkey() != initializer.pool {
+        return Err(anyhow!(
+            "The admin and pool keypairs do not match the initializer of pool {}",
+            initializer.pool
+        ));
+    }
+    send(rpc, &[initializer.instruction()], admin, &[pool])?;
+    JupiterRarefish::load(rpc, &initializer.pool)
+}
+
+fn send(
+    rpc: &RpcClient,
+    instructions: &[Instruction],
+    payer: &Keypair,
+    signers: &[&Keypair],
+) -> Result<Signature> {
+    let mut all_signers = vec![payer];
+    all_signers.extend_from_slice(signers);
+    let transaction = Transaction::new_signed_with_payer(
+        instructions,
+        Some(&payer.pubkey()),
+        &all_signers,
+        rpc.get_latest_blockhash()?,
+    );
+    Ok(rpc.send_and_confirm_transaction(&transaction)?)
+}
@@ -0,0 +1,198 @@
+//! Submission of Rarefish swaps as Jito bundles through the block engine API, for
+//! MEV-protected execution.
+//!
+//! A bundle is executed atomically and in order by the Jito validators and is never exposed
+//! to the public mempool, so the swap cannot be sandwiched. The validator is paid with a tip:
+//! a SOL transfer to one of the tip accounts, included in the last transaction of the bundle.
+
+use anyhow::{anyhow, Result};
+use serde_json::{json, Value};
+use solana_sdk::hash::Hash;
+use solana_sdk::instruction::Instruction;
+use solana_sdk::message::{v0, VersionedMessage};
+use solana_sdk::pubkey;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Keypair;
+use solana_sdk::signer::Signer;
+use solana_sdk::system_instruction;
+use solana_sdk::transaction::VersionedTransaction;
+
+use crate::amm::SwapParams;
+use crate::JupiterRarefish;
+
+/// Bundles endpoint of the mainnet block engine
+pub const DEFAULT_BLOCK_ENGINE_URL: &str = "https://mainnet.block-engine.jito.wtf/api/v1/bundles";
+
+/// Largest number of transactions in a bundle
+pub const MAX_BUNDLE_LEN: usize = 5;
+
+/// Accounts the Jito validators accept tips on
+pub const TIP_ACCOUNTS: [Pubkey; 8] = [
+    pubkey!("96gYZGLnJYVFmbjzopPSU6QiEV5fGqZNyN9nmNhvrZU5"),
+    pubkey!("HFqU5x63VTqvQss8hp11i4wVV8bD44PvwucfZ2bU7gRe"),
+    pubkey!("Cw8CFyM9FkoMi7K7Crf6HNQqf4uEMzpKw6QNghXLvLkY"),
+    pubkey!("ADaUMid9yfUytqMBgopwjb2DTLSokTSzL1zt6iGPaS49"),
+    pubkey!("DfXygSm4jCyNCybVYYK6DwvWqjKee8pbDmJGcLWNDXjh"),
+    pubkey!("ADuUkR4vqLUMWXxW9gh6D6L8pMSawimctcNZ5pGwDcEt"),
+    pubkey!("DttWaMuVvTiduZRnguLF7jNxTgiMBZ1hyAumKUiL2KRL"),
+    pubkey!("3AVi9Tg9Uo68tJfuvoKvqKNWKkC5wPdSSdeBnizKZ6jT"),
+];
+
+/// Transfers a tip of `lamports` from `payer` to a tip account. The tip account is picked
+/// from the payer so concurrent bundles of different payers spread their write locks.
+pub fn tip_instruction(payer: &Pubkey, lamports: u64) -> Instruction {
+    let tip_account = TIP_ACCOUNTS[payer.to_bytes()[0] as usize % TIP_ACCOUNTS.len()];
+    system_instruction::transfer(payer, &tip_account, lamports)
+}
+
+/// Signs a transaction executing `instructions` then tipping `tip_lamports`, ready to be
+/// sent as a single transaction bundle
+pub fn bundle_transaction(
+    instructions: &[Instruction],
+    tip_lamports: u64,
+    signer: &Keypair,
+    recent_blockhash: Hash,
+) -> Result<VersionedTransaction> {
+    let mut instructions = instructions.to_vec();
+    instructions.push(tip_instruction(&signer.pubkey(), tip_lamports));
+    Ok(VersionedTransaction::try_new(
+        VersionedMessage::V0(v0::Message::try_compile(
+            &signer.pubkey(),
+            &instructions,
+            &[],
+            recent_blockhash,
+        )?),
+        &[signer],
+    )?)
+}
+
+/// Signs the swap of `swap_params` with a tip as a single transaction bundle.
+///
+/// `swap_params.out_amount` is the minimum output, the bundle fails as a whole below it.
+pub fn swap_bundle(
+    jupiter_rarefish: &JupiterRarefish,
+    swap_params: &SwapParams,
+    tip_lamports: u64,
+    signer: &Keypair,
+    recent_blockhash: Hash,
+) -> Result<Vec<VersionedTransaction>> {
+    let swap = jupiter_rarefish.swap_instruction(swap_params);
+    Ok(vec![bundle_transaction(
+        &[swap],
+        tip_lamports,
+        signer,
+        recent_blockhash,
+    )?])
+}
+
+/// Client of the block engine bundles API
+pub struct JitoClient {
+    url: String,
+    http: reqwest::blocking::Client,
+}
+
+impl Default for JitoClient {
+    fn default() -> Self {
+        Self::new(DEFAULT_BLOCK_ENGINE_URL.to_string())
+    }
+}
+
+impl JitoClient {
+    pub fn new(url: String) -> Self {
+        Self {
+            url,
+            http: reqwest::blocking::Client::new(),
+        }
+    }
+
+    /// Submits the transactions as a bundle, returning its id
+    pub fn send_bundle(&self, transactions: &[VersionedTransaction]) -> Result<String> {
+        if transactions.is_empty() || transactions.len() > MAX_BUNDLE_LEN {
+            return Err(anyhow!(
+                "A bundle holds 1 to {} transactions, got {}",
+                MAX_BUNDLE_LEN,
+                transactions.len()
+            ));
+        }
+        let encoded = transactions
+            .iter()
+            .map(|transaction| Ok(bs58::encode(bincode::serialize(transaction)?).into_string()))
+            .collect::<Result<Vec<String>>>()?;
+        let result = self.call("sendBundle", json!([encoded]))?;
+        result
+            .as_str()
+            .map(str::to_string)
+            .ok_or_else(|| anyhow!("Unexpected sendBundle result {}", result))
+    }
+
+    /// Status of landed bundles, `None` for bundles that have not landed (yet)
+    pub fn bundle_statuses(&self, bundle_ids: &[String]) -> Result<Vec<Option<BundleStatus>>> {
+        let result = self.call("getBundleStatuses", json!([bundle_ids]))?;
+        let statuses = result
+            .get("value")
+            .and_then(Value::as_array)
+            .ok_or_else(|| anyhow!("Unexpected getBundleStatuses result {}", result))?;
+        Ok(statuses
+            .iter()
+            .map(|status| serde_json::from_value(status.clone()).ok())
+            .collect())
+    }
+
+    fn call(&self, method: &str, params: Value) -> Result<Value> {
+        let response: Value = self
+            .http
+            .post(&self.url)
+            .json(&json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "method": method,
+                "params": params,
+            }))
+            .send()?
+            .json()?;
+        if let Some(error) = response.get("error") {
+            return Err(anyhow!("Block engine {} failed: {}", method, error));
+        }
+        response
+            .get("result")
+            .cloned()
+            .ok_or_else(|| anyhow!("Block engine {} returned no result", method))
+    }
+}
+
+/// Status of a landed bundle
+#[derive(Clone, Debug, PartialEq, Eq, serde::Deserialize)]
+pub struct BundleStatus {
+    pub bundle_id: String,
+    /// Signatures of the bundle's transactions, base58 encoded
+    pub transactions: Vec<String>,
+    pub slot: u64,
+    pub confirmation_status: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_sdk::hash::Hash;
+    use solana_sdk::signature::Keypair;
+    use solana_sdk::signer::Signer;
+    use solana_sdk::system_program;
+
+    use super::{bundle_transaction, tip_instruction, TIP_ACCOUNTS};
+
+    #[test]
+    fn test_bundle_transaction_tips_last() {
+        let signer = Keypair::new();
+        let tip = tip_instruction(&signer.pubkey(), 10_000);
+        assert_eq!(tip.program_id, system_program::id());
+        assert!(TIP_ACCOUNTS.contains(&tip.accounts[1].pubkey));
+
+        let transaction = bundle_transaction(&[], 10_000, &signer, Hash::default()).unwrap();
+        let message = &transaction.message;
+        let instruction = message.instructions().last().unwrap();
+        assert_eq!(
+            message.static_account_keys()[instruction.program_id_index as usize],
+            system_program::id()
+        );
+        assert!(transaction.verify_with_results().iter().all(|ok| *ok));
+    }
+}
@@ -0,0 +1,123 @@
+//! Versions of the `SwapPool` account layout, so a program upgrade changing the layout is
+//! reported instead of being read with the wrong offsets.
+
+use std::sync::OnceLock;
+
+use anchor_lang::{AccountSerialize, AnchorDeserialize, Discriminator};
+use anyhow::{anyhow, Result};
+use hyperplane::state::SwapPool;
+use solana_sdk::pubkey::Pubkey;
+
+/// Upper bound of the size of a blank `SwapPool`, only used to measure the current layout
+const MAX_POOL_LEN: usize = 10_240;
+/// The token program fields missing from [`PoolLayout::V0`]
+const TOKEN_PROGRAM_FIELDS_LEN: usize = 2 * 32;
+
+/// Layouts of the `SwapPool` account the adapter reads, each converted to the current
+/// `SwapPool` of the hyperplane dependency
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PoolLayout {
+    /// Pools created before token-2022 support, without the `token_a_program` and
+    /// `token_b_program` fields. Read with both unset, that is the spl-token program.
+    V0,
+    /// The layout of the hyperplane release this crate is built against
+    V1,
+}
+
+impl PoolLayout {
+    pub const CURRENT: Self = PoolLayout::V1;
+
+    /// Size of the account data of the layout, discriminator included
+    pub fn data_len(self) -> usize {
+        let current = CurrentLayout::get();
+        match self {
+            PoolLayout::V0 => current.len - TOKEN_PROGRAM_FIELDS_LEN,
+            PoolLayout::V1 => current.len,
+        }
+    }
+
+    /// Identifies the layout of the pool account data by its size. Trailing zero bytes are
+    /// allowed after the current layout, any other data past the known fields means a newer
+    /// layout.
+    pub fn detect(data: &[u8]) -> Result<Self> {
+        if data.get(..8) != Some(&SwapPool::DISCRIMINATOR[..]) {
+            return Err(anyhow!("Account does not have the SwapPool discriminator"));
+        }
+        let current = CurrentLayout::get();
+        if data.len() >= current.len {
+            if data[current.len..].iter().any(|byte| *byte != 0) {
+                return Err(anyhow!(
+                    "Unknown SwapPool layout of {} bytes, {:?} has {} bytes",
+                    data.len(),
+                    Self::CURRENT,
+                    current.len
+                ));
+            }
+            Ok(PoolLayout::V1)
+        } else if data.len() == PoolLayout::V0.data_len() {
+            Ok(PoolLayout::V0)
+        } else {
+            Err(anyhow!("Unknown SwapPool layout of {} bytes", data.len()))
+        }
+    }
+
+    /// Reads the pool account data in whichever known layout it is stored
+    pub(crate) fn parse(data: &[u8]) -> Result<(Self, SwapPool)> {
+        let layout = Self::detect(data)?;
+        let pool = match layout {
+            PoolLayout::V0 => {
+                // The current layout with the token program fields put back, unset
+                let current = CurrentLayout::get();
+                let mut upgraded = Vec::with_capacity(current.len);
+                let mut copied = 0;
+                for (inserted, offset) in current.token_program_offsets.into_iter().enumerate() {
+                    let end = offset - inserted * 32;
+                    upgraded.extend_from_slice(&data[copied..end]);
+                    upgraded.extend_from_slice(&[0; 32]);
+                    copied = end;
+                }
+                upgraded.extend_from_slice(&data[copied..]);
+                deserialize(&upgraded[8..])?
+            }
+            PoolLayout::V1 => deserialize(&data[8..CurrentLayout::get().len])?,
+        };
+        Ok((layout, pool))
+    }
+}
+
+fn deserialize(mut data: &[u8]) -> Result<SwapPool> {
+    SwapPool::deserialize(&mut data).map_err(|e| anyhow!("Invalid SwapPool: {}", e))
+}
+
+/// Size and token program field offsets of the current layout, measured once on a blank pool
+/// so they follow the hyperplane dependency
+struct CurrentLayout {
+    len: usize,
+    /// Offsets of `token_a_program` and `token_b_program` in the account data, ascending
+    token_program_offsets: [usize; 2],
+}
+
+impl CurrentLayout {
+    fn get() -> &'static Self {
+        static CURRENT_LAYOUT: OnceLock<CurrentLayout> = OnceLock::new();
+        CURRENT_LAYOUT.get_or_init(|| {
+            let mut pool = deserialize(&vec![0; MAX_POOL_LEN]).expect("A blank SwapPool is valid");
+            let markers = [[1; 32], [2; 32]];
+            pool.token_a_program = Pubkey::new_from_array(markers[0]);
+            pool.token_b_program = Pubkey::new_from_array(markers[1]);
+            let mut data = vec![];
+            pool.try_serialize(&mut data)
+                .expect("A blank SwapPool serializes");
+            let mut token_program_offsets = markers.map(|marker| {
+                data.windows(32)
+                    .position(|window| window == marker)
+                    .expect("The token program fields are serialized")
+            });
+            token_program_offsets.sort_unstable();
+            CurrentLayout {
+                len: data.len(),
+                token_program_offsets,
+            }
+        })
+    }
+}
@@ -0,0 +1,194 @@
+//! Loading the list of pools from a hosted JSON manifest, so new pools are onboarded by
+//! updating the manifest instead of releasing the crate.
+//!
+//! The manifest is not trusted: every entry is checked against the chain before its pool is
+//! added, and entries failing the checks are reported instead of failing the whole load.
+//! ```json
+//! {
+//!     "pools": [
+//!         {
+//!             "address": "<base58>",
+//!             "tokenAMint": "<base58, optional>",
+//!             "tokenBMint": "<base58, optional>",
+//!             "params": { "labelSuffix": "SOL-USDC" }
+//!         }
+//!     ]
+//! }
+//! ```
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use solana_client::rpc_client::RpcClient;
+use solana_sdk::pubkey::Pubkey;
+
+use crate::amm::{Amm, KeyedAccount};
+use crate::registry::{fetch_accounts, MarketRegistry};
+use crate::JupiterRarefish;
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PoolManifest {
+    pub pools: Vec<ManifestEntry>,
+}
+
+/// A pool of the manifest. Addresses are kept as strings so a malformed one rejects its
+/// entry rather than the manifest.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ManifestEntry {
+    pub address: String,
+    /// Expected token A mint of the pool, checked on-chain when set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token_a_mint: Option<String>,
+    /// Expected token B mint of the pool, checked on-chain when set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token_b_mint: Option<String>,
+    /// The `PoolParams` of the pool
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub params: Option<Value>,
+}
+
+/// An entry of the manifest left out of the registry
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RejectedEntry {
+    pub address: String,
+    pub reason: String,
+}
+
+/// The registry of the valid entries of a manifest, with the rejected ones
+#[derive(Debug)]
+pub struct ManifestRegistry {
+    pub registry: MarketRegistry,
+    pub rejected: Vec<RejectedEntry>,
+}
+
+impl PoolManifest {
+    /// Downloads the manifest at `url`
+    pub fn fetch(url: &str) -> Result<Self> {
+        let response = reqwest::blocking::get(url)
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| anyhow!("Failed to fetch the pool manifest {}: {}", url, e))?;
+        Self::from_json(&response.bytes()?)
+    }
+
+    pub fn from_json(json: &[u8]) -> Result<Self> {
+        serde_json::from_slice(json).map_err(|e| anyhow!("Invalid pool manifest: {}", e))
+    }
+
+    /// Builds a ready-to-quote registry of the entries whose pool exists on-chain, is a
+    /// Rarefish pool owned by the expected program, trades the declared mints and has its
+    /// vaults
+    pub fn load_registry(&self, rpc: &RpcClient) -> Result<ManifestRegistry> {
+        let mut rejected = Vec::new();
+        let mut entries = Vec::with_capacity(self.pools.len());
+        for entry in &self.pools {
+            match entry.address.parse::<Pubkey>() {
+                Ok(key) => entries.push((key, entry)),
+                Err(_) => rejected.push(entry.rejected("invalid address".to_string())),
+            }
+        }
+        let keys: Vec<_> = entries.iter().map(|(key, _)| *key).collect();
+        let accounts_map = fetch_accounts(rpc, &keys)?;
+
+        let mut markets = Vec::with_capacity(entries.len());
+        for (key, entry) in entries {
+            let Some(account) = accounts_map.get(&key) else {
+                rejected.push(entry.rejected("account not found".to_string()));
+                continue;
+            };
+            let market = JupiterRarefish::new_from_keyed_account(&KeyedAccount {
+                key,
+                account: account.clone(),
+                params: entry.params.clone(),
+            })
+            .and_then(|market| entry.check_mints(&market).map(|()| market));
+            match market {
+                Ok(market) => markets.push((entry, market)),
+                Err(e) => rejected.push(entry.rejected(e.to_string())),
+            }
+        }
+
+        let keys: Vec<_> = markets
+            .iter()
+            .flat_map(|(_, market)| market.accounts_to_update())
+            .collect();
+        let accounts_map = fetch_accounts(rpc, &keys)?;
+        let mut registry = MarketRegistry::new();
+        for (entry, mut market) in markets {
+            let vaults = [market.pool().token_a_vault, market.pool().token_b_vault];
+            if let Some(missing) = vaults.iter().find(|key| !accounts_map.contains_key(key)) {
+                rejected.push(entry.rejected(format!("vault {} not found", missing)));
+                continue;
+            }
+            match market.update(&accounts_map) {
+                Ok(()) => registry.insert(market),
+                Err(e) => rejected.push(entry.rejected(e.to_string())),
+            }
+        }
+        Ok(ManifestRegistry { registry, rejected })
+    }
+}
+
+impl ManifestEntry {
+    fn rejected(&self, reason: String) -> RejectedEntry {
+        RejectedEntry {
+            address: self.address.clone(),
+            reason,
+        }
+    }
+
+    fn check_mints(&self, market: &JupiterRarefish) -> Result<()> {
+        let [token_a_mint, token_b_mint] = market.reserve_mints();
+        for (expected, mint, token) in [
+            (&self.token_a_mint, token_a_mint, "A"),
+            (&self.token_b_mint, token_b_mint, "B"),
+        ] {
+            let Some(expected) = expected else {
+                continue;
+            };
+            if *expected != mint.to_string() {
+                return Err(anyhow!(
+                    "token {} mint is {}, the manifest expects {}",
+                    token,
+                    mint,
+                    expected
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::{ManifestEntry, PoolManifest};
+
+    #[test]
+    fn test_pool_manifest() {
+        let json = json!({
+            "pools": [
+                {
+                    "address": "3uqKSr5gZzZSJXgrdikPeWGp1SnEqEayFABwzDQ3vRWe",
+                    "tokenAMint": "So11111111111111111111111111111111111111112",
+                    "params": { "labelSuffix": "SOL-USDC" }
+                },
+                { "address": "not a pubkey" }
+            ]
+        });
+        let manifest = PoolManifest::from_json(json.to_string().as_bytes()).unwrap();
+        assert_eq!(
+            manifest.pools[1],
+            ManifestEntry {
+                address: "not a pubkey".to_string(),
+                token_a_mint: None,
+                token_b_mint: None,
+                params: None,
+            }
+        );
+        assert_eq!(serde_json::to_value(&manifest).unwrap(), json);
+        assert!(PoolManifest::from_json(b"{}").is_err());
+    }
+}
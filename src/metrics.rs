@@ -0,0 +1,122 @@
+//! Prometheus metrics for the registry and the services built on it. The registry records
+//! them as it updates and quotes, services expose [`Metrics::encode`] on their own endpoint.
+
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use anyhow::Result;
+use prometheus::{
+    Encoder, Histogram, HistogramOpts, IntCounterVec, IntGaugeVec, Opts, Registry, TextEncoder,
+};
+use solana_sdk::pubkey::Pubkey;
+
+use crate::JupiterRarefish;
+
+pub struct Metrics {
+    registry: Registry,
+    quotes: IntCounterVec,
+    quote_latency: Histogram,
+    update_latency: Histogram,
+    stale_pools: IntCounterVec,
+    reserves: IntGaugeVec,
+}
+
+static METRICS: OnceLock<Metrics> = OnceLock::new();
+
+/// The process wide metrics, registered on first use
+pub fn metrics() -> &'static Metrics {
+    METRICS.get_or_init(|| Metrics::new(Registry::new()).expect("Metrics are valid"))
+}
+
+impl Metrics {
+    /// Creates the metrics and registers them with `registry`
+    pub fn new(registry: Registry) -> Result<Self> {
+        let quotes = IntCounterVec::new(
+            Opts::new("rarefish_quotes_total", "Quotes served, by pool and result"),
+            &["pool", "result"],
+        )?;
+        let quote_latency = Histogram::with_opts(
+            HistogramOpts::new(
+                "rarefish_quote_latency_seconds",
+                "Latency of a single quote",
+            )
+            .buckets(prometheus::exponential_buckets(1e-7, 4.0, 12)?),
+        )?;
+        let update_latency = Histogram::with_opts(
+            HistogramOpts::new(
+                "rarefish_update_latency_seconds",
+                "Latency of updating a single pool",
+            )
+            .buckets(prometheus::exponential_buckets(1e-7, 4.0, 12)?),
+        )?;
+        let stale_pools = IntCounterVec::new(
+            Opts::new(
+                "rarefish_stale_pool_total",
+                "Pools that failed to update or were quoted before their vaults were loaded",
+            ),
+            &["pool"],
+        )?;
+        let reserves = IntGaugeVec::new(
+            Opts::new(
+                "rarefish_pool_reserves",
+                "Vault balance of each pool, in raw units",
+            ),
+            &["pool", "mint"],
+        )?;
+        registry.register(Box::new(quotes.clone()))?;
+        registry.register(Box::new(quote_latency.clone()))?;
+        registry.register(Box::new(update_latency.clone()))?;
+        registry.register(Box::new(stale_pools.clone()))?;
+        registry.register(Box::new(reserves.clone()))?;
+        Ok(Self {
+            registry,
+            quotes,
+            quote_latency,
+            update_latency,
+            stale_pools,
+            reserves,
+        })
+    }
+
+    pub fn observe_quote(&self, pool: &Pubkey, latency: Duration, ok: bool) {
+        let result = if ok { "ok" } else { "error" };
+        self.quotes
+            .with_label_values(&[&pool.to_string(), result])
+            .inc();
+        self.quote_latency.observe(latency.as_secs_f64());
+    }
+
+    /// Records an update of `market`, refreshing its reserve gauges when it succeeded
+    pub fn observe_update(&self, market: &JupiterRarefish, latency: Duration, ok: bool) {
+        self.update_latency.observe(latency.as_secs_f64());
+        let pool = market.market_key.to_string();
+        if !ok {
+            self.stale_pools.with_label_values(&[&pool]).inc();
+            return;
+        }
+        if let Some((token_a_amount, token_b_amount)) = market.reserves() {
+            let [token_a_mint, token_b_mint] = market.reserve_mints();
+            for (mint, amount) in [
+                (token_a_mint, token_a_amount),
+                (token_b_mint, token_b_amount),
+            ] {
+                self.reserves
+                    .with_label_values(&[&pool, &mint.to_string()])
+                    .set(i64::try_from(amount).unwrap_or(i64::MAX));
+            }
+        }
+    }
+
+    pub fn record_stale(&self, pool: &Pubkey) {
+        self.stale_pools
+            .with_label_values(&[&pool.to_string()])
+            .inc();
+    }
+
+    /// All metrics in the Prometheus text format
+    pub fn encode(&self) -> Result<String> {
+        let mut buffer = vec![];
+        TextEncoder::new().encode(&self.registry.gather(), &mut buffer)?;
+        Ok(String::from_utf8(buffer)?)
+    }
+}
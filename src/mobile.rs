@@ -0,0 +1,196 @@
+//! UniFFI bindings for Kotlin and Swift, so mobile wallets can quote pools and build swap
+//! instructions on-device from accounts they fetched themselves.
+
+use std::fmt;
+use std::sync::{Arc, RwLock};
+
+use anchor_lang::InstructionData;
+use solana_sdk::account::Account;
+use solana_sdk::pubkey::Pubkey;
+
+use crate::amm::{swap_params, Amm, KeyedAccount, QuoteParams, SwapMode};
+use crate::JupiterRarefish;
+
+#[derive(Debug, uniffi::Error)]
+pub enum RarefishError {
+    InvalidPubkey { value: String },
+    InvalidAccount { reason: String },
+    NotReady,
+    Quote { reason: String },
+}
+
+impl fmt::Display for RarefishError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RarefishError::InvalidPubkey { value } => write!(f, "Invalid pubkey {}", value),
+            RarefishError::InvalidAccount { reason } => write!(f, "Invalid account: {}", reason),
+            RarefishError::NotReady => write!(f, "The pool vaults have not been updated"),
+            RarefishError::Quote { reason } => write!(f, "Quote failed: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for RarefishError {}
+
+#[derive(uniffi::Record)]
+pub struct RarefishQuote {
+    pub in_amount: u64,
+    pub out_amount: u64,
+    pub fee_amount: u64,
+    pub fee_mint: String,
+}
+
+#[derive(uniffi::Record)]
+pub struct RarefishAccountMeta {
+    pub pubkey: String,
+    pub is_signer: bool,
+    pub is_writable: bool,
+}
+
+#[derive(uniffi::Record)]
+pub struct RarefishInstruction {
+    pub program_id: String,
+    pub accounts: Vec<RarefishAccountMeta>,
+    pub data: Vec<u8>,
+}
+
+#[derive(uniffi::Object)]
+pub struct RarefishPool {
+    inner: RwLock<JupiterRarefish>,
+}
+
+#[uniffi::export]
+impl RarefishPool {
+    /// Creates a pool from its address and raw account data
+    #[uniffi::constructor]
+    pub fn new(pool: String, pool_data: Vec<u8>) -> Result<Arc<Self>, RarefishError> {
+        let keyed_account = KeyedAccount {
+            key: parse_pubkey(&pool)?,
+            account: Account {
+                lamports: 0,
+                data: pool_data,
+                owner: hyperplane::id(),
+                executable: false,
+                rent_epoch: 0,
+            },
+            params: None,
+        };
+        let jupiter_rarefish =
+            JupiterRarefish::new_from_keyed_account(&keyed_account).map_err(|e| {
+                RarefishError::InvalidAccount {
+                    reason: e.to_string(),
+                }
+            })?;
+        Ok(Arc::new(Self {
+            inner: RwLock::new(jupiter_rarefish),
+        }))
+    }
+
+    /// Token A and token B mints
+    pub fn mints(&self) -> Vec<String> {
+        self.inner
+            .read()
+            .unwrap()
+            .reserve_mints()
+            .iter()
+            .map(Pubkey::to_string)
+            .collect()
+    }
+
+    /// Sets the token A and token B vault balances
+    pub fn update_reserves(&self, token_a_amount: u64, token_b_amount: u64) {
+        self.inner
+            .write()
+            .unwrap()
+            .update_reserves(token_a_amount, token_b_amount);
+    }
+
+    /// Quotes an exact-in swap of `amount` of `input_mint`
+    pub fn quote(&self, input_mint: String, amount: u64) -> Result<RarefishQuote, RarefishError> {
+        let jupiter_rarefish = self.inner.read().unwrap();
+        if jupiter_rarefish.reserves().is_none() {
+            return Err(RarefishError::NotReady);
+        }
+        let (input_mint, output_mint) = swap_mints(&jupiter_rarefish, &input_mint)?;
+        let quote = jupiter_rarefish
+            .quote(&QuoteParams {
+                input_mint,
+                output_mint,
+                amount,
+                swap_mode: SwapMode::ExactIn,
+            })
+            .map_err(|e| RarefishError::Quote {
+                reason: e.to_string(),
+            })?;
+        Ok(RarefishQuote {
+            in_amount: quote.in_amount,
+            out_amount: quote.out_amount,
+            fee_amount: quote.fee_amount,
+            fee_mint: quote.fee_mint.to_string(),
+        })
+    }
+
+    /// Builds the swap instruction selling `amount_in` of `input_mint` from `source_token_account`
+    /// into `destination_token_account`, both owned by `owner`
+    pub fn swap_instruction(
+        &self,
+        owner: String,
+        input_mint: String,
+        source_token_account: String,
+        destination_token_account: String,
+        amount_in: u64,
+        minimum_amount_out: u64,
+    ) -> Result<RarefishInstruction, RarefishError> {
+        let jupiter_rarefish = self.inner.read().unwrap();
+        let (source_mint, destination_mint) = swap_mints(&jupiter_rarefish, &input_mint)?;
+        let account_metas = jupiter_rarefish.swap_account_metas(&swap_params(
+            amount_in,
+            minimum_amount_out,
+            source_mint,
+            destination_mint,
+            parse_pubkey(&source_token_account)?,
+            parse_pubkey(&destination_token_account)?,
+            parse_pubkey(&owner)?,
+        ));
+        Ok(RarefishInstruction {
+            program_id: jupiter_rarefish.program_id().to_string(),
+            accounts: account_metas
+                .iter()
+                .map(|meta| RarefishAccountMeta {
+                    pubkey: meta.pubkey.to_string(),
+                    is_signer: meta.is_signer,
+                    is_writable: meta.is_writable,
+                })
+                .collect(),
+            data: hyperplane::instruction::Swap {
+                amount_in,
+                minimum_amount_out,
+            }
+            .data(),
+        })
+    }
+}
+
+/// Input and output mints of a swap selling `input_mint`
+fn swap_mints(
+    jupiter_rarefish: &JupiterRarefish,
+    input_mint: &str,
+) -> Result<(Pubkey, Pubkey), RarefishError> {
+    let input_mint = parse_pubkey(input_mint)?;
+    let [token_a_mint, token_b_mint] = jupiter_rarefish.reserve_mints();
+    if input_mint == token_a_mint {
+        Ok((token_a_mint, token_b_mint))
+    } else if input_mint == token_b_mint {
+        Ok((token_b_mint, token_a_mint))
+    } else {
+        Err(RarefishError::Quote {
+            reason: format!("Mint {} is not part of the pool", input_mint),
+        })
+    }
+}
+
+fn parse_pubkey(value: &str) -> Result<Pubkey, RarefishError> {
+    value.parse().map_err(|_| RarefishError::InvalidPubkey {
+        value: value.to_string(),
+    })
+}
@@ -0,0 +1,222 @@
+//! Periodic comparison of quotes with simulations of the same swaps, to catch quote drift
+//! after program upgrades before users lose money to slippage.
+
+use std::thread;
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use rand::Rng;
+use solana_client::rpc_client::RpcClient;
+use solana_sdk::pubkey::Pubkey;
+
+use crate::amm::Amm;
+use crate::registry::{fetch_accounts, SharedRegistry};
+use crate::simulation::{simulate_swap_unsigned, SimulatedSwap};
+use crate::token::VaultState;
+use crate::{JupiterRarefish, TradeDirection};
+
+/// A simulated swap whose output differs from the quote by more than the tolerance
+#[derive(Clone, Debug)]
+pub struct Divergence {
+    pub pool: Pubkey,
+    pub simulation: SimulatedSwap,
+}
+
+impl Divergence {
+    /// Difference between the realized and quoted output, in basis points of the quote.
+    /// None if the simulation failed.
+    pub fn difference_bps(&self) -> Option<f64> {
+        let difference = self.simulation.difference()?;
+        Some(difference as f64 * 10_000.0 / self.simulation.quote.out_amount.max(1) as f64)
+    }
+}
+
+/// Quotes random sizes on every pool of a registry, simulates the swaps from the associated
+/// token accounts of a wallet and reports the quotes the program does not reproduce.
+///
+/// Sizes are drawn log-uniformly up to a fraction of the source reserve, capped by the
+/// wallet's balance, so the wallet only needs a small balance of each mint to cover the
+/// small sizes. Each pool is re-fetched right before it is checked, swaps landing between the
+/// fetch and the simulation can still cause one-off divergences.
+pub struct QuoteMonitor {
+    rpc: RpcClient,
+    registry: SharedRegistry,
+    owner: Pubkey,
+    payer: Option<Pubkey>,
+    samples: usize,
+    max_reserve_fraction: f64,
+    tolerance: u64,
+    interval: Duration,
+}
+
+impl QuoteMonitor {
+    /// Monitor simulating from the associated token accounts of `owner`. No keypair is
+    /// needed, the simulations skip signature verification.
+    pub fn new(rpc: RpcClient, registry: SharedRegistry, owner: Pubkey) -> Self {
+        Self {
+            rpc,
+            registry,
+            owner,
+            payer: None,
+            samples: 1,
+            max_reserve_fraction: 0.1,
+            tolerance: 0,
+            interval: Duration::from_secs(60),
+        }
+    }
+
+    /// Account paying the simulated transaction fees, when the owner holds no SOL
+    pub fn with_payer(mut self, payer: Pubkey) -> Self {
+        self.payer = Some(payer);
+        self
+    }
+
+    /// Number of sizes simulated per pool and direction in every round
+    pub fn with_samples(mut self, samples: usize) -> Self {
+        self.samples = samples;
+        self
+    }
+
+    /// Largest size drawn, as a fraction of the source reserve
+    pub fn with_max_reserve_fraction(mut self, max_reserve_fraction: f64) -> Self {
+        self.max_reserve_fraction = max_reserve_fraction;
+        self
+    }
+
+    /// Largest difference between the realized and quoted output, in raw units of the
+    /// output, that is not reported
+    pub fn with_tolerance(mut self, tolerance: u64) -> Self {
+        self.tolerance = tolerance;
+        self
+    }
+
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Runs a round of checks over every pool, returning the divergences found
+    pub fn check(&self) -> Result<Vec<Divergence>> {
+        let markets: Vec<JupiterRarefish> =
+            self.registry.read().unwrap().markets().cloned().collect();
+        let mut divergences = vec![];
+        for market in markets {
+            divergences.extend(self.check_market(market)?);
+        }
+        Ok(divergences)
+    }
+
+    /// Runs a round of checks every interval forever, passing the divergences of each round
+    /// to `on_divergence`. Failed rounds are logged and retried at the next interval.
+    pub fn run(self, mut on_divergence: impl FnMut(&Divergence)) {
+        loop {
+            match self.check() {
+                Ok(divergences) => divergences.iter().for_each(&mut on_divergence),
+                Err(e) => log::warn!("Quote monitor round failed: {}", e),
+            }
+            thread::sleep(self.interval);
+        }
+    }
+
+    /// Spawns [`QuoteMonitor::run`] on a background thread
+    pub fn spawn(self, on_divergence: impl FnMut(&Divergence) + Send + 'static) {
+        thread::spawn(move || self.run(on_divergence));
+    }
+
+    fn check_market(&self, mut market: JupiterRarefish) -> Result<Vec<Divergence>> {
+        market.update(&fetch_accounts(&self.rpc, &market.accounts_to_update())?)?;
+        let (token_a_amount, token_b_amount) = market
+            .reserves()
+            .ok_or_else(|| anyhow!("Pool {} vaults not found", market.key()))?;
+        let [token_a_mint, token_b_mint] = market.reserve_mints();
+        let wallet_accounts = [
+            wallet_token_account(&self.owner, &token_a_mint, market.token_a_program()),
+            wallet_token_account(&self.owner, &token_b_mint, market.token_b_program()),
+        ];
+        let wallet_accounts_map = fetch_accounts(&self.rpc, &wallet_accounts)?;
+        let balance = |account: &Pubkey| {
+            wallet_accounts_map
+                .get(account)
+                .and_then(|account| VaultState::parse(&account.data).ok())
+                .map_or(0, |vault| vault.amount)
+        };
+
+        let mut divergences = vec![];
+        for (trade_direction, input_mint, reserve, wallet_account) in [
+            (
+                TradeDirection::AtoB,
+                token_a_mint,
+                token_a_amount,
+                wallet_accounts[0],
+            ),
+            (
+                TradeDirection::BtoA,
+                token_b_mint,
+                token_b_amount,
+                wallet_accounts[1],
+            ),
+        ] {
+            let max_amount =
+                ((reserve as f64 * self.max_reserve_fraction) as u64).min(balance(&wallet_account));
+            for _ in 0..self.samples {
+                let Some(amount) = random_amount(max_amount) else {
+                    break;
+                };
+                // Sizes the adapter refuses to quote are not simulated
+                if market.quote_many(trade_direction, &[amount]).is_err() {
+                    continue;
+                }
+                let simulation = simulate_swap_unsigned(
+                    &self.rpc,
+                    &market,
+                    &self.owner,
+                    self.payer.as_ref(),
+                    &input_mint,
+                    amount,
+                )?;
+                let diverged = simulation.difference().map_or(true, |difference| {
+                    difference.unsigned_abs() > u128::from(self.tolerance)
+                });
+                if diverged {
+                    divergences.push(Divergence {
+                        pool: market.key(),
+                        simulation,
+                    });
+                }
+            }
+        }
+        Ok(divergences)
+    }
+}
+
+fn wallet_token_account(owner: &Pubkey, mint: &Pubkey, token_program: Pubkey) -> Pubkey {
+    anchor_spl::associated_token::get_associated_token_address_with_program_id(
+        owner,
+        mint,
+        &token_program,
+    )
+}
+
+/// Log-uniform amount between 1 and `max_amount`, None if `max_amount` is zero
+fn random_amount(max_amount: u64) -> Option<u64> {
+    if max_amount == 0 {
+        return None;
+    }
+    let exponent = rand::thread_rng().gen_range(0.0..=(max_amount as f64).log10());
+    Some((10_f64.powf(exponent) as u64).clamp(1, max_amount))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::random_amount;
+
+    #[test]
+    fn test_random_amount() {
+        assert_eq!(random_amount(0), None);
+        assert_eq!(random_amount(1), Some(1));
+        for _ in 0..1_000 {
+            let amount = random_amount(1_000_000).unwrap();
+            assert!((1..=1_000_000).contains(&amount));
+        }
+    }
+}
@@ -0,0 +1,166 @@
+//! Per-pool configuration passed in `KeyedAccount.params`, so operators can configure pools
+//! individually without forking the crate.
+
+use std::borrow::Cow;
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use solana_sdk::pubkey::Pubkey;
+
+/// Keys of the JSON params, any other key is ignored with a warning
+const KEYS: [&str; 5] = [
+    "labelSuffix",
+    "programId",
+    "tokenAHostFeesAccount",
+    "tokenBHostFeesAccount",
+    "strict",
+];
+
+/// Overrides read from the JSON `params` of a pool's keyed account, all optional:
+/// ```json
+/// {
+///     "labelSuffix": "SOL-USDC",
+///     "programId": "<base58>",
+///     "tokenAHostFeesAccount": "<base58>",
+///     "tokenBHostFeesAccount": "<base58>",
+///     "strict": true
+/// }
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PoolParams {
+    /// Appended to the "Rarefish" label
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label_suffix: Option<String>,
+    /// The hyperplane deployment owning the pool, defaults to the mainnet program
+    #[serde(default, with = "optional_pubkey", skip_serializing_if = "Option::is_none")]
+    pub program_id: Option<Pubkey>,
+    /// Token A account receiving the host share of the owner fee of A to B swaps
+    #[serde(default, with = "optional_pubkey", skip_serializing_if = "Option::is_none")]
+    pub token_a_host_fees_account: Option<Pubkey>,
+    /// Token B account receiving the host share of the owner fee of B to A swaps
+    #[serde(default, with = "optional_pubkey", skip_serializing_if = "Option::is_none")]
+    pub token_b_host_fees_account: Option<Pubkey>,
+    /// Fails updates missing an account the adapter depends on, instead of keeping the
+    /// previous state, and adds the checks too costly to make by default: the pool addresses
+    /// are those the program derives, the vaults belong to the pool authority and the mints
+    /// to the token programs
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub strict: bool,
+}
+
+impl PoolParams {
+    /// Reads the params, warning about the keys it does not know rather than failing: params
+    /// written for a newer version of the crate still load, and typos are logged
+    pub fn from_value(value: &Value) -> Result<Self> {
+        for key in Self::unknown_keys(value) {
+            log::warn!("Ignoring unknown pool param {}", key);
+        }
+        Self::deserialize(value).map_err(|e| anyhow!("Invalid pool params: {}", e))
+    }
+
+    /// Keys of the JSON params that are not pool params
+    pub fn unknown_keys(value: &Value) -> Vec<&str> {
+        value
+            .as_object()
+            .into_iter()
+            .flat_map(|object| object.keys())
+            .map(String::as_str)
+            .filter(|key| !KEYS.contains(key))
+            .collect()
+    }
+
+    pub fn to_value(&self) -> Value {
+        serde_json::to_value(self).expect("Pool params serialize to JSON")
+    }
+
+    pub(crate) fn label(&self) -> Cow<'static, str> {
+        match &self.label_suffix {
+            Some(label_suffix) => Cow::Owned(format!("Rarefish {}", label_suffix)),
+            None => Cow::Borrowed("Rarefish"),
+        }
+    }
+}
+
+/// Pubkeys as base58 strings
+mod optional_pubkey {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use solana_sdk::pubkey::Pubkey;
+
+    pub fn serialize<S: Serializer>(
+        pubkey: &Option<Pubkey>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        pubkey
+            .map(|pubkey| pubkey.to_string())
+            .serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<Pubkey>, D::Error> {
+        Option::<String>::deserialize(deserializer)?
+            .map(|pubkey| pubkey.parse().map_err(serde::de::Error::custom))
+            .transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use solana_sdk::pubkey::Pubkey;
+
+    use super::{PoolParams, KEYS};
+
+    #[test]
+    fn test_pool_params() {
+        assert_eq!(
+            PoolParams::from_value(&json!({})).unwrap(),
+            PoolParams::default()
+        );
+        assert_eq!(PoolParams::default().label(), "Rarefish");
+
+        let host_fees_account = Pubkey::new_unique();
+        let value = json!({
+            "labelSuffix": "SOL-USDC",
+            "tokenBHostFeesAccount": host_fees_account.to_string(),
+            "strict": true,
+        });
+        let params = PoolParams::from_value(&value).unwrap();
+        assert_eq!(params.label(), "Rarefish SOL-USDC");
+        assert_eq!(params.program_id, None);
+        assert_eq!(params.token_b_host_fees_account, Some(host_fees_account));
+        assert!(params.strict);
+        assert_eq!(params.to_value(), value);
+
+        assert!(PoolParams::from_value(&json!({ "programId": "not a pubkey" })).is_err());
+
+        // Unknown keys are ignored
+        let value = json!({ "labelSufix": "typo", "strict": true });
+        assert_eq!(PoolParams::unknown_keys(&value), vec!["labelSufix"]);
+        let params = PoolParams::from_value(&value).unwrap();
+        assert_eq!(params.label(), "Rarefish");
+        assert!(params.strict);
+
+        let every_param = PoolParams {
+            label_suffix: Some("SOL-USDC".to_string()),
+            program_id: Some(Pubkey::new_unique()),
+            token_a_host_fees_account: Some(Pubkey::new_unique()),
+            token_b_host_fees_account: Some(host_fees_account),
+            strict: true,
+        }
+        .to_value();
+        let mut keys: Vec<&str> = every_param
+            .as_object()
+            .unwrap()
+            .keys()
+            .map(String::as_str)
+            .collect();
+        keys.sort_unstable();
+        let mut known_keys = KEYS.to_vec();
+        known_keys.sort_unstable();
+        assert_eq!(keys, known_keys);
+        assert!(PoolParams::unknown_keys(&every_param).is_empty());
+    }
+}
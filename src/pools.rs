@@ -0,0 +1,74 @@
+//! Canonical mainnet Rarefish markets and their mints.
+
+use solana_sdk::pubkey;
+use solana_sdk::pubkey::Pubkey;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct KnownMint {
+    pub symbol: &'static str,
+    pub mint: Pubkey,
+    pub decimals: u8,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct KnownPool {
+    /// `<token A>/<token B>`
+    pub name: &'static str,
+    /// The `SwapPool` account
+    pub key: Pubkey,
+    pub token_a: KnownMint,
+    pub token_b: KnownMint,
+}
+
+pub const SOL: KnownMint = KnownMint {
+    symbol: "SOL",
+    mint: pubkey!("So11111111111111111111111111111111111111112"),
+    decimals: 9,
+};
+
+pub const USDC: KnownMint = KnownMint {
+    symbol: "USDC",
+    mint: pubkey!("EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v"),
+    decimals: 6,
+};
+
+pub const USDH: KnownMint = KnownMint {
+    symbol: "USDH",
+    mint: pubkey!("USDH1SM1ojwWUga67PGrgFWUHibbjqMvuMaDkRJTgkX"),
+    decimals: 6,
+};
+
+pub const HBB: KnownMint = KnownMint {
+    symbol: "HBB",
+    mint: pubkey!("HBB111SCo9jkCejsZfz8Ec8nH7T6THF8KEKSnvwT6XK6"),
+    decimals: 6,
+};
+
+pub const SOL_USDC: KnownPool = KnownPool {
+    name: "SOL/USDC",
+    key: pubkey!("3uqKSr5gZzZSJXgrdikPeWGp1SnEqEayFABwzDQ3vRWe"),
+    token_a: SOL,
+    token_b: USDC,
+};
+
+pub const USDH_HBB: KnownPool = KnownPool {
+    name: "USDH/HBB",
+    key: pubkey!("HcCyVwmtcYKLQYCgfQPv8LVRxW3XDkbop4WZRShGCvK4"),
+    token_a: USDH,
+    token_b: HBB,
+};
+
+pub const MAINNET_POOLS: &[KnownPool] = &[SOL_USDC, USDH_HBB];
+
+/// The known mainnet pool at `key`
+pub fn find(key: &Pubkey) -> Option<&'static KnownPool> {
+    MAINNET_POOLS.iter().find(|pool| pool.key == *key)
+}
+
+/// The known mainnet pool trading `mint_a` against `mint_b`, in either order
+pub fn find_by_mints(mint_a: &Pubkey, mint_b: &Pubkey) -> Option<&'static KnownPool> {
+    MAINNET_POOLS.iter().find(|pool| {
+        (pool.token_a.mint, pool.token_b.mint) == (*mint_a, *mint_b)
+            || (pool.token_a.mint, pool.token_b.mint) == (*mint_b, *mint_a)
+    })
+}
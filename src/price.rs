@@ -0,0 +1,311 @@
+use anyhow::{anyhow, Result};
+use hyperplane::state::SwapState;
+use rust_decimal::Decimal;
+
+use crate::amm::{Quote, QuoteParams};
+use crate::curve::{stable_invariant, stable_marginal_price, CurveParams};
+use crate::{JupiterRarefish, TradeDirection};
+
+/// Prices realized by a quote, in destination tokens per source token
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ExecutionPrice {
+    /// Price of the trade before fees are deducted from the input
+    pub execution_price: Decimal,
+    /// Output received per unit of input, after fees
+    pub effective_rate: Decimal,
+    /// Marginal price before the trade, before fees
+    pub spot_price: Decimal,
+    /// How much worse the execution price is than the spot price, in basis points
+    pub slippage_bps: Decimal,
+}
+
+impl ExecutionPrice {
+    /// Converts raw token prices into UI prices using the mint decimals
+    pub fn to_ui(&self, source_decimals: u8, destination_decimals: u8) -> Result<Self> {
+        let scale = decimals_scale(source_decimals, destination_decimals)?;
+        Ok(Self {
+            execution_price: self.execution_price * scale,
+            effective_rate: self.effective_rate * scale,
+            spot_price: self.spot_price * scale,
+            slippage_bps: self.slippage_bps,
+        })
+    }
+}
+
+/// A fee as the fraction `numerator / denominator`, zero when the denominator is
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct FeeRate {
+    pub numerator: u64,
+    pub denominator: u64,
+}
+
+impl FeeRate {
+    pub fn rate(&self) -> Decimal {
+        fee_rate(self.numerator, self.denominator)
+    }
+
+    pub fn bps(&self) -> Decimal {
+        self.rate() * Decimal::from(10_000)
+    }
+}
+
+/// Factor converting a raw price into a UI price: 10^(source decimals - destination decimals).
+/// Fails when the decimals are too far apart for a `Decimal`.
+pub(crate) fn decimals_scale(source_decimals: u8, destination_decimals: u8) -> Result<Decimal> {
+    let exponent = source_decimals as i32 - destination_decimals as i32;
+    let scale = if exponent >= 0 {
+        10_i128
+            .checked_pow(exponent.unsigned_abs())
+            .and_then(|power| Decimal::try_from_i128_with_scale(power, 0).ok())
+    } else {
+        Decimal::try_from_i128_with_scale(1, exponent.unsigned_abs()).ok()
+    };
+    scale.ok_or_else(|| {
+        anyhow!(
+            "Cannot scale a price from {} to {} decimals",
+            source_decimals,
+            destination_decimals
+        )
+    })
+}
+
+impl JupiterRarefish {
+    /// Marginal price of the destination token in terms of the source token for an
+    /// infinitesimally small trade, computed from the reserves and curve without quoting.
+    ///
+    /// With `include_fees` the trade and owner fees taken from the input are deducted.
+    pub fn spot_price(
+        &self,
+        trade_direction: TradeDirection,
+        include_fees: bool,
+    ) -> Result<Decimal> {
+        let (token_a_amount, token_b_amount) = self
+            .reserves()
+            .ok_or_else(|| anyhow!("These token accounts should be updated first"))?;
+        self.spot_price_at(
+            token_a_amount,
+            token_b_amount,
+            trade_direction,
+            include_fees,
+        )
+    }
+
+    /// [`JupiterRarefish::spot_price`] for the given vault balances instead of the current ones
+    pub(crate) fn spot_price_at(
+        &self,
+        token_a_amount: u64,
+        token_b_amount: u64,
+        trade_direction: TradeDirection,
+        include_fees: bool,
+    ) -> Result<Decimal> {
+        let (a, b) = (Decimal::from(token_a_amount), Decimal::from(token_b_amount));
+        // Token B received per token A
+        let price_a_to_b = match self.curve_info() {
+            CurveParams::ConstantProduct => checked_div(b, a)?,
+            CurveParams::ConstantPrice { token_b_price } => {
+                checked_div(Decimal::ONE, Decimal::from(token_b_price))?
+            }
+            CurveParams::Offset { token_b_offset } => {
+                checked_div(b + Decimal::from(token_b_offset), a)?
+            }
+            CurveParams::Stable {
+                amp,
+                token_a_factor,
+                token_b_factor,
+            } => {
+                let x = token_a_amount as f64 * token_a_factor as f64;
+                let y = token_b_amount as f64 * token_b_factor as f64;
+                if x == 0.0 || y == 0.0 {
+                    return Err(anyhow!("Pool has no liquidity"));
+                }
+                let normalized_price = stable_marginal_price(amp as f64, x, y);
+                let price = normalized_price * token_a_factor as f64 / token_b_factor as f64;
+                Decimal::try_from(price)?
+            }
+        };
+        let price = match trade_direction {
+            TradeDirection::AtoB => price_a_to_b,
+            TradeDirection::BtoA => checked_div(Decimal::ONE, price_a_to_b)?,
+        };
+        if include_fees {
+            Ok(price * (Decimal::ONE - self.input_fee_rate()))
+        } else {
+            Ok(price)
+        }
+    }
+
+    /// Execution price, effective rate and slippage versus spot of a quote, in raw units
+    pub fn execution_price(
+        &self,
+        quote_params: &QuoteParams,
+        quote: &Quote,
+    ) -> Result<ExecutionPrice> {
+        if quote_params.amount == 0 {
+            return Err(anyhow!("Cannot price a zero amount quote"));
+        }
+        let trade_direction =
+            self.trade_direction(&quote_params.input_mint, &quote_params.output_mint)?;
+        let spot_price = self.spot_price(trade_direction, false)?;
+        let in_amount = Decimal::from(quote_params.amount);
+        let out_amount = Decimal::from(quote.out_amount);
+        let effective_rate = out_amount / in_amount;
+        let in_amount_after_fees = in_amount * (Decimal::ONE - self.input_fee_rate());
+        let execution_price = checked_div(out_amount, in_amount_after_fees)?;
+        let slippage_bps = if spot_price.is_zero() {
+            Decimal::ZERO
+        } else {
+            (Decimal::ONE - execution_price / spot_price) * Decimal::from(10_000)
+        };
+        Ok(ExecutionPrice {
+            execution_price,
+            effective_rate,
+            spot_price,
+            slippage_bps,
+        })
+    }
+
+    /// Amplification coefficient of a stable curve pool, `None` for other curves
+    pub fn amp(&self) -> Option<u64> {
+        match self.curve_info() {
+            CurveParams::Stable { amp, .. } => Some(amp),
+            _ => None,
+        }
+    }
+
+    /// StableSwap invariant `D` of the current reserves of a stable curve pool, in units of
+    /// the balances normalized with the token factors
+    pub fn stable_invariant(&self) -> Result<Decimal> {
+        let CurveParams::Stable {
+            amp,
+            token_a_factor,
+            token_b_factor,
+        } = self.curve_info()
+        else {
+            return Err(anyhow!(
+                "Pool {} is not a stable curve pool",
+                self.market_key
+            ));
+        };
+        let (token_a_amount, token_b_amount) = self
+            .reserves()
+            .ok_or_else(|| anyhow!("These token accounts should be updated first"))?;
+        let x = token_a_amount as f64 * token_a_factor as f64;
+        let y = token_b_amount as f64 * token_b_factor as f64;
+        if x == 0.0 || y == 0.0 {
+            return Err(anyhow!("Pool has no liquidity"));
+        }
+        Ok(Decimal::try_from(stable_invariant(amp as f64, x, y))?)
+    }
+
+    /// Invariant per raw LP token of a stable curve pool. It only grows as fees accrue,
+    /// whatever the balance of the reserves.
+    ///
+    /// The adapter does not track the LP mint, `lp_supply` is its current supply.
+    pub fn virtual_price(&self, lp_supply: u64) -> Result<Decimal> {
+        if lp_supply == 0 {
+            return Err(anyhow!("Pool {} has no LP supply", self.market_key));
+        }
+        Ok(self.stable_invariant()? / Decimal::from(lp_supply))
+    }
+
+    /// Fee taken from the input of swaps, which stays in the pool for liquidity providers
+    pub fn trade_fee(&self) -> FeeRate {
+        let fees = self.pool.fees();
+        FeeRate {
+            numerator: fees.trade_fee_numerator,
+            denominator: fees.trade_fee_denominator,
+        }
+    }
+
+    /// Fee taken from the input of swaps into the fees vault of the pool owner
+    pub fn owner_trade_fee(&self) -> FeeRate {
+        let fees = self.pool.fees();
+        FeeRate {
+            numerator: fees.owner_trade_fee_numerator,
+            denominator: fees.owner_trade_fee_denominator,
+        }
+    }
+
+    /// Fee taken from withdrawals of liquidity, not charged on swaps
+    pub fn owner_withdraw_fee(&self) -> FeeRate {
+        let fees = self.pool.fees();
+        FeeRate {
+            numerator: fees.owner_withdraw_fee_numerator,
+            denominator: fees.owner_withdraw_fee_denominator,
+        }
+    }
+
+    /// Share of the owner trade fee paid to the host fees account of a swap, if any. It
+    /// does not add to the fees paid by the trader.
+    pub fn host_fee(&self) -> FeeRate {
+        let fees = self.pool.fees();
+        FeeRate {
+            numerator: fees.host_fee_numerator,
+            denominator: fees.host_fee_denominator,
+        }
+    }
+
+    /// Total fee charged on the input of swaps, trade and owner fees, in basis points
+    pub fn effective_fee_bps(&self) -> Decimal {
+        self.input_fee_rate() * Decimal::from(10_000)
+    }
+
+    /// Fraction of the input taken by the trade and owner fees
+    fn input_fee_rate(&self) -> Decimal {
+        self.trade_fee().rate() + self.owner_trade_fee().rate()
+    }
+}
+
+pub(crate) fn fee_rate(numerator: u64, denominator: u64) -> Decimal {
+    if denominator == 0 {
+        Decimal::ZERO
+    } else {
+        Decimal::from(numerator) / Decimal::from(denominator)
+    }
+}
+
+fn checked_div(numerator: Decimal, denominator: Decimal) -> Result<Decimal> {
+    numerator
+        .checked_div(denominator)
+        .ok_or_else(|| anyhow!("Pool has no liquidity"))
+}
+
+#[cfg(test)]
+mod tests {
+    use rust_decimal::Decimal;
+
+    use super::{decimals_scale, FeeRate};
+
+    #[test]
+    fn test_decimals_scale() {
+        assert_eq!(decimals_scale(9, 6).unwrap(), Decimal::from(1_000));
+        assert_eq!(decimals_scale(6, 9).unwrap(), Decimal::new(1, 3));
+        assert_eq!(decimals_scale(6, 6).unwrap(), Decimal::ONE);
+        assert_eq!(
+            decimals_scale(19, 0).unwrap(),
+            Decimal::from(10_u64.pow(19))
+        );
+        assert_eq!(
+            decimals_scale(21, 0).unwrap(),
+            Decimal::from(10_u64.pow(19)) * Decimal::ONE_HUNDRED
+        );
+        assert_eq!(decimals_scale(0, 28).unwrap(), Decimal::new(1, 28));
+        assert!(decimals_scale(30, 0).is_err());
+        assert!(decimals_scale(0, 30).is_err());
+        assert!(decimals_scale(255, 0).is_err());
+    }
+
+    #[test]
+    fn test_fee_rate() {
+        let fee = FeeRate {
+            numerator: 25,
+            denominator: 10_000,
+        };
+        assert_eq!(fee.bps(), Decimal::from(25));
+        let fee = FeeRate {
+            numerator: 0,
+            denominator: 0,
+        };
+        assert_eq!(fee.rate(), Decimal::ZERO);
+    }
+}
@@ -0,0 +1,153 @@
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::thread;
+use std::time::Duration;
+
+use anyhow::Result;
+use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
+use axum::extract::State;
+use axum::response::Response;
+use axum::routing::get;
+use axum::Router;
+use rust_decimal::prelude::ToPrimitive;
+use serde::Serialize;
+use solana_client::rpc_client::RpcClient;
+use solana_sdk::pubkey::Pubkey;
+use tokio::sync::broadcast;
+
+use crate::amm::Amm;
+use crate::registry::{fetch_accounts, MarketRegistry, SharedRegistry};
+use crate::TradeDirection;
+
+/// Number of updates buffered for slow websocket clients before they start missing updates
+const CHANNEL_CAPACITY: usize = 1024;
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PriceUpdate {
+    pub pool: String,
+    pub slot: u64,
+    pub reserve_a: u64,
+    pub reserve_b: u64,
+    /// Raw token B received per raw token A at the margin, before fees
+    pub price: f64,
+}
+
+/// Publishes price and reserve updates to websocket subscribers when a pool's reserves change
+#[derive(Clone)]
+pub struct PriceFeedPublisher {
+    sender: broadcast::Sender<PriceUpdate>,
+    last_reserves: HashMap<Pubkey, (u64, u64)>,
+}
+
+impl Default for PriceFeedPublisher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PriceFeedPublisher {
+    pub fn new() -> Self {
+        let (sender, _) = broadcast::channel(CHANNEL_CAPACITY);
+        Self {
+            sender,
+            last_reserves: HashMap::new(),
+        }
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<PriceUpdate> {
+        self.sender.subscribe()
+    }
+
+    /// Publishes an update for every market whose reserves changed since the last call
+    pub fn publish_changes(&mut self, registry: &MarketRegistry, slot: u64) -> usize {
+        let mut published = 0;
+        for market in registry.markets() {
+            let Some(reserves) = market.reserves() else {
+                continue;
+            };
+            if self.last_reserves.insert(market.key(), reserves) == Some(reserves) {
+                continue;
+            }
+            let update = PriceUpdate {
+                pool: market.key().to_string(),
+                slot,
+                reserve_a: reserves.0,
+                reserve_b: reserves.1,
+                price: market
+                    .spot_price(TradeDirection::AtoB, false)
+                    .ok()
+                    .and_then(|price| price.to_f64())
+                    .unwrap_or_default(),
+            };
+            // Sending only fails when there are no subscribers
+            let _ = self.sender.send(update);
+            published += 1;
+        }
+        published
+    }
+}
+
+/// Refreshes the registry every `interval` and publishes the pools whose reserves changed
+pub fn spawn_publisher(
+    registry: SharedRegistry,
+    mut publisher: PriceFeedPublisher,
+    rpc: RpcClient,
+    interval: Duration,
+) {
+    thread::spawn(move || loop {
+        let fetch = || -> Result<_> {
+            let keys = registry.read().unwrap().get_accounts_to_update();
+            Ok((rpc.get_slot()?, fetch_accounts(&rpc, &keys)?))
+        };
+        match fetch() {
+            Ok((slot, accounts_map)) => {
+                let mut registry = registry.write().unwrap();
+                // The markets that failed keep their state, the others are still published
+                if let Err(e) = registry.update(&accounts_map) {
+                    log::warn!("Failed to update markets: {}", e);
+                }
+                publisher.publish_changes(&registry, slot);
+            }
+            Err(e) => log::warn!("Failed to fetch market accounts: {}", e),
+        }
+        thread::sleep(interval);
+    });
+}
+
+async fn subscribe(
+    State(sender): State<broadcast::Sender<PriceUpdate>>,
+    ws: WebSocketUpgrade,
+) -> Response {
+    let receiver = sender.subscribe();
+    ws.on_upgrade(move |socket| forward_updates(socket, receiver))
+}
+
+async fn forward_updates(mut socket: WebSocket, mut receiver: broadcast::Receiver<PriceUpdate>) {
+    loop {
+        let update = match receiver.recv().await {
+            Ok(update) => update,
+            Err(broadcast::error::RecvError::Lagged(_)) => continue,
+            Err(broadcast::error::RecvError::Closed) => return,
+        };
+        let Ok(text) = serde_json::to_string(&update) else {
+            continue;
+        };
+        if socket.send(Message::Text(text)).await.is_err() {
+            return;
+        }
+    }
+}
+
+pub fn router(publisher: &PriceFeedPublisher) -> Router {
+    Router::new()
+        .route("/ws", get(subscribe))
+        .with_state(publisher.sender.clone())
+}
+
+/// Serves the price feed on `/ws` until the server fails
+pub async fn serve(addr: SocketAddr, publisher: &PriceFeedPublisher) -> Result<()> {
+    let listener = tokio::net::TcpListener::bind(addr).await?;
+    axum::serve(listener, router(publisher)).await?;
+    Ok(())
+}
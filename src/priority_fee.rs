@@ -0,0 +1,116 @@
+//! Compute-unit price recommendations from the fees recently paid to write-lock a pool's
+//! accounts, and the ComputeBudget instructions paying them.
+
+use anyhow::Result;
+use solana_client::rpc_client::RpcClient;
+use solana_sdk::compute_budget::ComputeBudgetInstruction;
+use solana_sdk::instruction::Instruction;
+
+use crate::JupiterRarefish;
+
+/// Compute units requested for a transaction with a single swap, with headroom for the
+/// stable curve and token-2022 transfers
+pub const DEFAULT_SWAP_COMPUTE_UNIT_LIMIT: u32 = 200_000;
+
+/// Percentile of the recent fees recommended by default
+pub const DEFAULT_PERCENTILE: u8 = 75;
+
+/// Compute budget of a transaction, paid on top of the base signature fee
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ComputeBudget {
+    pub compute_unit_limit: u32,
+    /// Price of a compute unit, in micro-lamports
+    pub compute_unit_price: u64,
+}
+
+impl Default for ComputeBudget {
+    fn default() -> Self {
+        Self {
+            compute_unit_limit: DEFAULT_SWAP_COMPUTE_UNIT_LIMIT,
+            compute_unit_price: 0,
+        }
+    }
+}
+
+impl ComputeBudget {
+    /// The priority fee paid, in lamports, if the whole limit is requested
+    pub fn priority_fee(&self) -> u64 {
+        ((u128::from(self.compute_unit_limit) * u128::from(self.compute_unit_price) + 999_999)
+            / 1_000_000)
+            .try_into()
+            .unwrap_or(u64::MAX)
+    }
+
+    /// The ComputeBudget instructions setting this budget, to put first in the transaction.
+    /// The price instruction is omitted when the price is zero.
+    pub fn instructions(&self) -> Vec<Instruction> {
+        let mut instructions = vec![ComputeBudgetInstruction::set_compute_unit_limit(
+            self.compute_unit_limit,
+        )];
+        if self.compute_unit_price > 0 {
+            instructions.push(ComputeBudgetInstruction::set_compute_unit_price(
+                self.compute_unit_price,
+            ));
+        }
+        instructions
+    }
+}
+
+/// Recommends a compute-unit price, in micro-lamports, from the prioritization fees paid in
+/// the recent slots to write-lock the accounts of the pool.
+///
+/// `percentile` of the per-slot fees is returned, 100 for the highest fee seen. Slots where
+/// no prioritized transaction locked the accounts count as zero, so quiet pools get a low
+/// price.
+pub fn estimate_compute_unit_price(
+    rpc: &RpcClient,
+    jupiter_rarefish: &JupiterRarefish,
+    percentile: u8,
+) -> Result<u64> {
+    let fees: Vec<u64> = rpc
+        .get_recent_prioritization_fees(&jupiter_rarefish.write_locked_accounts())?
+        .iter()
+        .map(|fee| fee.prioritization_fee)
+        .collect();
+    Ok(fee_percentile(fees, percentile))
+}
+
+/// The fee at `percentile` of `fees` (nearest rank), 0 for no fees
+fn fee_percentile(mut fees: Vec<u64>, percentile: u8) -> u64 {
+    if fees.is_empty() {
+        return 0;
+    }
+    fees.sort_unstable();
+    let rank = (fees.len() * usize::from(percentile.min(100)) + 99) / 100;
+    fees[rank.saturating_sub(1)]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{fee_percentile, ComputeBudget};
+
+    #[test]
+    fn test_fee_percentile() {
+        assert_eq!(fee_percentile(vec![], 75), 0);
+        let fees = vec![0, 500, 0, 10_000, 2_000, 0, 1_000, 0];
+        assert_eq!(fee_percentile(fees.clone(), 0), 0);
+        assert_eq!(fee_percentile(fees.clone(), 50), 0);
+        assert_eq!(fee_percentile(fees.clone(), 75), 1_000);
+        assert_eq!(fee_percentile(fees.clone(), 100), 10_000);
+        assert_eq!(fee_percentile(fees, 255), 10_000);
+    }
+
+    #[test]
+    fn test_compute_budget() {
+        let budget = ComputeBudget::default();
+        assert_eq!(budget.priority_fee(), 0);
+        assert_eq!(budget.instructions().len(), 1);
+
+        let budget = ComputeBudget {
+            compute_unit_limit: 200_000,
+            compute_unit_price: 1_500,
+        };
+        assert_eq!(budget.priority_fee(), 300);
+        assert_eq!(budget.instructions().len(), 2);
+    }
+}
@@ -0,0 +1,116 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use futures::StreamExt;
+use solana_account_decoder::UiAccountEncoding;
+use solana_client::nonblocking::pubsub_client::PubsubClient;
+use solana_client::rpc_client::RpcClient;
+use solana_client::rpc_config::RpcAccountInfoConfig;
+use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::pubkey::Pubkey;
+
+use crate::amm::AccountMap;
+use crate::registry::{fetch_accounts, SharedRegistry};
+
+/// Keeps a registry up to date from websocket account subscriptions, for operators
+/// without access to a geyser endpoint
+pub struct PubsubUpdater {
+    ws_url: String,
+    commitment: CommitmentConfig,
+    reconnect_delay: Duration,
+    registry: SharedRegistry,
+    rpc: Option<Arc<RpcClient>>,
+    accounts_map: AccountMap,
+}
+
+impl PubsubUpdater {
+    pub fn new(ws_url: String, registry: SharedRegistry) -> Self {
+        Self {
+            ws_url,
+            commitment: CommitmentConfig::processed(),
+            reconnect_delay: Duration::from_secs(1),
+            registry,
+            rpc: None,
+            accounts_map: AccountMap::new(),
+        }
+    }
+
+    pub fn with_commitment(mut self, commitment: CommitmentConfig) -> Self {
+        self.commitment = commitment;
+        self
+    }
+
+    pub fn with_reconnect_delay(mut self, reconnect_delay: Duration) -> Self {
+        self.reconnect_delay = reconnect_delay;
+        self
+    }
+
+    /// Re-fetches every account after each (re)connection so updates missed while
+    /// disconnected are not lost
+    pub fn with_rpc(mut self, rpc: Arc<RpcClient>) -> Self {
+        self.rpc = Some(rpc);
+        self
+    }
+
+    /// Applies account notifications forever, reconnecting and resubscribing whenever the
+    /// websocket connection drops
+    pub async fn run(mut self) -> Result<()> {
+        loop {
+            if let Err(e) = self.subscribe_until_closed().await {
+                log::warn!("Account subscriptions failed: {}", e);
+            }
+            tokio::time::sleep(self.reconnect_delay).await;
+        }
+    }
+
+    async fn subscribe_until_closed(&mut self) -> Result<()> {
+        let keys = self.registry.read().unwrap().get_accounts_to_update();
+        let client = PubsubClient::new(&self.ws_url).await?;
+        let config = RpcAccountInfoConfig {
+            encoding: Some(UiAccountEncoding::Base64),
+            commitment: Some(self.commitment),
+            ..RpcAccountInfoConfig::default()
+        };
+        let mut streams = Vec::with_capacity(keys.len());
+        for key in keys.iter().copied() {
+            let (stream, _unsubscribe) =
+                client.account_subscribe(&key, Some(config.clone())).await?;
+            streams.push(stream.map(move |response| (key, response)));
+        }
+        self.resync(keys).await?;
+
+        let mut notifications = futures::stream::select_all(streams);
+        while let Some((key, response)) = notifications.next().await {
+            let Some(account) = response.value.decode() else {
+                continue;
+            };
+            self.accounts_map.insert(key, account);
+            if let Err(e) = self
+                .registry
+                .write()
+                .unwrap()
+                .update_dependents(&key, &self.accounts_map)
+            {
+                log::warn!("Failed to update markets for {}: {}", key, e);
+            }
+        }
+        Err(anyhow!("Websocket connection to {} closed", self.ws_url))
+    }
+
+    /// Fetches the current state of `keys` once subscribed, so nothing between the last
+    /// notification and the new subscriptions is missed
+    async fn resync(&mut self, keys: Vec<Pubkey>) -> Result<()> {
+        let Some(rpc) = self.rpc.clone() else {
+            return Ok(());
+        };
+        let accounts_map =
+            tokio::task::spawn_blocking(move || fetch_accounts(&rpc, &keys)).await??;
+        self.accounts_map.extend(accounts_map);
+        // A market failing to update must not stop the subscriptions of the others
+        if let Err(e) = self.registry.write().unwrap().update(&self.accounts_map) {
+            log::warn!("Failed to update markets: {}", e);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,257 @@
+//! Caching of registry quotes, to absorb repeated identical requests when serving quotes
+//! without returning quotes of a pool state that has since been updated.
+
+use std::collections::HashMap;
+
+use anyhow::{anyhow, Result};
+use solana_sdk::pubkey::Pubkey;
+
+use crate::amm::Quote;
+use crate::registry::MarketRegistry;
+use crate::{StateVersion, TradeDirection};
+
+/// Default maximum number of cached quotes
+const DEFAULT_CAPACITY: usize = 100_000;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+struct QuoteKey {
+    pool: Pubkey,
+    /// Whether the quote sells token A
+    a_to_b: bool,
+    amount: u64,
+}
+
+#[derive(Clone, Debug)]
+struct CachedQuote {
+    version: StateVersion,
+    quote: Quote,
+}
+
+/// Caches quotes per (pool, direction, amount bucket) for the pool state they were computed
+/// from.
+///
+/// [`QuoteCache::quote`] reads the [`StateVersion`] of the pool's adapter, which changes with
+/// every update the registry applies to the pool, so a quote is never reused once any of the
+/// pool accounts has been updated, even within the same slot.
+#[derive(Clone, Debug)]
+pub struct QuoteCache {
+    entries: HashMap<QuoteKey, CachedQuote>,
+    capacity: usize,
+    significant_digits: Option<u32>,
+}
+
+impl Default for QuoteCache {
+    fn default() -> Self {
+        Self::new(DEFAULT_CAPACITY)
+    }
+}
+
+impl QuoteCache {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            entries: HashMap::new(),
+            capacity,
+            significant_digits: None,
+        }
+    }
+
+    /// Buckets amounts by rounding them down to `digits` significant decimal digits, so
+    /// nearby amounts share a quote. The quote is computed for the rounded amount, which
+    /// is reported as its `in_amount`.
+    pub fn with_significant_digits(mut self, digits: u32) -> Self {
+        self.significant_digits = Some(digits.max(1));
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// The amount quotes for `amount` are computed and cached for
+    pub fn bucket(&self, amount: u64) -> u64 {
+        match self.significant_digits {
+            Some(digits) => {
+                let total_digits = amount.checked_ilog10().map_or(1, |log| log + 1);
+                let scale = 10_u64.pow(total_digits.saturating_sub(digits));
+                amount - amount % scale
+            }
+            None => amount,
+        }
+    }
+
+    /// Returns the quote cached at `version` of the pool for the bucket of `amount`, or
+    /// computes it with `quote` for the bucket amount and caches it. Errors are not cached.
+    pub fn get_or_quote(
+        &mut self,
+        pool: &Pubkey,
+        trade_direction: TradeDirection,
+        version: StateVersion,
+        amount: u64,
+        quote: impl FnOnce(u64) -> Result<Quote>,
+    ) -> Result<Quote> {
+        let key = QuoteKey {
+            pool: *pool,
+            a_to_b: matches!(trade_direction, TradeDirection::AtoB),
+            amount: self.bucket(amount),
+        };
+        if let Some(cached) = self.entries.get(&key) {
+            if cached.version == version {
+                return Ok(cached.quote.clone());
+            }
+        }
+        let result = quote(key.amount)?;
+        if self.entries.len() >= self.capacity {
+            self.evict();
+        }
+        self.entries.insert(
+            key,
+            CachedQuote {
+                version,
+                quote: result.clone(),
+            },
+        );
+        Ok(result)
+    }
+
+    /// Quotes through [`MarketRegistry::quote`], caching the result for the current state
+    /// of the pool
+    pub fn quote(
+        &mut self,
+        registry: &MarketRegistry,
+        pool: &Pubkey,
+        input_mint: &Pubkey,
+        amount: u64,
+    ) -> Result<Quote> {
+        let market = registry
+            .get(pool)
+            .ok_or_else(|| anyhow!("Pool {} is not registered", pool))?;
+        let [token_a_mint, token_b_mint] = market.reserve_mints();
+        let trade_direction = if *input_mint == token_a_mint {
+            TradeDirection::AtoB
+        } else if *input_mint == token_b_mint {
+            TradeDirection::BtoA
+        } else {
+            return Err(anyhow!("Mint {} is not part of pool {}", input_mint, pool));
+        };
+        let version = market.state_version();
+        self.get_or_quote(pool, trade_direction, version, amount, |amount| {
+            registry.quote(pool, input_mint, amount)
+        })
+    }
+
+    /// Drops the quotes of past pool states, or everything if they are all still valid
+    fn evict(&mut self) {
+        let mut pool_versions: HashMap<Pubkey, StateVersion> = HashMap::new();
+        for (key, cached) in &self.entries {
+            let pool_version = pool_versions.entry(key.pool).or_default();
+            *pool_version = (*pool_version).max(cached.version);
+        }
+        self.entries
+            .retain(|key, cached| cached.version >= pool_versions[&key.pool]);
+        if self.entries.len() >= self.capacity {
+            self.entries.clear();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use solana_sdk::pubkey::Pubkey;
+
+    use super::QuoteCache;
+    use crate::amm::Quote;
+    use crate::{StateVersion, TradeDirection};
+
+    fn version(updates: u64) -> StateVersion {
+        StateVersion {
+            updates,
+            ..StateVersion::default()
+        }
+    }
+
+    #[test]
+    fn test_quote_cache_invalidated_by_version() {
+        let pool = Pubkey::new_unique();
+        let calls = Cell::new(0);
+        let quote = |amount: u64| {
+            calls.set(calls.get() + 1);
+            Ok(Quote {
+                in_amount: amount,
+                out_amount: amount / 2,
+                ..Quote::default()
+            })
+        };
+
+        let mut cache = QuoteCache::new(10);
+        let first = cache
+            .get_or_quote(&pool, TradeDirection::AtoB, version(1), 1_000, quote)
+            .unwrap();
+        let second = cache
+            .get_or_quote(&pool, TradeDirection::AtoB, version(1), 1_000, quote)
+            .unwrap();
+        assert_eq!(calls.get(), 1);
+        assert_eq!(first.out_amount, second.out_amount);
+
+        // The other direction has its own quotes
+        cache
+            .get_or_quote(&pool, TradeDirection::BtoA, version(1), 1_000, quote)
+            .unwrap();
+        assert_eq!(calls.get(), 2);
+
+        cache
+            .get_or_quote(&pool, TradeDirection::AtoB, version(2), 1_000, quote)
+            .unwrap();
+        assert_eq!(calls.get(), 3);
+
+        // Other pools are unaffected
+        let other_pool = Pubkey::new_unique();
+        cache
+            .get_or_quote(&other_pool, TradeDirection::AtoB, version(1), 1_000, quote)
+            .unwrap();
+        cache
+            .get_or_quote(&other_pool, TradeDirection::AtoB, version(1), 1_000, quote)
+            .unwrap();
+        assert_eq!(calls.get(), 4);
+
+        assert!(cache
+            .get_or_quote(&pool, TradeDirection::AtoB, version(2), 5, |_| Err(
+                anyhow::anyhow!("curve error")
+            ))
+            .is_err());
+        assert_eq!(cache.len(), 3);
+    }
+
+    #[test]
+    fn test_quote_cache_buckets() {
+        let cache = QuoteCache::new(10).with_significant_digits(3);
+        assert_eq!(cache.bucket(0), 0);
+        assert_eq!(cache.bucket(7), 7);
+        assert_eq!(cache.bucket(999), 999);
+        assert_eq!(cache.bucket(1_234_567), 1_230_000);
+        assert_eq!(cache.bucket(u64::MAX), 18_400_000_000_000_000_000);
+        assert_eq!(QuoteCache::new(10).bucket(1_234_567), 1_234_567);
+    }
+
+    #[test]
+    fn test_quote_cache_capacity() {
+        let pool = Pubkey::new_unique();
+        let mut cache = QuoteCache::new(2);
+        for amount in 0..5 {
+            cache
+                .get_or_quote(&pool, TradeDirection::AtoB, version(1), amount, |amount| {
+                    Ok(Quote {
+                        in_amount: amount,
+                        ..Quote::default()
+                    })
+                })
+                .unwrap();
+            assert!(cache.len() <= 2);
+        }
+    }
+}
@@ -0,0 +1,184 @@
+//! Client side rate limiting of RPC requests, so the registry refresh and the other RPC
+//! helpers stay within the limits of public endpoints.
+//!
+//! The limit is applied by the [`RpcSender`] of the client, every request of an `RpcClient`
+//! built with [`rate_limited_rpc_client`] goes through it whatever helper sends it.
+
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use serde_json::Value;
+use solana_client::client_error::Result as ClientResult;
+use solana_client::http_sender::HttpSender;
+use solana_client::nonblocking::rpc_client::RpcClient as NonblockingRpcClient;
+use solana_client::rpc_client::{RpcClient, RpcClientConfig};
+use solana_client::rpc_request::RpcRequest;
+use solana_client::rpc_sender::{RpcSender, RpcTransportStats};
+use solana_sdk::commitment_config::CommitmentConfig;
+
+/// A token bucket refilled at `requests_per_second`, holding up to `burst` requests, with an
+/// optional minimum interval between two requests.
+///
+/// Requests over the limit are delayed, not rejected. Share one limiter between the clients
+/// of an endpoint so they draw from the same budget.
+#[derive(Debug)]
+pub struct RateLimiter {
+    requests_per_second: f64,
+    burst: f64,
+    min_interval: Duration,
+    bucket: Mutex<Bucket>,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    /// Negative when requests are queued waiting for the bucket to refill
+    tokens: f64,
+    refilled_at: Instant,
+    /// When the last reserved request is sent
+    last_request_at: Option<Instant>,
+}
+
+impl RateLimiter {
+    /// Allows `requests_per_second` on average, with bursts of as many requests
+    pub fn new(requests_per_second: u32) -> Self {
+        let requests_per_second = f64::from(requests_per_second.max(1));
+        Self {
+            requests_per_second,
+            burst: requests_per_second,
+            min_interval: Duration::ZERO,
+            bucket: Mutex::new(Bucket {
+                tokens: requests_per_second,
+                refilled_at: Instant::now(),
+                last_request_at: None,
+            }),
+        }
+    }
+
+    pub fn with_burst(mut self, burst: u32) -> Self {
+        self.burst = f64::from(burst.max(1));
+        self.bucket.get_mut().unwrap().tokens = self.burst;
+        self
+    }
+
+    /// Paces the requests, sending them at least `min_interval` apart even when the bucket
+    /// allows a burst
+    pub fn with_min_interval(mut self, min_interval: Duration) -> Self {
+        self.min_interval = min_interval;
+        self
+    }
+
+    /// Reserves a request, returning how long to wait before sending it
+    pub fn reserve(&self) -> Duration {
+        let now = Instant::now();
+        let mut bucket = self.bucket.lock().unwrap();
+        let elapsed = now.duration_since(bucket.refilled_at).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * self.requests_per_second).min(self.burst);
+        bucket.refilled_at = now;
+        bucket.tokens -= 1.0;
+
+        let mut wait = if bucket.tokens < 0.0 {
+            Duration::from_secs_f64(-bucket.tokens / self.requests_per_second)
+        } else {
+            Duration::ZERO
+        };
+        if let Some(last_request_at) = bucket.last_request_at {
+            let paced_at = last_request_at + self.min_interval;
+            wait = wait.max(paced_at.saturating_duration_since(now));
+        }
+        bucket.last_request_at = Some(now + wait);
+        wait
+    }
+
+    /// Blocks the thread until a request may be sent
+    pub fn acquire(&self) {
+        let wait = self.reserve();
+        if !wait.is_zero() {
+            thread::sleep(wait);
+        }
+    }
+
+    /// Waits until a request may be sent
+    pub async fn acquire_async(&self) {
+        let wait = self.reserve();
+        if !wait.is_zero() {
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
+
+/// Sends the requests of `inner` through a [`RateLimiter`]
+pub struct RateLimitedSender<S = HttpSender> {
+    inner: S,
+    limiter: Arc<RateLimiter>,
+}
+
+impl<S> RateLimitedSender<S> {
+    pub fn new(inner: S, limiter: Arc<RateLimiter>) -> Self {
+        Self { inner, limiter }
+    }
+}
+
+#[async_trait]
+impl<S: RpcSender + Send + Sync> RpcSender for RateLimitedSender<S> {
+    async fn send(&self, request: RpcRequest, params: Value) -> ClientResult<Value> {
+        self.limiter.acquire_async().await;
+        self.inner.send(request, params).await
+    }
+
+    fn get_transport_stats(&self) -> RpcTransportStats {
+        self.inner.get_transport_stats()
+    }
+
+    fn url(&self) -> String {
+        self.inner.url()
+    }
+}
+
+/// An `RpcClient` for `url` whose requests are limited by `limiter`
+pub fn rate_limited_rpc_client(
+    url: impl ToString,
+    commitment: CommitmentConfig,
+    limiter: Arc<RateLimiter>,
+) -> RpcClient {
+    RpcClient::new_sender(
+        RateLimitedSender::new(HttpSender::new(url.to_string()), limiter),
+        RpcClientConfig::with_commitment(commitment),
+    )
+}
+
+/// [`rate_limited_rpc_client`] with the nonblocking client
+pub fn rate_limited_nonblocking_rpc_client(
+    url: impl ToString,
+    commitment: CommitmentConfig,
+    limiter: Arc<RateLimiter>,
+) -> NonblockingRpcClient {
+    NonblockingRpcClient::new_sender(
+        RateLimitedSender::new(HttpSender::new(url.to_string()), limiter),
+        RpcClientConfig::with_commitment(commitment),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::RateLimiter;
+
+    #[test]
+    fn test_rate_limiter() {
+        let limiter = RateLimiter::new(10).with_burst(2);
+        assert_eq!(limiter.reserve(), Duration::ZERO);
+        assert_eq!(limiter.reserve(), Duration::ZERO);
+        // The bucket is empty, the next requests wait for it to refill at 100ms per request
+        let wait = limiter.reserve();
+        assert!(wait > Duration::from_millis(90) && wait <= Duration::from_millis(100));
+        assert!(limiter.reserve() > wait);
+
+        let paced = RateLimiter::new(1_000).with_min_interval(Duration::from_millis(50));
+        assert_eq!(paced.reserve(), Duration::ZERO);
+        let wait = paced.reserve();
+        assert!(wait > Duration::from_millis(40) && wait <= Duration::from_millis(50));
+    }
+}
@@ -0,0 +1,271 @@
+//! Time series of pool reserves and prices, written as append-only records for TWAP, volume
+//! and APR computations.
+//!
+//! Records are sampled from a [`MarketRegistry`] kept up to date by any of the updaters, on
+//! an interval with [`spawn_recorder`] or whenever the caller decides with
+//! [`ReserveRecorder::record`].
+
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::Result;
+use rust_decimal::Decimal;
+use serde_json::json;
+use solana_sdk::pubkey::Pubkey;
+
+use crate::amm::Amm;
+use crate::price::decimals_scale;
+use crate::registry::{MarketRegistry, SharedRegistry};
+use crate::{JupiterRarefish, TradeDirection};
+
+/// The state of a pool at a point in time
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ReserveRecord {
+    pub unix_timestamp: i64,
+    /// Slot of the cluster clock shared with the adapter, if any
+    pub slot: Option<u64>,
+    pub pool: Pubkey,
+    pub token_a_amount: u64,
+    pub token_b_amount: u64,
+    pub token_a_fees: Option<u64>,
+    pub token_b_fees: Option<u64>,
+    /// Spot price of token A in token B UI units, before fees. Unknown without the mint
+    /// decimals, when they are too far apart to scale, or when the pool has no liquidity.
+    pub price: Option<Decimal>,
+}
+
+impl ReserveRecord {
+    /// Samples `market`, `None` until its vaults have been updated
+    pub fn sample(market: &JupiterRarefish, unix_timestamp: i64) -> Option<Self> {
+        let (token_a_amount, token_b_amount) = market.reserves()?;
+        let price = market
+            .token_a_decimals()
+            .zip(market.token_b_decimals())
+            .and_then(|(token_a_decimals, token_b_decimals)| {
+                let price = market.spot_price(TradeDirection::AtoB, false).ok()?;
+                Some(price * decimals_scale(token_a_decimals, token_b_decimals).ok()?)
+            });
+        let (token_a_fees, token_b_fees) = match market.fees_balances() {
+            Some((token_a_fees, token_b_fees)) => (Some(token_a_fees), Some(token_b_fees)),
+            None => (None, None),
+        };
+        Some(Self {
+            unix_timestamp,
+            slot: market.current_slot(),
+            pool: market.key(),
+            token_a_amount,
+            token_b_amount,
+            token_a_fees,
+            token_b_fees,
+            price,
+        })
+    }
+}
+
+/// Destination of the records
+pub trait RecordSink {
+    fn write(&mut self, records: &[ReserveRecord]) -> Result<()>;
+}
+
+/// Writes one JSON object per record and line, flushed after every batch so a crash loses
+/// at most the batch being written
+pub struct JsonLinesSink<W: Write> {
+    writer: W,
+}
+
+impl JsonLinesSink<BufWriter<File>> {
+    /// Appends to the file at `path`, creating it if needed
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self::new(BufWriter::new(file)))
+    }
+}
+
+impl<W: Write> JsonLinesSink<W> {
+    pub fn new(writer: W) -> Self {
+        Self { writer }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+impl<W: Write> RecordSink for JsonLinesSink<W> {
+    fn write(&mut self, records: &[ReserveRecord]) -> Result<()> {
+        for record in records {
+            // Amounts as numbers, the price as a string to keep its full precision
+            let line = json!({
+                "unixTimestamp": record.unix_timestamp,
+                "slot": record.slot,
+                "pool": record.pool.to_string(),
+                "tokenAAmount": record.token_a_amount,
+                "tokenBAmount": record.token_b_amount,
+                "tokenAFees": record.token_a_fees,
+                "tokenBFees": record.token_b_fees,
+                "price": record.price.map(|price| price.to_string()),
+            });
+            writeln!(self.writer, "{}", line)?;
+        }
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+/// Writes every batch as a row group of a Parquet file, which is only readable once
+/// [`ParquetSink::close`] has written its footer: use a file per recording session
+#[cfg(feature = "parquet")]
+pub struct ParquetSink<W: Write + Send> {
+    writer: parquet::arrow::ArrowWriter<W>,
+}
+
+#[cfg(feature = "parquet")]
+impl<W: Write + Send> ParquetSink<W> {
+    pub fn new(writer: W) -> Result<Self> {
+        Ok(Self {
+            writer: parquet::arrow::ArrowWriter::try_new(writer, parquet_schema(), None)?,
+        })
+    }
+
+    pub fn close(self) -> Result<()> {
+        self.writer.close()?;
+        Ok(())
+    }
+}
+
+#[cfg(feature = "parquet")]
+fn parquet_schema() -> std::sync::Arc<arrow::datatypes::Schema> {
+    use arrow::datatypes::{DataType, Field, Schema};
+
+    std::sync::Arc::new(Schema::new(vec![
+        Field::new("unix_timestamp", DataType::Int64, false),
+        Field::new("slot", DataType::UInt64, true),
+        Field::new("pool", DataType::Utf8, false),
+        Field::new("token_a_amount", DataType::UInt64, false),
+        Field::new("token_b_amount", DataType::UInt64, false),
+        Field::new("token_a_fees", DataType::UInt64, true),
+        Field::new("token_b_fees", DataType::UInt64, true),
+        Field::new("price", DataType::Float64, true),
+    ]))
+}
+
+#[cfg(feature = "parquet")]
+impl<W: Write + Send> RecordSink for ParquetSink<W> {
+    fn write(&mut self, records: &[ReserveRecord]) -> Result<()> {
+        use std::sync::Arc;
+
+        use arrow::array::{ArrayRef, Float64Array, Int64Array, StringArray, UInt64Array};
+        use arrow::record_batch::RecordBatch;
+        use rust_decimal::prelude::ToPrimitive;
+
+        if records.is_empty() {
+            return Ok(());
+        }
+        let integers = |f: fn(&ReserveRecord) -> u64| -> ArrayRef {
+            Arc::new(UInt64Array::from_iter_values(records.iter().map(f)))
+        };
+        let optional_integers = |f: fn(&ReserveRecord) -> Option<u64>| -> ArrayRef {
+            Arc::new(records.iter().map(f).collect::<UInt64Array>())
+        };
+        let columns: Vec<ArrayRef> = vec![
+            Arc::new(Int64Array::from_iter_values(
+                records.iter().map(|record| record.unix_timestamp),
+            )),
+            optional_integers(|record| record.slot),
+            Arc::new(StringArray::from_iter_values(
+                records.iter().map(|record| record.pool.to_string()),
+            )),
+            integers(|record| record.token_a_amount),
+            integers(|record| record.token_b_amount),
+            optional_integers(|record| record.token_a_fees),
+            optional_integers(|record| record.token_b_fees),
+            Arc::new(
+                records
+                    .iter()
+                    .map(|record| record.price.and_then(|price| price.to_f64()))
+                    .collect::<Float64Array>(),
+            ),
+        ];
+        self.writer
+            .write(&RecordBatch::try_new(parquet_schema(), columns)?)?;
+        // Each batch is its own row group so records reach the file as they are written
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+/// Samples the markets of a registry into a [`RecordSink`]
+pub struct ReserveRecorder<S: RecordSink> {
+    sink: S,
+    on_change: bool,
+    last_reserves: HashMap<Pubkey, (u64, u64)>,
+}
+
+impl<S: RecordSink> ReserveRecorder<S> {
+    pub fn new(sink: S) -> Self {
+        Self {
+            sink,
+            on_change: false,
+            last_reserves: HashMap::new(),
+        }
+    }
+
+    /// Only records a pool when its reserves changed since its last record
+    pub fn with_on_change(mut self, on_change: bool) -> Self {
+        self.on_change = on_change;
+        self
+    }
+
+    /// Records the markets of `registry` now, returning the number of records written
+    pub fn record(&mut self, registry: &MarketRegistry) -> Result<usize> {
+        let unix_timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_secs() as i64);
+        self.record_at(registry, unix_timestamp)
+    }
+
+    /// Records the markets of `registry` with the given timestamp, e.g. when replaying
+    pub fn record_at(&mut self, registry: &MarketRegistry, unix_timestamp: i64) -> Result<usize> {
+        let mut records: Vec<_> = registry
+            .markets()
+            .filter_map(|market| ReserveRecord::sample(market, unix_timestamp))
+            .filter(|record| {
+                let reserves = (record.token_a_amount, record.token_b_amount);
+                let changed = self.last_reserves.get(&record.pool) != Some(&reserves);
+                !self.on_change || changed
+            })
+            .collect();
+        // Stable output order whatever the registry order
+        records.sort_by_key(|record| record.pool);
+        self.sink.write(&records)?;
+        for record in &records {
+            self.last_reserves
+                .insert(record.pool, (record.token_a_amount, record.token_b_amount));
+        }
+        Ok(records.len())
+    }
+
+    pub fn into_sink(self) -> S {
+        self.sink
+    }
+}
+
+/// Records the registry every `interval` on a background thread. Failed writes are logged
+/// and retried with the next sample.
+pub fn spawn_recorder<S: RecordSink + Send + 'static>(
+    registry: SharedRegistry,
+    mut recorder: ReserveRecorder<S>,
+    interval: Duration,
+) -> thread::JoinHandle<()> {
+    thread::spawn(move || loop {
+        let result = recorder.record(&registry.read().unwrap());
+        if let Err(e) = result {
+            log::warn!("Failed to record reserves: {}", e);
+        }
+        thread::sleep(interval);
+    })
+}
@@ -0,0 +1,117 @@
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use futures::StreamExt;
+use solana_client::nonblocking::pubsub_client::PubsubClient;
+use solana_client::rpc_client::RpcClient;
+use tokio::task::JoinHandle;
+
+use crate::registry::{fetch_accounts, MarketRegistry, SharedRegistry};
+
+/// What makes [`AutoRefresh`] fetch the accounts of its markets again
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RefreshTrigger {
+    /// Every interval
+    Interval(Duration),
+    /// Every `slots` slots, as notified by `slotSubscribe` on the websocket endpoint
+    Slots { ws_url: String, slots: u64 },
+}
+
+/// Owns a set of markets and keeps them fresh from RPC on a tokio task, quotes go through
+/// the read handle of [`AutoRefresh::handle`].
+///
+/// Accounts are fetched without holding the lock so quotes are only blocked while the
+/// markets are being updated. Failed refreshes are logged and retried on the next trigger.
+pub struct AutoRefresh {
+    registry: SharedRegistry,
+    rpc: Arc<RpcClient>,
+    trigger: RefreshTrigger,
+    reconnect_delay: Duration,
+}
+
+impl AutoRefresh {
+    pub fn new(registry: MarketRegistry, rpc: Arc<RpcClient>) -> Self {
+        Self {
+            registry: Arc::new(RwLock::new(registry)),
+            rpc,
+            trigger: RefreshTrigger::Interval(Duration::from_secs(1)),
+            reconnect_delay: Duration::from_secs(1),
+        }
+    }
+
+    pub fn with_trigger(mut self, trigger: RefreshTrigger) -> Self {
+        self.trigger = trigger;
+        self
+    }
+
+    /// Delay before resubscribing when the slot subscription of
+    /// [`RefreshTrigger::Slots`] drops
+    pub fn with_reconnect_delay(mut self, reconnect_delay: Duration) -> Self {
+        self.reconnect_delay = reconnect_delay;
+        self
+    }
+
+    /// The markets being refreshed, for quoting
+    pub fn handle(&self) -> SharedRegistry {
+        self.registry.clone()
+    }
+
+    /// Starts refreshing on the current tokio runtime, returning the read handle with the
+    /// task. Abort the task to stop refreshing.
+    pub fn spawn(self) -> (SharedRegistry, JoinHandle<()>) {
+        let registry = self.handle();
+        let task = tokio::spawn(self.run());
+        (registry, task)
+    }
+
+    /// Refreshes the markets forever
+    pub async fn run(self) {
+        match self.trigger.clone() {
+            RefreshTrigger::Interval(interval) => {
+                let mut ticks = tokio::time::interval(interval);
+                ticks.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+                loop {
+                    ticks.tick().await;
+                    self.refresh_logged().await;
+                }
+            }
+            RefreshTrigger::Slots { ws_url, slots } => loop {
+                if let Err(e) = self.refresh_on_slots(&ws_url, slots.max(1)).await {
+                    log::warn!("Slot subscription failed: {}", e);
+                }
+                tokio::time::sleep(self.reconnect_delay).await;
+            },
+        }
+    }
+
+    async fn refresh_on_slots(&self, ws_url: &str, slots: u64) -> Result<()> {
+        let client = PubsubClient::new(ws_url).await?;
+        let (mut notifications, _unsubscribe) = client.slot_subscribe().await?;
+        let mut refreshed_slot = None;
+        while let Some(slot_info) = notifications.next().await {
+            if refreshed_slot.map_or(true, |refreshed_slot| {
+                slot_info.slot >= refreshed_slot + slots
+            }) {
+                refreshed_slot = Some(slot_info.slot);
+                self.refresh_logged().await;
+            }
+        }
+        Err(anyhow!("Websocket connection to {} closed", ws_url))
+    }
+
+    async fn refresh_logged(&self) {
+        if let Err(e) = self.refresh().await {
+            log::warn!("Failed to refresh markets: {}", e);
+        }
+    }
+
+    /// Fetches and applies the accounts of every market once
+    pub async fn refresh(&self) -> Result<()> {
+        let keys = self.registry.read().unwrap().get_accounts_to_update();
+        let rpc = self.rpc.clone();
+        let accounts_map =
+            tokio::task::spawn_blocking(move || fetch_accounts(&rpc, &keys)).await??;
+        Ok(self.registry.write().unwrap().update(&accounts_map)?)
+    }
+}
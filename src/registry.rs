@@ -0,0 +1,282 @@
+use std::collections::HashMap;
+use std::fmt;
+#[cfg(not(target_arch = "wasm32"))]
+use std::fs;
+#[cfg(not(target_arch = "wasm32"))]
+use std::path::Path;
+use std::sync::{Arc, RwLock};
+#[cfg(feature = "client")]
+use std::thread;
+#[cfg(feature = "client")]
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "client")]
+use solana_client::rpc_client::RpcClient;
+use solana_sdk::pubkey::Pubkey;
+
+#[cfg(feature = "client")]
+use crate::amm::KeyedAccount;
+use crate::amm::{AccountMap, Amm, Quote, QuoteParams, SwapMode};
+use crate::JupiterRarefish;
+
+/// Maximum number of accounts accepted by a single `getMultipleAccounts` call
+#[cfg(feature = "client")]
+pub(crate) const MAX_MULTIPLE_ACCOUNTS: usize = 100;
+
+pub type SharedRegistry = Arc<RwLock<MarketRegistry>>;
+
+/// On-disk representation of a registry, see [`MarketRegistry::save_snapshot`]
+#[derive(Serialize)]
+struct RegistrySnapshotRef<'a> {
+    markets: Vec<&'a JupiterRarefish>,
+}
+
+#[derive(Deserialize)]
+struct RegistrySnapshot {
+    markets: Vec<JupiterRarefish>,
+}
+
+/// A set of Rarefish markets that are refreshed together
+#[derive(Clone, Debug, Default)]
+pub struct MarketRegistry {
+    markets: HashMap<Pubkey, JupiterRarefish>,
+}
+
+impl MarketRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Writes every market, including its vault and mint state, to `path` as JSON so a
+    /// service can cold start from disk and catch up through updates
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn save_snapshot(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        // Write to a temporary file first so a crash never leaves a truncated snapshot behind
+        let temporary_path = path.with_extension("tmp");
+        fs::write(&temporary_path, self.to_snapshot_json()?)?;
+        fs::rename(&temporary_path, path)?;
+        Ok(())
+    }
+
+    /// Restores a registry written by [`MarketRegistry::save_snapshot`]
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn load_snapshot(path: impl AsRef<Path>) -> Result<Self> {
+        Self::from_snapshot_json(&fs::read(path)?)
+    }
+
+    /// The snapshot written by [`MarketRegistry::save_snapshot`], for storage other than files
+    pub fn to_snapshot_json(&self) -> Result<Vec<u8>> {
+        let snapshot = RegistrySnapshotRef {
+            markets: self.markets.values().collect(),
+        };
+        Ok(serde_json::to_vec(&snapshot)?)
+    }
+
+    /// Restores a registry from [`MarketRegistry::to_snapshot_json`]
+    pub fn from_snapshot_json(json: &[u8]) -> Result<Self> {
+        let snapshot: RegistrySnapshot = serde_json::from_slice(json)?;
+        let mut registry = Self::new();
+        for market in snapshot.markets {
+            registry.insert(market);
+        }
+        Ok(registry)
+    }
+
+    pub fn insert(&mut self, market: JupiterRarefish) {
+        self.markets.insert(market.key(), market);
+    }
+
+    pub fn remove(&mut self, pool: &Pubkey) -> Option<JupiterRarefish> {
+        self.markets.remove(pool)
+    }
+
+    pub fn get(&self, pool: &Pubkey) -> Option<&JupiterRarefish> {
+        self.markets.get(pool)
+    }
+
+    pub fn markets(&self) -> impl Iterator<Item = &JupiterRarefish> {
+        self.markets.values()
+    }
+
+    pub fn len(&self) -> usize {
+        self.markets.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.markets.is_empty()
+    }
+
+    /// All accounts required to update every market in the registry
+    pub fn get_accounts_to_update(&self) -> Vec<Pubkey> {
+        let mut accounts: Vec<Pubkey> = self
+            .markets
+            .values()
+            .flat_map(|market| market.accounts_to_update())
+            .collect();
+        accounts.sort();
+        accounts.dedup();
+        accounts
+    }
+
+    /// Updates every market, a market failing to update keeping its previous state without
+    /// holding back the others
+    pub fn update(&mut self, accounts_map: &AccountMap) -> Result<(), UpdateErrors> {
+        let mut errors = Vec::new();
+        for market in self.markets.values_mut() {
+            if let Err(e) = update_market(market, accounts_map) {
+                errors.push((market.key(), e));
+            }
+        }
+        UpdateErrors::check(errors)
+    }
+
+    /// Updates only the markets that depend on `key`, for streaming updaters receiving
+    /// one account at a time. `accounts_map` must still contain every account the
+    /// affected markets require.
+    pub fn update_dependents(
+        &mut self,
+        key: &Pubkey,
+        accounts_map: &AccountMap,
+    ) -> Result<(), UpdateErrors> {
+        let mut errors = Vec::new();
+        for market in self.markets.values_mut() {
+            if market.accounts_to_update().contains(key) {
+                if let Err(e) = update_market(market, accounts_map) {
+                    errors.push((market.key(), e));
+                }
+            }
+        }
+        UpdateErrors::check(errors)
+    }
+
+    /// Quotes an exact-in swap of `amount` of `input_mint` against `pool`
+    pub fn quote(&self, pool: &Pubkey, input_mint: &Pubkey, amount: u64) -> Result<Quote> {
+        let market = self
+            .get(pool)
+            .ok_or_else(|| anyhow!("Pool {} is not registered", pool))?;
+        let reserve_mints = market.reserve_mints();
+        if !reserve_mints.contains(input_mint) {
+            return Err(anyhow!("Mint {} is not part of pool {}", input_mint, pool));
+        }
+        let output_mint = reserve_mints
+            .into_iter()
+            .find(|mint| mint != input_mint)
+            .ok_or_else(|| anyhow!("Pool {} has no output mint for {}", pool, input_mint))?;
+        if market.reserves().is_none() {
+            #[cfg(feature = "metrics")]
+            crate::metrics::metrics().record_stale(pool);
+            return Err(anyhow!("Pool {} has not been updated", pool));
+        }
+        #[cfg(feature = "metrics")]
+        let start = std::time::Instant::now();
+        let quote = market.quote(&QuoteParams {
+            input_mint: *input_mint,
+            output_mint,
+            amount,
+            swap_mode: SwapMode::ExactIn,
+        });
+        #[cfg(feature = "metrics")]
+        crate::metrics::metrics().observe_quote(pool, start.elapsed(), quote.is_ok());
+        quote
+    }
+}
+
+/// The markets that failed to update, with their error
+#[derive(Debug)]
+pub struct UpdateErrors {
+    pub errors: Vec<(Pubkey, anyhow::Error)>,
+}
+
+impl UpdateErrors {
+    fn check(mut errors: Vec<(Pubkey, anyhow::Error)>) -> Result<(), Self> {
+        if errors.is_empty() {
+            return Ok(());
+        }
+        errors.sort_by_key(|(pool, _)| *pool);
+        Err(Self { errors })
+    }
+}
+
+impl fmt::Display for UpdateErrors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Failed to update {} market(s)", self.errors.len())?;
+        for (pool, e) in &self.errors {
+            write!(f, "; {}: {}", pool, e)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for UpdateErrors {}
+
+fn update_market(market: &mut JupiterRarefish, accounts_map: &AccountMap) -> Result<()> {
+    #[cfg(feature = "metrics")]
+    let start = std::time::Instant::now();
+    let result = market.update(accounts_map);
+    #[cfg(feature = "metrics")]
+    crate::metrics::metrics().observe_update(market, start.elapsed(), result.is_ok());
+    result
+}
+
+#[cfg(feature = "client")]
+impl MarketRegistry {
+    /// Fetches the given pools and their vaults, returning a ready-to-quote registry
+    pub fn load(rpc: &RpcClient, pools: &[Pubkey]) -> Result<Self> {
+        let mut registry = Self::new();
+        let accounts_map = fetch_accounts(rpc, pools)?;
+        for pool in pools {
+            let account = accounts_map
+                .get(pool)
+                .ok_or_else(|| anyhow!("Pool {} not found", pool))?;
+            registry.insert(JupiterRarefish::new_from_keyed_account(&KeyedAccount {
+                key: *pool,
+                account: account.clone(),
+                params: None,
+            })?);
+        }
+        registry.refresh(rpc)?;
+        Ok(registry)
+    }
+
+    pub fn refresh(&mut self, rpc: &RpcClient) -> Result<()> {
+        let accounts_map = fetch_accounts(rpc, &self.get_accounts_to_update())?;
+        Ok(self.update(&accounts_map)?)
+    }
+}
+
+/// Fetches accounts in chunks of [`MAX_MULTIPLE_ACCOUNTS`], skipping the ones that don't exist
+#[cfg(feature = "client")]
+pub fn fetch_accounts(rpc: &RpcClient, keys: &[Pubkey]) -> Result<AccountMap> {
+    let mut accounts_map = AccountMap::new();
+    for chunk in keys.chunks(MAX_MULTIPLE_ACCOUNTS) {
+        for (key, account) in chunk.iter().zip(rpc.get_multiple_accounts(chunk)?) {
+            if let Some(account) = account {
+                accounts_map.insert(*key, account);
+            }
+        }
+    }
+    Ok(accounts_map)
+}
+
+/// Refreshes the registry from `rpc` every `interval` on a background thread.
+///
+/// Accounts are fetched without holding the lock so quotes are only blocked while the
+/// markets are being updated.
+#[cfg(feature = "client")]
+pub fn spawn_refresh(registry: SharedRegistry, rpc: RpcClient, interval: Duration) {
+    thread::spawn(move || loop {
+        thread::sleep(interval);
+        let keys = registry.read().unwrap().get_accounts_to_update();
+        match fetch_accounts(&rpc, &keys) {
+            Ok(accounts_map) => {
+                if let Err(e) = registry.write().unwrap().update(&accounts_map) {
+                    log::warn!("Failed to update markets: {}", e);
+                }
+            }
+            Err(e) => log::warn!("Failed to fetch market accounts: {}", e),
+        }
+    });
+}
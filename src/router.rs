@@ -0,0 +1,450 @@
+//! Routing within Rarefish only, for products that integrate Rarefish without the rest of
+//! Jupiter: quotes every registered pool of a pair and picks the best execution.
+
+use std::collections::{BTreeSet, HashMap};
+
+use anyhow::{anyhow, Result};
+use solana_sdk::instruction::{AccountMeta, Instruction};
+use solana_sdk::pubkey::Pubkey;
+
+use crate::amm::{swap_params, Quote, SwapParams};
+use crate::registry::MarketRegistry;
+
+/// Default number of chunks an amount is divided into when splitting across pools
+pub const DEFAULT_SPLIT_STEPS: u64 = 20;
+
+/// A quote of a single pool
+#[derive(Clone, Debug)]
+pub struct PoolQuote {
+    pub pool: Pubkey,
+    pub quote: Quote,
+}
+
+/// Pools of the registry trading `input_mint` against `output_mint`, in a stable order
+pub fn pools_for_pair(
+    registry: &MarketRegistry,
+    input_mint: &Pubkey,
+    output_mint: &Pubkey,
+) -> Vec<Pubkey> {
+    let mut pools: Vec<Pubkey> = registry
+        .markets()
+        .filter(|market| {
+            let mints = market.reserve_mints();
+            input_mint != output_mint && mints.contains(input_mint) && mints.contains(output_mint)
+        })
+        .map(|market| market.market_key)
+        .collect();
+    pools.sort();
+    pools
+}
+
+/// Quotes an exact-in swap of `amount` on every pool of the pair, best output first.
+/// Pools that cannot quote the amount are left out.
+pub fn quote_pools(
+    registry: &MarketRegistry,
+    input_mint: &Pubkey,
+    output_mint: &Pubkey,
+    amount: u64,
+) -> Vec<PoolQuote> {
+    let mut quotes: Vec<PoolQuote> = pools_for_pair(registry, input_mint, output_mint)
+        .into_iter()
+        .filter_map(|pool| {
+            registry
+                .quote(&pool, input_mint, amount)
+                .ok()
+                .map(|quote| PoolQuote { pool, quote })
+        })
+        .collect();
+    // Stable sort keeps the pool order for equal outputs so the choice is deterministic
+    quotes.sort_by(|a, b| b.quote.out_amount.cmp(&a.quote.out_amount));
+    quotes
+}
+
+/// The pool giving the most output for an exact-in swap of `amount`
+pub fn best_quote(
+    registry: &MarketRegistry,
+    input_mint: &Pubkey,
+    output_mint: &Pubkey,
+    amount: u64,
+) -> Result<PoolQuote> {
+    quote_pools(registry, input_mint, output_mint, amount)
+        .into_iter()
+        .next()
+        .ok_or_else(|| {
+            anyhow!(
+                "No Rarefish pool can quote {} {} to {}",
+                amount,
+                input_mint,
+                output_mint
+            )
+        })
+}
+
+/// An exact-in swap split across several pools of the same pair
+#[derive(Clone, Debug)]
+pub struct SplitQuote {
+    pub in_amount: u64,
+    pub out_amount: u64,
+    pub fee_amount: u64,
+    /// The quote of every pool receiving part of the amount
+    pub allocations: Vec<PoolQuote>,
+}
+
+impl SplitQuote {
+    /// Swap account metas of every allocation, in order, with `swap_params` supplying the
+    /// mints and the user accounts
+    pub fn account_metas(
+        &self,
+        registry: &MarketRegistry,
+        swap_params: &SwapParams,
+    ) -> Result<Vec<Vec<AccountMeta>>> {
+        self.allocations
+            .iter()
+            .map(|allocation| {
+                let market = registry
+                    .get(&allocation.pool)
+                    .ok_or_else(|| anyhow!("Pool {} is not registered", allocation.pool))?;
+                Ok(market.swap_account_metas(swap_params).to_vec())
+            })
+            .collect()
+    }
+}
+
+/// Splits an exact-in swap of `amount` across the pools of the pair to maximize the total
+/// output. The amount is divided into `steps` chunks, each given to the pool with the best
+/// marginal output, which is optimal up to the chunk size for the convex curves of the pools.
+///
+/// The whole amount is allocated or this errors: a split the pools can only partially fill
+/// is not returned.
+pub fn split_quote(
+    registry: &MarketRegistry,
+    input_mint: &Pubkey,
+    output_mint: &Pubkey,
+    amount: u64,
+    steps: u64,
+) -> Result<SplitQuote> {
+    let pools = pools_for_pair(registry, input_mint, output_mint);
+    let quoters: Vec<_> = pools
+        .iter()
+        .map(|pool| {
+            move |amount: u64| -> Result<u64> {
+                Ok(registry.quote(pool, input_mint, amount)?.out_amount)
+            }
+        })
+        .collect();
+    let allocations = split_allocations(amount, steps, &quoters);
+    let allocated: u64 = allocations.iter().sum();
+    if allocated < amount {
+        return Err(anyhow!(
+            "Rarefish pools can only quote {} of {} {} to {}",
+            allocated,
+            amount,
+            input_mint,
+            output_mint
+        ));
+    }
+
+    let mut split = SplitQuote {
+        in_amount: 0,
+        out_amount: 0,
+        fee_amount: 0,
+        allocations: vec![],
+    };
+    for (pool, allocation) in pools.into_iter().zip(allocations) {
+        if allocation == 0 {
+            continue;
+        }
+        let quote = registry.quote(&pool, input_mint, allocation)?;
+        split.in_amount += quote.in_amount;
+        split.out_amount += quote.out_amount;
+        split.fee_amount += quote.fee_amount;
+        split.allocations.push(PoolQuote { pool, quote });
+    }
+    if split.allocations.is_empty() {
+        return Err(anyhow!(
+            "No Rarefish pool can quote {} {} to {}",
+            amount,
+            input_mint,
+            output_mint
+        ));
+    }
+    Ok(split)
+}
+
+/// Greedy allocation of `amount` in `steps` chunks over the pools quoted by `quoters`, which
+/// map an input amount to the output amount. The last chunk takes the rounding remainder.
+fn split_allocations<F: Fn(u64) -> Result<u64>>(
+    amount: u64,
+    steps: u64,
+    quoters: &[F],
+) -> Vec<u64> {
+    let mut allocations = vec![0; quoters.len()];
+    let mut outputs = vec![0; quoters.len()];
+    let steps = steps.clamp(1, amount.max(1));
+    let chunk = amount / steps;
+    let mut remaining = amount;
+    while remaining > 0 && !quoters.is_empty() {
+        let size = if remaining < 2 * chunk {
+            remaining
+        } else {
+            chunk
+        };
+        let best = quoters
+            .iter()
+            .enumerate()
+            .filter_map(|(index, quoter)| {
+                let output = quoter(allocations[index] + size).ok()?;
+                Some((index, output, output.saturating_sub(outputs[index])))
+            })
+            .max_by_key(|(index, _, marginal)| (*marginal, std::cmp::Reverse(*index)));
+        let Some((index, output, _)) = best else {
+            break;
+        };
+        allocations[index] += size;
+        outputs[index] = output;
+        remaining -= size;
+    }
+    allocations
+}
+
+/// One swap of a route
+#[derive(Clone, Debug)]
+pub struct RouteHop {
+    pub pool: Pubkey,
+    pub input_mint: Pubkey,
+    pub output_mint: Pubkey,
+    pub quote: Quote,
+}
+
+/// A direct or multi-hop exact-in swap through Rarefish pools
+#[derive(Clone, Debug)]
+pub struct Route {
+    pub in_amount: u64,
+    pub out_amount: u64,
+    /// The swaps in execution order, each selling the output of the previous one
+    pub hops: Vec<RouteHop>,
+}
+
+impl Route {
+    /// The swap instructions of the route in execution order. `token_accounts` maps every
+    /// mint of the route to the token account of `owner` holding it, every hop but the last
+    /// one only requires the quoted output, the last one at least `minimum_amount_out`.
+    pub fn instructions(
+        &self,
+        registry: &MarketRegistry,
+        owner: &Pubkey,
+        token_accounts: &HashMap<Pubkey, Pubkey>,
+        minimum_amount_out: u64,
+    ) -> Result<Vec<Instruction>> {
+        let token_account = |mint: &Pubkey| {
+            token_accounts
+                .get(mint)
+                .copied()
+                .ok_or_else(|| anyhow!("No token account for mint {}", mint))
+        };
+        self.hops
+            .iter()
+            .enumerate()
+            .map(|(index, hop)| {
+                let market = registry
+                    .get(&hop.pool)
+                    .ok_or_else(|| anyhow!("Pool {} is not registered", hop.pool))?;
+                let minimum_amount_out = if index + 1 == self.hops.len() {
+                    minimum_amount_out
+                } else {
+                    hop.quote.out_amount
+                };
+                Ok(market.swap_instruction(&swap_params(
+                    hop.quote.in_amount,
+                    minimum_amount_out,
+                    hop.input_mint,
+                    hop.output_mint,
+                    token_account(&hop.input_mint)?,
+                    token_account(&hop.output_mint)?,
+                    *owner,
+                )))
+            })
+            .collect()
+    }
+}
+
+/// Mints that can be reached from `mint` with a single swap, in a stable order
+fn neighbour_mints(registry: &MarketRegistry, mint: &Pubkey) -> BTreeSet<Pubkey> {
+    registry
+        .markets()
+        .map(|market| market.reserve_mints())
+        .filter(|mints| mints.contains(mint))
+        .flatten()
+        .filter(|neighbour| neighbour != mint)
+        .collect()
+}
+
+/// The best route selling `amount` of `input_mint` for `output_mint`, either through a
+/// direct pool or through one intermediate mint when that gives more output
+pub fn best_route(
+    registry: &MarketRegistry,
+    input_mint: &Pubkey,
+    output_mint: &Pubkey,
+    amount: u64,
+) -> Result<Route> {
+    let mut routes = vec![];
+    if let Ok(direct) = best_quote(registry, input_mint, output_mint, amount) {
+        routes.push(Route {
+            in_amount: direct.quote.in_amount,
+            out_amount: direct.quote.out_amount,
+            hops: vec![RouteHop {
+                pool: direct.pool,
+                input_mint: *input_mint,
+                output_mint: *output_mint,
+                quote: direct.quote,
+            }],
+        });
+    }
+    routes.extend(two_hop_routes(registry, input_mint, output_mint, amount));
+    routes
+        .into_iter()
+        .reduce(|best, route| {
+            if route.out_amount > best.out_amount {
+                route
+            } else {
+                best
+            }
+        })
+        .ok_or_else(|| {
+            anyhow!(
+                "No Rarefish route for {} {} to {}",
+                amount,
+                input_mint,
+                output_mint
+            )
+        })
+}
+
+/// Quotes selling `amount` of `input_mint` through `pools` in order, each hop selling the
+/// output of the previous one
+pub fn quote_route(
+    registry: &MarketRegistry,
+    pools: &[Pubkey],
+    input_mint: &Pubkey,
+    amount: u64,
+) -> Result<Route> {
+    let mut hops: Vec<RouteHop> = Vec::with_capacity(pools.len());
+    let (mut hop_input_mint, mut hop_amount) = (*input_mint, amount);
+    for pool in pools {
+        let market = registry
+            .get(pool)
+            .ok_or_else(|| anyhow!("Pool {} is not registered", pool))?;
+        let output_mint = match market.reserve_mints() {
+            [token_a_mint, token_b_mint] if token_a_mint == hop_input_mint => token_b_mint,
+            [token_a_mint, token_b_mint] if token_b_mint == hop_input_mint => token_a_mint,
+            _ => return Err(anyhow!("Pool {} does not trade {}", pool, hop_input_mint)),
+        };
+        let quote = registry.quote(pool, &hop_input_mint, hop_amount)?;
+        hop_amount = quote.out_amount;
+        hops.push(RouteHop {
+            pool: *pool,
+            input_mint: hop_input_mint,
+            output_mint,
+            quote,
+        });
+        hop_input_mint = output_mint;
+    }
+    let in_amount = hops
+        .first()
+        .map(|hop| hop.quote.in_amount)
+        .ok_or_else(|| anyhow!("A route has at least one pool"))?;
+    Ok(Route {
+        in_amount,
+        out_amount: hop_amount,
+        hops,
+    })
+}
+
+/// Routes A to X to B through every intermediate mint X, using the best pool of each hop
+pub fn two_hop_routes(
+    registry: &MarketRegistry,
+    input_mint: &Pubkey,
+    output_mint: &Pubkey,
+    amount: u64,
+) -> Vec<Route> {
+    neighbour_mints(registry, input_mint)
+        .into_iter()
+        .filter(|intermediate_mint| intermediate_mint != output_mint)
+        .filter_map(|intermediate_mint| {
+            let first = best_quote(registry, input_mint, &intermediate_mint, amount).ok()?;
+            let second = best_quote(
+                registry,
+                &intermediate_mint,
+                output_mint,
+                first.quote.out_amount,
+            )
+            .ok()?;
+            Some(Route {
+                in_amount: first.quote.in_amount,
+                out_amount: second.quote.out_amount,
+                hops: vec![
+                    RouteHop {
+                        pool: first.pool,
+                        input_mint: *input_mint,
+                        output_mint: intermediate_mint,
+                        quote: first.quote,
+                    },
+                    RouteHop {
+                        pool: second.pool,
+                        input_mint: intermediate_mint,
+                        output_mint: *output_mint,
+                        quote: second.quote,
+                    },
+                ],
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::{anyhow, Result};
+
+    use super::split_allocations;
+
+    /// Constant product output without fees
+    fn constant_product(source: u64, destination: u64) -> impl Fn(u64) -> Result<u64> {
+        move |amount| {
+            Ok((u128::from(amount) * u128::from(destination)
+                / (u128::from(source) + u128::from(amount))) as u64)
+        }
+    }
+
+    #[test]
+    fn test_split_allocations() {
+        // Equal pools share the amount equally
+        let pools = [
+            constant_product(1_000_000, 1_000_000),
+            constant_product(1_000_000, 1_000_000),
+        ];
+        assert_eq!(split_allocations(100_000, 20, &pools), vec![50_000, 50_000]);
+
+        // A pool four times deeper takes most of it
+        let pools = [
+            constant_product(1_000_000, 1_000_000),
+            constant_product(4_000_000, 4_000_000),
+        ];
+        let allocations = split_allocations(100_000, 20, &pools);
+        assert_eq!(allocations.iter().sum::<u64>(), 100_000);
+        assert!(allocations[1] >= 3 * allocations[0]);
+        let split_output = pools[0](allocations[0]).unwrap() + pools[1](allocations[1]).unwrap();
+        assert!(split_output >= pools[1](100_000).unwrap());
+
+        // Small amounts are not split into empty chunks
+        assert_eq!(split_allocations(3, 20, &pools).iter().sum::<u64>(), 3);
+        assert_eq!(split_allocations(0, 20, &pools), vec![0, 0]);
+    }
+
+    #[test]
+    fn test_split_allocations_skips_failing_pools() {
+        let failing = |_: u64| -> Result<u64> { Err(anyhow!("curve error")) };
+        let working = |amount: u64| -> Result<u64> { Ok(amount / 2) };
+        let pools: [&dyn Fn(u64) -> Result<u64>; 2] = [&failing, &working];
+        assert_eq!(split_allocations(1_000, 10, &pools), vec![0, 1_000]);
+    }
+}
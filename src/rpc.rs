@@ -0,0 +1,51 @@
+//! Loading an adapter from the pool address in one call.
+
+use anyhow::{anyhow, Result};
+use solana_client::nonblocking::rpc_client::RpcClient as NonblockingRpcClient;
+use solana_client::rpc_client::RpcClient;
+use solana_sdk::pubkey::Pubkey;
+
+use crate::amm::{AccountMap, Amm, KeyedAccount};
+use crate::registry::{fetch_accounts, MAX_MULTIPLE_ACCOUNTS};
+use crate::JupiterRarefish;
+
+impl JupiterRarefish {
+    /// Fetches the pool and the accounts it depends on, returning a ready-to-quote adapter
+    pub fn load(rpc: &RpcClient, pool: &Pubkey) -> Result<Self> {
+        let account = rpc
+            .get_account(pool)
+            .map_err(|e| anyhow!("Failed to fetch pool {}: {}", pool, e))?;
+        let mut jupiter_rarefish = Self::new_from_keyed_account(&KeyedAccount {
+            key: *pool,
+            account,
+            params: None,
+        })?;
+        let accounts_map = fetch_accounts(rpc, &jupiter_rarefish.get_accounts_to_update())?;
+        jupiter_rarefish.update(&accounts_map)?;
+        Ok(jupiter_rarefish)
+    }
+
+    /// [`JupiterRarefish::load`] with the nonblocking RPC client
+    pub async fn load_async(rpc: &NonblockingRpcClient, pool: &Pubkey) -> Result<Self> {
+        let account = rpc
+            .get_account(pool)
+            .await
+            .map_err(|e| anyhow!("Failed to fetch pool {}: {}", pool, e))?;
+        let mut jupiter_rarefish = Self::new_from_keyed_account(&KeyedAccount {
+            key: *pool,
+            account,
+            params: None,
+        })?;
+        let keys = jupiter_rarefish.get_accounts_to_update();
+        let mut accounts_map = AccountMap::new();
+        for chunk in keys.chunks(MAX_MULTIPLE_ACCOUNTS) {
+            for (key, account) in chunk.iter().zip(rpc.get_multiple_accounts(chunk).await?) {
+                if let Some(account) = account {
+                    accounts_map.insert(*key, account);
+                }
+            }
+        }
+        jupiter_rarefish.update(&accounts_map)?;
+        Ok(jupiter_rarefish)
+    }
+}
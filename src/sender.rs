@@ -0,0 +1,171 @@
+//! Sending of swap transactions until they land, re-signing them with a fresh blockhash
+//! when they expire.
+
+use std::thread;
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, Result};
+use solana_client::rpc_client::RpcClient;
+use solana_client::rpc_config::{RpcSendTransactionConfig, RpcTransactionConfig};
+use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::signature::{Keypair, Signature};
+use solana_transaction_status::UiTransactionEncoding;
+
+use crate::decode::{decode_transaction_transfers, ExecutedSwap};
+use crate::transaction::SwapTransactionBuilder;
+
+/// How long a transaction confirmed at the processed commitment is waited for at the
+/// confirmed one, to decode it
+const CONFIRMED_FETCH_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// A swap transaction that landed
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SentSwap {
+    pub signature: Signature,
+    pub slot: u64,
+    /// Attempts it took, each with a new blockhash
+    pub attempts: usize,
+    /// The swaps of the transaction as executed, with the realized amounts
+    pub swaps: Vec<ExecutedSwap>,
+}
+
+/// Sends swap transactions and waits for their confirmation.
+///
+/// A transaction is rebroadcast every `resend_interval` until it is confirmed or its
+/// blockhash expires, it is then re-signed with a fresh blockhash and sent again, up to
+/// `max_attempts` times. A transaction that lands with an error is not retried.
+pub struct SwapSender<'a> {
+    rpc: &'a RpcClient,
+    commitment: CommitmentConfig,
+    max_attempts: usize,
+    resend_interval: Duration,
+    poll_interval: Duration,
+    skip_preflight: bool,
+}
+
+impl<'a> SwapSender<'a> {
+    pub fn new(rpc: &'a RpcClient) -> Self {
+        Self {
+            rpc,
+            commitment: CommitmentConfig::confirmed(),
+            max_attempts: 3,
+            resend_interval: Duration::from_secs(2),
+            poll_interval: Duration::from_millis(500),
+            skip_preflight: false,
+        }
+    }
+
+    /// Commitment the transaction is confirmed at, and the blockhash fetched at
+    pub fn with_commitment(mut self, commitment: CommitmentConfig) -> Self {
+        self.commitment = commitment;
+        self
+    }
+
+    pub fn with_max_attempts(mut self, max_attempts: usize) -> Self {
+        self.max_attempts = max_attempts.max(1);
+        self
+    }
+
+    pub fn with_resend_interval(mut self, resend_interval: Duration) -> Self {
+        self.resend_interval = resend_interval;
+        self
+    }
+
+    pub fn with_poll_interval(mut self, poll_interval: Duration) -> Self {
+        self.poll_interval = poll_interval;
+        self
+    }
+
+    /// Skips the preflight simulation of the RPC node
+    pub fn with_skip_preflight(mut self, skip_preflight: bool) -> Self {
+        self.skip_preflight = skip_preflight;
+        self
+    }
+
+    /// Signs and sends the swap of `builder` until it is confirmed, then decodes the realized
+    /// amounts from the landed transaction
+    pub fn send(&self, builder: &SwapTransactionBuilder, signers: &[&Keypair]) -> Result<SentSwap> {
+        for attempt in 1..=self.max_attempts {
+            let (blockhash, last_valid_block_height) = self
+                .rpc
+                .get_latest_blockhash_with_commitment(self.commitment)?;
+            let transaction = builder.build(signers, blockhash)?;
+            let signature = transaction.signatures[0];
+            let config = RpcSendTransactionConfig {
+                skip_preflight: self.skip_preflight,
+                preflight_commitment: Some(self.commitment.commitment),
+                // Rebroadcasting is done here, until the blockhash expires
+                max_retries: Some(0),
+                ..RpcSendTransactionConfig::default()
+            };
+            self.rpc
+                .send_transaction_with_config(&transaction, config)?;
+
+            let mut last_send = Instant::now();
+            loop {
+                thread::sleep(self.poll_interval);
+                let status = self.rpc.get_signature_statuses(&[signature])?.value.pop();
+                if let Some(status) = status.flatten() {
+                    if let Some(err) = status.err {
+                        return Err(anyhow!("Swap transaction {} failed: {}", signature, err));
+                    }
+                    if status.satisfies_commitment(self.commitment) {
+                        return self.confirmed(builder, signature, attempt);
+                    }
+                    // Landed but not at the commitment yet, it can no longer expire
+                    continue;
+                }
+                if self.rpc.get_block_height_with_commitment(self.commitment)?
+                    > last_valid_block_height
+                {
+                    break;
+                }
+                if last_send.elapsed() >= self.resend_interval {
+                    // Errors of a rebroadcast are those of the first send, already checked
+                    let _ = self.rpc.send_transaction_with_config(&transaction, config);
+                    last_send = Instant::now();
+                }
+            }
+        }
+        Err(anyhow!(
+            "Swap transaction expired {} times without landing",
+            self.max_attempts
+        ))
+    }
+
+    fn confirmed(
+        &self,
+        builder: &SwapTransactionBuilder,
+        signature: Signature,
+        attempts: usize,
+    ) -> Result<SentSwap> {
+        // Transactions cannot be fetched at the processed commitment
+        let commitment = if self.commitment.is_at_least_confirmed() {
+            self.commitment
+        } else {
+            CommitmentConfig::confirmed()
+        };
+        let config = RpcTransactionConfig {
+            encoding: Some(UiTransactionEncoding::Base64),
+            commitment: Some(commitment),
+            max_supported_transaction_version: Some(0),
+        };
+        let deadline = Instant::now() + CONFIRMED_FETCH_TIMEOUT;
+        let transaction = loop {
+            match self.rpc.get_transaction_with_config(&signature, config) {
+                Ok(transaction) => break transaction,
+                // A transaction processed but not confirmed yet is not found
+                Err(_) if commitment != self.commitment && Instant::now() < deadline => {
+                    thread::sleep(self.poll_interval)
+                }
+                Err(e) => return Err(e.into()),
+            }
+        };
+        Ok(SentSwap {
+            signature,
+            slot: transaction.slot,
+            attempts,
+            swaps: decode_transaction_transfers(&builder.program_id(), &transaction)?,
+        })
+    }
+}
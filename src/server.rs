@@ -0,0 +1,125 @@
+use std::net::SocketAddr;
+
+use anyhow::{anyhow, Result};
+use axum::extract::{Query, State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::{Json, Router};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use solana_sdk::pubkey::Pubkey;
+
+use crate::amm::Amm;
+use crate::registry::SharedRegistry;
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct QuoteQuery {
+    pool: String,
+    input_mint: String,
+    amount: u64,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct QuoteResponse {
+    pool: String,
+    input_mint: String,
+    output_mint: String,
+    in_amount: u64,
+    out_amount: u64,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct PoolResponse {
+    pool: String,
+    label: String,
+    mints: Vec<String>,
+}
+
+struct ServerError(StatusCode, anyhow::Error);
+
+impl IntoResponse for ServerError {
+    fn into_response(self) -> Response {
+        (self.0, Json(json!({ "error": self.1.to_string() }))).into_response()
+    }
+}
+
+fn parse_pubkey(value: &str) -> Result<Pubkey, ServerError> {
+    value
+        .parse()
+        .map_err(|_| ServerError(StatusCode::BAD_REQUEST, anyhow!("Invalid pubkey {}", value)))
+}
+
+async fn quote(
+    State(registry): State<SharedRegistry>,
+    Query(query): Query<QuoteQuery>,
+) -> Result<Json<QuoteResponse>, ServerError> {
+    let pool = parse_pubkey(&query.pool)?;
+    let input_mint = parse_pubkey(&query.input_mint)?;
+    let registry = registry.read().unwrap();
+    let market = registry.get(&pool).ok_or_else(|| {
+        ServerError(
+            StatusCode::NOT_FOUND,
+            anyhow!("Pool {} is not registered", pool),
+        )
+    })?;
+    let output_mint = market
+        .reserve_mints()
+        .into_iter()
+        .find(|mint| *mint != input_mint)
+        .unwrap_or_default();
+    let quote = registry
+        .quote(&pool, &input_mint, query.amount)
+        .map_err(|e| ServerError(StatusCode::BAD_REQUEST, e))?;
+    Ok(Json(QuoteResponse {
+        pool: pool.to_string(),
+        input_mint: input_mint.to_string(),
+        output_mint: output_mint.to_string(),
+        in_amount: quote.in_amount,
+        out_amount: quote.out_amount,
+    }))
+}
+
+async fn pools(State(registry): State<SharedRegistry>) -> Json<Vec<PoolResponse>> {
+    let registry = registry.read().unwrap();
+    Json(
+        registry
+            .markets()
+            .map(|market| PoolResponse {
+                pool: market.key().to_string(),
+                label: market.label(),
+                mints: market
+                    .reserve_mints()
+                    .iter()
+                    .map(Pubkey::to_string)
+                    .collect(),
+            })
+            .collect(),
+    )
+}
+
+pub fn router(registry: SharedRegistry) -> Router {
+    let router = Router::new()
+        .route("/quote", get(quote))
+        .route("/pools", get(pools));
+    #[cfg(feature = "metrics")]
+    let router = router.route("/metrics", get(metrics));
+    router.with_state(registry)
+}
+
+#[cfg(feature = "metrics")]
+async fn metrics() -> Result<String, ServerError> {
+    crate::metrics::metrics()
+        .encode()
+        .map_err(|e| ServerError(StatusCode::INTERNAL_SERVER_ERROR, e))
+}
+
+/// Serves quotes for the registered markets until the server fails
+pub async fn serve(addr: SocketAddr, registry: SharedRegistry) -> Result<()> {
+    let listener = tokio::net::TcpListener::bind(addr).await?;
+    axum::serve(listener, router(registry)).await?;
+    Ok(())
+}
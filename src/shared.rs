@@ -0,0 +1,115 @@
+use std::collections::HashSet;
+use std::sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+use anyhow::Result;
+use solana_sdk::pubkey::Pubkey;
+
+#[cfg(feature = "amm-interface-0-4")]
+use crate::amm::AmmContext;
+use crate::amm::{
+    AccountMap, Amm, AmmUserSetup, KeyedAccount, Quote, QuoteParams, SwapAndAccountMetas,
+    SwapParams,
+};
+#[cfg(feature = "amm-interface-0-4")]
+use crate::clock::SharedClock;
+use crate::JupiterRarefish;
+
+/// A `JupiterRarefish` shared between threads.
+///
+/// Clones (including `clone_amm`) point to the same instance, so one continuously updated
+/// adapter can be quoted from many threads without copying the pool state per request.
+#[derive(Clone, Debug)]
+pub struct SharedRarefish {
+    inner: Arc<RwLock<JupiterRarefish>>,
+}
+
+impl SharedRarefish {
+    pub fn new(jupiter_rarefish: JupiterRarefish) -> Self {
+        Self {
+            inner: Arc::new(RwLock::new(jupiter_rarefish)),
+        }
+    }
+
+    pub fn read(&self) -> RwLockReadGuard<'_, JupiterRarefish> {
+        self.inner.read().unwrap()
+    }
+
+    pub fn write(&self) -> RwLockWriteGuard<'_, JupiterRarefish> {
+        self.inner.write().unwrap()
+    }
+}
+
+impl From<JupiterRarefish> for SharedRarefish {
+    fn from(jupiter_rarefish: JupiterRarefish) -> Self {
+        Self::new(jupiter_rarefish)
+    }
+}
+
+impl Amm for SharedRarefish {
+    fn program_id(&self) -> Pubkey {
+        self.read().program_id()
+    }
+
+    #[cfg(not(feature = "amm-interface-0-4"))]
+    fn from_keyed_account(keyed_account: &KeyedAccount) -> Result<Self> {
+        JupiterRarefish::new_from_keyed_account(keyed_account).map(Self::new)
+    }
+
+    #[cfg(feature = "amm-interface-0-4")]
+    fn from_keyed_account(keyed_account: &KeyedAccount, amm_context: &AmmContext) -> Result<Self> {
+        JupiterRarefish::new_from_keyed_account(keyed_account)
+            .map(|amm| Self::new(amm.with_clock(SharedClock::from(&amm_context.clock_ref))))
+    }
+
+    fn label(&self) -> String {
+        self.read().label()
+    }
+
+    fn key(&self) -> Pubkey {
+        self.read().key()
+    }
+
+    fn get_reserve_mints(&self) -> Vec<Pubkey> {
+        self.read().get_reserve_mints()
+    }
+
+    fn get_accounts_to_update(&self) -> Vec<Pubkey> {
+        self.read().get_accounts_to_update()
+    }
+
+    fn update(&mut self, accounts_map: &AccountMap) -> Result<()> {
+        self.write().update(accounts_map)
+    }
+
+    fn quote(&self, quote_params: &QuoteParams) -> Result<Quote> {
+        self.read().quote(quote_params)
+    }
+
+    fn get_swap_and_account_metas(&self, swap_params: &SwapParams) -> Result<SwapAndAccountMetas> {
+        self.read().get_swap_and_account_metas(swap_params)
+    }
+
+    fn supports_exact_out(&self) -> bool {
+        self.read().supports_exact_out()
+    }
+
+    fn unidirectional(&self) -> bool {
+        self.read().unidirectional()
+    }
+
+    fn underlying_liquidities(&self) -> Option<HashSet<Pubkey>> {
+        self.read().underlying_liquidities()
+    }
+
+    fn get_accounts_len(&self) -> usize {
+        self.read().get_accounts_len()
+    }
+
+    fn get_user_setup(&self) -> Option<AmmUserSetup> {
+        self.read().get_user_setup()
+    }
+
+    fn clone_amm(&self) -> Box<dyn Amm + Send + Sync> {
+        Box::new(self.clone())
+    }
+}
@@ -0,0 +1,329 @@
+//! Simulation of swaps with `simulateTransaction`, to check quotes against the deployed program.
+
+use anchor_lang::AnchorDeserialize;
+use anyhow::Result;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use solana_account_decoder::UiAccountEncoding;
+use solana_client::rpc_client::RpcClient;
+use solana_client::rpc_config::{
+    RpcSimulateTransactionAccountsConfig, RpcSimulateTransactionConfig,
+};
+use solana_sdk::account::Account;
+use solana_sdk::hash::Hash;
+use solana_sdk::instruction::Instruction;
+use solana_sdk::message::{v0, VersionedMessage};
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::{Keypair, Signature};
+use solana_sdk::signer::Signer;
+use solana_sdk::transaction::{TransactionError, VersionedTransaction};
+use solana_transaction_status::UiTransactionReturnData;
+
+use crate::amm::{Amm, Quote, QuoteParams, SwapMode};
+use crate::events::{parse_logs, SwapEvent};
+use crate::registry::fetch_accounts;
+use crate::token::VaultState;
+use crate::transaction::SwapTransactionBuilder;
+use crate::JupiterRarefish;
+
+const PROGRAM_RETURN_PREFIX: &str = "Program return: ";
+
+/// Balance of a token account before and after a simulated transaction
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BalanceChange {
+    pub account: Pubkey,
+    pub pre: u64,
+    pub post: u64,
+}
+
+/// A swap quoted by the adapter and simulated against the program
+#[derive(Clone, Debug)]
+pub struct SimulatedSwap {
+    /// The hyperplane deployment of the pool
+    pub program_id: Pubkey,
+    pub input_mint: Pubkey,
+    pub output_mint: Pubkey,
+    pub in_amount: u64,
+    pub quote: Quote,
+    /// The owner's associated token account of the input mint
+    pub source: BalanceChange,
+    /// The owner's associated token account of the output mint
+    pub destination: BalanceChange,
+    pub logs: Vec<String>,
+    pub return_data: Option<UiTransactionReturnData>,
+    /// Why the simulated transaction failed, if it did
+    pub error: Option<TransactionError>,
+}
+
+impl SimulatedSwap {
+    /// Tokens received by the destination account, None if the simulation failed
+    pub fn realized_out(&self) -> Option<u64> {
+        self.error
+            .is_none()
+            .then(|| self.destination.post.saturating_sub(self.destination.pre))
+    }
+
+    /// Output reported by the program in its swap event or return data, see
+    /// [`parse_simulated_out`]
+    pub fn logged_out(&self) -> Option<u64> {
+        parse_simulated_out(&self.program_id, &self.logs, self.return_data.as_ref())
+    }
+
+    /// Realized minus quoted output, None if the simulation failed
+    pub fn difference(&self) -> Option<i128> {
+        self.realized_out()
+            .map(|realized_out| realized_out as i128 - self.quote.out_amount as i128)
+    }
+}
+
+/// Quotes an exact-in swap of `amount` of `input_mint` and simulates it from the associated
+/// token accounts of `signer`, without a minimum output so the program's result is observed
+/// whatever the quote.
+///
+/// The quote uses the state `jupiter_rarefish` was last updated with, refresh it right before
+/// simulating to compare both at (almost) the same slot.
+pub fn simulate_swap(
+    rpc: &RpcClient,
+    jupiter_rarefish: &JupiterRarefish,
+    signer: &Keypair,
+    input_mint: &Pubkey,
+    amount: u64,
+) -> Result<SimulatedSwap> {
+    let swap = SwapTransaction::new(jupiter_rarefish, &signer.pubkey(), input_mint, amount)?;
+    let transaction = VersionedTransaction::try_new(
+        VersionedMessage::V0(v0::Message::try_compile(
+            &signer.pubkey(),
+            &[swap.instruction.clone()],
+            &[],
+            rpc.get_latest_blockhash()?,
+        )?),
+        &[signer],
+    )?;
+    swap.simulate(rpc, &transaction, RpcSimulateTransactionConfig::default())
+}
+
+/// [`simulate_swap`] without a keypair, for CI and read-only services.
+///
+/// The transaction is simulated with signature verification disabled and the blockhash
+/// replaced by the RPC, so only the public keys are needed. `owner` holds the token accounts
+/// and authorizes the transfer, `payer` pays the transaction fee and defaults to `owner`. The
+/// payer must still hold enough SOL for the fee, pass a funded placeholder when the owner
+/// has none.
+pub fn simulate_swap_unsigned(
+    rpc: &RpcClient,
+    jupiter_rarefish: &JupiterRarefish,
+    owner: &Pubkey,
+    payer: Option<&Pubkey>,
+    input_mint: &Pubkey,
+    amount: u64,
+) -> Result<SimulatedSwap> {
+    let swap = SwapTransaction::new(jupiter_rarefish, owner, input_mint, amount)?;
+    let message = VersionedMessage::V0(v0::Message::try_compile(
+        payer.unwrap_or(owner),
+        &[swap.instruction.clone()],
+        &[],
+        Hash::default(),
+    )?);
+    let transaction = VersionedTransaction {
+        signatures: vec![Signature::default(); message.header().num_required_signatures as usize],
+        message,
+    };
+    swap.simulate(
+        rpc,
+        &transaction,
+        RpcSimulateTransactionConfig {
+            sig_verify: false,
+            replace_recent_blockhash: true,
+            ..RpcSimulateTransactionConfig::default()
+        },
+    )
+}
+
+/// Output of the last swap of the hyperplane deployment `program_id` in a simulated or
+/// executed transaction.
+///
+/// Read from the swap event hyperplane logs, or from the return data of the program when
+/// the logs were truncated before the event. `return_data` is the `returnData` of a
+/// `simulateTransaction` response, the `Program return` log line is used without it.
+pub fn parse_simulated_out(
+    program_id: &Pubkey,
+    logs: &[String],
+    return_data: Option<&UiTransactionReturnData>,
+) -> Option<u64> {
+    if let Some(logged) = parse_logs(program_id, logs).last() {
+        return Some(logged.event.token_out_amount);
+    }
+    let (returning_program_id, data) = match return_data {
+        Some(return_data) => (return_data.program_id.as_str(), return_data.data.0.as_str()),
+        None => logs
+            .iter()
+            .rev()
+            .find_map(|log| log.strip_prefix(PROGRAM_RETURN_PREFIX)?.split_once(' '))?,
+    };
+    if returning_program_id.parse::<Pubkey>().ok()? != *program_id {
+        return None;
+    }
+    let data = STANDARD.decode(data.trim()).ok()?;
+    SwapEvent::deserialize(&mut data.as_slice())
+        .ok()
+        .map(|event| event.token_out_amount)
+}
+
+/// A swap instruction from the owner's associated token accounts and the quote it is
+/// compared with
+struct SwapTransaction {
+    program_id: Pubkey,
+    input_mint: Pubkey,
+    output_mint: Pubkey,
+    amount: u64,
+    quote: Quote,
+    source_token_account: Pubkey,
+    destination_token_account: Pubkey,
+    instruction: Instruction,
+}
+
+impl SwapTransaction {
+    fn new(
+        jupiter_rarefish: &JupiterRarefish,
+        owner: &Pubkey,
+        input_mint: &Pubkey,
+        amount: u64,
+    ) -> Result<Self> {
+        // Only simulated to compare the output with the quote, so any output is accepted
+        let builder = SwapTransactionBuilder::new(jupiter_rarefish, *owner, *input_mint, amount)?
+            .with_minimum_out_amount(0);
+        let output_mint = builder.output_mint();
+        let quote = jupiter_rarefish.quote(&QuoteParams {
+            input_mint: *input_mint,
+            output_mint,
+            amount,
+            swap_mode: SwapMode::ExactIn,
+        })?;
+        Ok(Self {
+            program_id: jupiter_rarefish.program_id(),
+            input_mint: *input_mint,
+            output_mint,
+            amount,
+            quote,
+            source_token_account: builder.source_token_account(),
+            destination_token_account: builder.destination_token_account(),
+            instruction: builder.swap_instruction()?,
+        })
+    }
+
+    fn simulate(
+        self,
+        rpc: &RpcClient,
+        transaction: &VersionedTransaction,
+        config: RpcSimulateTransactionConfig,
+    ) -> Result<SimulatedSwap> {
+        let user_accounts = [self.source_token_account, self.destination_token_account];
+        let pre_accounts = fetch_accounts(rpc, &user_accounts)?;
+        let response = rpc.simulate_transaction_with_config(
+            transaction,
+            RpcSimulateTransactionConfig {
+                accounts: Some(RpcSimulateTransactionAccountsConfig {
+                    encoding: Some(UiAccountEncoding::Base64),
+                    addresses: user_accounts.iter().map(|key| key.to_string()).collect(),
+                }),
+                ..config
+            },
+        )?;
+        let post_accounts: Vec<Option<Account>> = response
+            .value
+            .accounts
+            .unwrap_or_default()
+            .into_iter()
+            .map(|account| account.and_then(|account| account.decode()))
+            .collect();
+        let balance_change = |index: usize| BalanceChange {
+            account: user_accounts[index],
+            pre: token_amount(pre_accounts.get(&user_accounts[index])),
+            post: token_amount(post_accounts.get(index).and_then(Option::as_ref)),
+        };
+        Ok(SimulatedSwap {
+            program_id: self.program_id,
+            input_mint: self.input_mint,
+            output_mint: self.output_mint,
+            in_amount: self.amount,
+            quote: self.quote,
+            source: balance_change(0),
+            destination: balance_change(1),
+            logs: response.value.logs.unwrap_or_default(),
+            return_data: response.value.return_data,
+            error: response.value.err,
+        })
+    }
+}
+
+/// Reads the balance of a raw token account, missing accounts count as empty
+fn token_amount(account: Option<&Account>) -> u64 {
+    account
+        .and_then(|account| VaultState::parse(&account.data).ok())
+        .map_or(0, |vault| vault.amount)
+}
+
+#[cfg(test)]
+mod tests {
+    use anchor_lang::AnchorSerialize;
+    use base64::engine::general_purpose::STANDARD;
+    use base64::Engine;
+    use solana_sdk::pubkey::Pubkey;
+    use solana_transaction_status::{UiReturnDataEncoding, UiTransactionReturnData};
+
+    use super::parse_simulated_out;
+    use crate::events::SwapEvent;
+
+    #[test]
+    fn test_parse_simulated_out() {
+        let event = SwapEvent {
+            token_in_amount: 10_000_000,
+            token_out_amount: 212_345,
+            total_fees: 2_500,
+        };
+        let mut event_data = SwapEvent::discriminator().to_vec();
+        event.serialize(&mut event_data).unwrap();
+        let return_data = STANDARD.encode(event.try_to_vec().unwrap());
+        let hyperplane = hyperplane::id();
+
+        let logs = vec![
+            format!("Program {} invoke [1]", hyperplane),
+            format!("Program data: {}", STANDARD.encode(&event_data)),
+            format!("Program return: {} {}", hyperplane, return_data),
+            format!("Program {} success", hyperplane),
+        ];
+        assert_eq!(parse_simulated_out(&hyperplane, &logs, None), Some(212_345));
+
+        // Without the event the return data is used, from the response or the logs
+        let truncated = vec![logs[0].clone(), logs[2].clone()];
+        assert_eq!(
+            parse_simulated_out(&hyperplane, &truncated, None),
+            Some(212_345)
+        );
+        assert_eq!(
+            parse_simulated_out(
+                &hyperplane,
+                &truncated[..1],
+                Some(&UiTransactionReturnData {
+                    program_id: hyperplane.to_string(),
+                    data: (return_data.clone(), UiReturnDataEncoding::Base64),
+                })
+            ),
+            Some(212_345)
+        );
+
+        // Return data of another program is not a swap output
+        let other = vec![format!(
+            "Program return: {} {}",
+            Pubkey::new_unique(),
+            return_data
+        )];
+        assert_eq!(parse_simulated_out(&hyperplane, &other, None), None);
+        assert_eq!(parse_simulated_out(&hyperplane, &logs[..1], None), None);
+        // Nor is the output of the pools of another deployment
+        assert_eq!(
+            parse_simulated_out(&Pubkey::new_unique(), &logs, None),
+            None
+        );
+    }
+}
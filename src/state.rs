@@ -0,0 +1,104 @@
+use anchor_lang::AccountSerialize;
+use anyhow::{anyhow, Result};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use solana_sdk::pubkey::Pubkey;
+
+use crate::token::VaultState;
+use crate::{check_pool_addresses, parse_pool, JupiterRarefish, PoolParams};
+
+/// Serialized form of the adapter. The pool is stored as base64 encoded account data so
+/// a restored adapter quotes exactly like the original, the curve is rebuilt from the pool
+/// and the label derived from the params.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct JupiterRarefishState {
+    market_key: String,
+    program_id: String,
+    pool: String,
+    token_a_vault: Option<VaultState>,
+    token_b_vault: Option<VaultState>,
+    #[serde(default)]
+    token_a_fees: Option<u64>,
+    #[serde(default)]
+    token_b_fees: Option<u64>,
+    token_a_decimals: Option<u8>,
+    token_b_decimals: Option<u8>,
+    #[serde(default)]
+    params: Option<serde_json::Value>,
+}
+
+impl TryFrom<&JupiterRarefish> for JupiterRarefishState {
+    type Error = anyhow::Error;
+
+    fn try_from(jupiter_rarefish: &JupiterRarefish) -> Result<Self> {
+        let mut pool = vec![];
+        jupiter_rarefish.pool.try_serialize(&mut pool)?;
+        Ok(Self {
+            market_key: jupiter_rarefish.market_key.to_string(),
+            program_id: jupiter_rarefish.program_id.to_string(),
+            pool: STANDARD.encode(pool),
+            token_a_vault: jupiter_rarefish.token_a_vault,
+            token_b_vault: jupiter_rarefish.token_b_vault,
+            token_a_fees: jupiter_rarefish.token_a_fees,
+            token_b_fees: jupiter_rarefish.token_b_fees,
+            token_a_decimals: jupiter_rarefish.token_a_decimals,
+            token_b_decimals: jupiter_rarefish.token_b_decimals,
+            params: Some(jupiter_rarefish.params.to_value()),
+        })
+    }
+}
+
+impl TryFrom<JupiterRarefishState> for JupiterRarefish {
+    type Error = anyhow::Error;
+
+    /// Goes through the same pool checks as `new_from_keyed_account`, so a corrupt or stale
+    /// snapshot is rejected instead of building an adapter that cannot quote
+    fn try_from(state: JupiterRarefishState) -> Result<Self> {
+        let pool_data = STANDARD.decode(state.pool)?;
+        let market_key = parse_pubkey(&state.market_key)?;
+        let program_id = parse_pubkey(&state.program_id)?;
+        let pool = parse_pool(&market_key, &pool_data)?;
+        let params = state
+            .params
+            .as_ref()
+            .map(PoolParams::from_value)
+            .transpose()?
+            .unwrap_or_default();
+        if params.strict {
+            check_pool_addresses(&market_key, &pool, &program_id)?;
+        }
+        let mut jupiter_rarefish = Self::from_pool(market_key, pool, program_id, params);
+        jupiter_rarefish.token_a_vault = state.token_a_vault;
+        jupiter_rarefish.token_b_vault = state.token_b_vault;
+        jupiter_rarefish.token_a_fees = state.token_a_fees;
+        jupiter_rarefish.token_b_fees = state.token_b_fees;
+        jupiter_rarefish.token_a_decimals = state.token_a_decimals;
+        jupiter_rarefish.token_b_decimals = state.token_b_decimals;
+        jupiter_rarefish.refresh_curve();
+        Ok(jupiter_rarefish)
+    }
+}
+
+fn parse_pubkey(value: &str) -> Result<Pubkey> {
+    value
+        .parse()
+        .map_err(|_| anyhow!("Invalid pubkey {}", value))
+}
+
+impl Serialize for JupiterRarefish {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        JupiterRarefishState::try_from(self)
+            .map_err(serde::ser::Error::custom)?
+            .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for JupiterRarefish {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        JupiterRarefishState::deserialize(deserializer)?
+            .try_into()
+            .map_err(serde::de::Error::custom)
+    }
+}
@@ -0,0 +1,437 @@
+//! Helpers creating Rarefish pools from scratch in a local `solana-program-test` bank, so swaps
+//! can be exercised end-to-end (token-2022 mints, host fees, any curve) without mainnet state.
+
+use anchor_spl::token::spl_token;
+use anchor_spl::token_2022::spl_token_2022;
+use hyperplane::curve::fees::Fees;
+use hyperplane::CurveUserParameters;
+use solana_program_test::{processor, ProgramTest, ProgramTestContext};
+use solana_sdk::account::Account;
+use solana_sdk::instruction::Instruction;
+use solana_sdk::program_pack::Pack;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Keypair;
+use solana_sdk::signer::Signer;
+use solana_sdk::system_instruction;
+use solana_sdk::transaction::Transaction;
+
+use crate::amm::{swap_params, AccountMap, Amm, KeyedAccount};
+use crate::initialize::PoolInitializer;
+use crate::JupiterRarefish;
+
+/// Offset of the `amount` field in the SPL token (and token-2022) account layout
+const TOKEN_ACCOUNT_AMOUNT_OFFSET: usize = 64;
+
+/// Parameters of a pool created by [`LocalPool::new`]
+pub struct LocalPoolConfig {
+    pub curve_parameters: CurveUserParameters,
+    pub fees: Fees,
+    pub token_a_decimals: u8,
+    pub token_b_decimals: u8,
+    pub token_a_program: Pubkey,
+    pub token_b_program: Pubkey,
+    pub initial_supply_a: u64,
+    pub initial_supply_b: u64,
+}
+
+impl Default for LocalPoolConfig {
+    fn default() -> Self {
+        Self {
+            curve_parameters: CurveUserParameters::ConstantProduct,
+            fees: Fees {
+                trade_fee_numerator: 25,
+                trade_fee_denominator: 10_000,
+                owner_trade_fee_numerator: 5,
+                owner_trade_fee_denominator: 10_000,
+                owner_withdraw_fee_numerator: 0,
+                owner_withdraw_fee_denominator: 0,
+                host_fee_numerator: 20,
+                host_fee_denominator: 100,
+            },
+            token_a_decimals: 9,
+            token_b_decimals: 6,
+            token_a_program: spl_token::id(),
+            token_b_program: spl_token::id(),
+            initial_supply_a: 1_000_000_000_000,
+            initial_supply_b: 1_000_000_000_000,
+        }
+    }
+}
+
+/// A pool initialized by the hyperplane program running in a local bank
+pub struct LocalPool {
+    pub context: ProgramTestContext,
+    pub admin: Keypair,
+    pub pool: Pubkey,
+    pub token_a_mint: Pubkey,
+    pub token_b_mint: Pubkey,
+    pub token_a_program: Pubkey,
+    pub token_b_program: Pubkey,
+}
+
+/// A wallet holding token accounts for both sides of a [`LocalPool`]
+pub struct LocalUser {
+    pub keypair: Keypair,
+    pub token_a: Pubkey,
+    pub token_b: Pubkey,
+}
+
+impl LocalPool {
+    /// Starts a bank with the hyperplane program, creates both mints and initializes the pool
+    /// with the admin's initial supply
+    pub async fn new(config: LocalPoolConfig) -> Self {
+        let program_test = ProgramTest::new(
+            "hyperplane",
+            hyperplane::id(),
+            processor!(hyperplane::entry),
+        );
+        let context = program_test.start_with_context().await;
+        let admin = Keypair::new();
+        let mut local_pool = LocalPool {
+            context,
+            admin,
+            pool: Pubkey::default(),
+            token_a_mint: Pubkey::default(),
+            token_b_mint: Pubkey::default(),
+            token_a_program: config.token_a_program,
+            token_b_program: config.token_b_program,
+        };
+        local_pool
+            .transfer_lamports(&local_pool.admin.pubkey(), 10_000_000_000)
+            .await;
+        local_pool.token_a_mint = local_pool
+            .create_mint(config.token_a_decimals, &config.token_a_program)
+            .await;
+        local_pool.token_b_mint = local_pool
+            .create_mint(config.token_b_decimals, &config.token_b_program)
+            .await;
+        let (token_a_mint, token_b_mint) = (local_pool.token_a_mint, local_pool.token_b_mint);
+        local_pool.pool = local_pool
+            .initialize(token_a_mint, token_b_mint, config)
+            .await;
+        local_pool
+    }
+
+    /// Creates another spl-token mint in the bank, for the pools of [`LocalPool::add_pool`]
+    pub async fn add_mint(&mut self, decimals: u8) -> Pubkey {
+        self.create_mint(decimals, &spl_token::id()).await
+    }
+
+    /// Initializes another pool in the bank between two of its mints, the decimals and token
+    /// programs of `config` are ignored. `pool` stays the first pool.
+    pub async fn add_pool(
+        &mut self,
+        token_a_mint: Pubkey,
+        token_b_mint: Pubkey,
+        config: LocalPoolConfig,
+    ) -> Pubkey {
+        self.initialize(token_a_mint, token_b_mint, config).await
+    }
+
+    async fn initialize(
+        &mut self,
+        token_a_mint: Pubkey,
+        token_b_mint: Pubkey,
+        config: LocalPoolConfig,
+    ) -> Pubkey {
+        let admin = self.admin.pubkey();
+        let (token_a_program, token_b_program) = (
+            self.token_program(&token_a_mint),
+            self.token_program(&token_b_mint),
+        );
+        let admin_token_a_ata = self
+            .create_token_account(&admin, token_a_mint, token_a_program)
+            .await;
+        self.mint_to(token_a_mint, &admin_token_a_ata, config.initial_supply_a)
+            .await;
+        let admin_token_b_ata = self
+            .create_token_account(&admin, token_b_mint, token_b_program)
+            .await;
+        self.mint_to(token_b_mint, &admin_token_b_ata, config.initial_supply_b)
+            .await;
+
+        let pool = Keypair::new();
+        let ixn = PoolInitializer::new(
+            admin,
+            pool.pubkey(),
+            token_a_mint,
+            token_b_mint,
+            config.initial_supply_a,
+            config.initial_supply_b,
+        )
+        .with_token_programs(token_a_program, token_b_program)
+        .with_admin_token_accounts(admin_token_a_ata, admin_token_b_ata)
+        .with_curve(config.curve_parameters)
+        .with_fees(config.fees)
+        .instruction();
+        let admin = self.admin.insecure_clone();
+        self.process(&[ixn], &[&admin, &pool]).await;
+        pool.pubkey()
+    }
+
+    /// Creates a funded wallet with token accounts for both mints
+    pub async fn create_user(&mut self, token_a_amount: u64, token_b_amount: u64) -> LocalUser {
+        let keypair = Keypair::new();
+        let (token_a_mint, token_b_mint) = (self.token_a_mint, self.token_b_mint);
+        let token_a = self
+            .create_token_account(&keypair.pubkey(), token_a_mint, self.token_a_program)
+            .await;
+        self.mint_to(token_a_mint, &token_a, token_a_amount).await;
+        let token_b = self
+            .create_token_account(&keypair.pubkey(), token_b_mint, self.token_b_program)
+            .await;
+        self.mint_to(token_b_mint, &token_b, token_b_amount).await;
+        LocalUser {
+            keypair,
+            token_a,
+            token_b,
+        }
+    }
+
+    /// Creates a token account of `owner` holding `amount` of any mint of the bank
+    pub async fn create_token_account_with(
+        &mut self,
+        owner: &Pubkey,
+        mint: Pubkey,
+        amount: u64,
+    ) -> Pubkey {
+        let token_program = self.token_program(&mint);
+        let account = self.create_token_account(owner, mint, token_program).await;
+        self.mint_to(mint, &account, amount).await;
+        account
+    }
+
+    /// Builds an adapter from the current pool, vault and mint accounts of the bank
+    pub async fn adapter(&mut self) -> JupiterRarefish {
+        self.pool_adapter(self.pool).await
+    }
+
+    /// [`LocalPool::adapter`] of any pool of the bank, such as those of [`LocalPool::add_pool`]
+    pub async fn pool_adapter(&mut self, pool: Pubkey) -> JupiterRarefish {
+        let account = self.get_account(pool).await.unwrap();
+        let mut jupiter_rarefish = JupiterRarefish::new_from_keyed_account(&KeyedAccount {
+            key: pool,
+            account,
+            params: None,
+        })
+        .unwrap();
+        self.refresh(&mut jupiter_rarefish).await;
+        jupiter_rarefish
+    }
+
+    /// Executes `ixns` signed by `user`, such as the swaps of a route
+    pub async fn execute(
+        &mut self,
+        ixns: &[Instruction],
+        user: &Keypair,
+    ) -> Result<(), solana_program_test::BanksClientError> {
+        self.try_process(ixns, &[user]).await
+    }
+
+    /// Updates an adapter with the accounts it depends on as they currently are in the bank
+    pub async fn refresh(&mut self, jupiter_rarefish: &mut JupiterRarefish) {
+        let mut accounts_map = AccountMap::new();
+        for key in jupiter_rarefish.get_accounts_to_update() {
+            if let Some(account) = self.get_account(key).await {
+                accounts_map.insert(key, account);
+            }
+        }
+        jupiter_rarefish.update(&accounts_map).unwrap();
+    }
+
+    /// Swaps `amount_in` of `source_mint` for the other token through the instruction built by
+    /// the adapter, returning the amount received by the user or the transaction error
+    pub async fn swap(
+        &mut self,
+        jupiter_rarefish: &JupiterRarefish,
+        user: &LocalUser,
+        source_mint: &Pubkey,
+        amount_in: u64,
+    ) -> Result<u64, solana_program_test::BanksClientError> {
+        let (destination_mint, source, destination) = if *source_mint == self.token_a_mint {
+            (self.token_b_mint, user.token_a, user.token_b)
+        } else {
+            (self.token_a_mint, user.token_b, user.token_a)
+        };
+        let accounts = jupiter_rarefish
+            .get_swap_and_account_metas(&swap_params(
+                amount_in,
+                0,
+                *source_mint,
+                destination_mint,
+                source,
+                destination,
+                user.keypair.pubkey(),
+            ))
+            .unwrap();
+        let ixn = Instruction {
+            program_id: jupiter_rarefish.program_id(),
+            accounts: accounts.account_metas,
+            data: hyperplane::instruction::Swap {
+                amount_in,
+                minimum_amount_out: 0,
+            }
+            .data(),
+        };
+        let destination_pre = self.token_balance(&destination).await;
+        self.try_process(&[ixn], &[&user.keypair]).await?;
+        Ok(self.token_balance(&destination).await - destination_pre)
+    }
+
+    pub async fn token_balance(&mut self, key: &Pubkey) -> u64 {
+        token_balance(&mut self.context, *key).await
+    }
+
+    pub async fn get_account(&mut self, key: Pubkey) -> Option<Account> {
+        self.context.banks_client.get_account(key).await.unwrap()
+    }
+
+    async fn transfer_lamports(&mut self, to: &Pubkey, lamports: u64) {
+        let ixn = system_instruction::transfer(&self.context.payer.pubkey(), to, lamports);
+        self.process(&[ixn], &[]).await;
+    }
+
+    async fn create_mint(&mut self, decimals: u8, token_program: &Pubkey) -> Pubkey {
+        let mint = Keypair::new();
+        let rent = self.context.banks_client.get_rent().await.unwrap();
+        let space = spl_token::state::Mint::LEN;
+        let ixns = [
+            system_instruction::create_account(
+                &self.context.payer.pubkey(),
+                &mint.pubkey(),
+                rent.minimum_balance(space),
+                space as u64,
+                token_program,
+            ),
+            spl_token_2022::instruction::initialize_mint2(
+                token_program,
+                &mint.pubkey(),
+                &self.context.payer.pubkey(),
+                None,
+                decimals,
+            )
+            .unwrap(),
+        ];
+        self.process(&ixns, &[&mint]).await;
+        mint.pubkey()
+    }
+
+    async fn create_token_account(
+        &mut self,
+        owner: &Pubkey,
+        mint: Pubkey,
+        token_program: Pubkey,
+    ) -> Pubkey {
+        let account = Keypair::new();
+        let rent = self.context.banks_client.get_rent().await.unwrap();
+        let space = spl_token::state::Account::LEN;
+        let ixns = [
+            system_instruction::create_account(
+                &self.context.payer.pubkey(),
+                &account.pubkey(),
+                rent.minimum_balance(space),
+                space as u64,
+                &token_program,
+            ),
+            spl_token_2022::instruction::initialize_account3(
+                &token_program,
+                &account.pubkey(),
+                &mint,
+                owner,
+            )
+            .unwrap(),
+        ];
+        self.process(&ixns, &[&account]).await;
+        account.pubkey()
+    }
+
+    /// Token program of a mint of the bank, those of [`LocalPool::add_mint`] are spl-token
+    fn token_program(&self, mint: &Pubkey) -> Pubkey {
+        if *mint == self.token_a_mint {
+            self.token_a_program
+        } else if *mint == self.token_b_mint {
+            self.token_b_program
+        } else {
+            spl_token::id()
+        }
+    }
+
+    async fn mint_to(&mut self, mint: Pubkey, account: &Pubkey, amount: u64) {
+        let token_program = self.token_program(&mint);
+        let ixn = spl_token_2022::instruction::mint_to(
+            &token_program,
+            &mint,
+            account,
+            &self.context.payer.pubkey(),
+            &[],
+            amount,
+        )
+        .unwrap();
+        self.process(&[ixn], &[]).await;
+    }
+
+    async fn process(&mut self, ixns: &[Instruction], signers: &[&Keypair]) {
+        self.try_process(ixns, signers).await.unwrap();
+    }
+
+    async fn try_process(
+        &mut self,
+        ixns: &[Instruction],
+        signers: &[&Keypair],
+    ) -> Result<(), solana_program_test::BanksClientError> {
+        let blockhash = self.context.get_new_latest_blockhash().await.unwrap();
+        let mut all_signers = vec![&self.context.payer];
+        all_signers.extend_from_slice(signers);
+        let txn = Transaction::new_signed_with_payer(
+            ixns,
+            Some(&self.context.payer.pubkey()),
+            &all_signers,
+            blockhash,
+        );
+        self.context.banks_client.process_transaction(txn).await
+    }
+}
+
+/// Token account owned by `owner` holding `amount` of `mint`, in the base SPL token layout
+pub fn user_token_account(
+    mint: Pubkey,
+    owner: Pubkey,
+    amount: u64,
+    token_program: Pubkey,
+) -> Account {
+    use spl_token::state::{Account as SplAccount, AccountState};
+
+    let mut data = vec![0; SplAccount::LEN];
+    SplAccount::pack(
+        SplAccount {
+            mint,
+            owner,
+            amount,
+            state: AccountState::Initialized,
+            ..SplAccount::default()
+        },
+        &mut data,
+    )
+    .unwrap();
+    Account {
+        lamports: 1_000_000_000,
+        data,
+        owner: token_program,
+        executable: false,
+        rent_epoch: 0,
+    }
+}
+
+pub async fn token_balance(context: &mut ProgramTestContext, key: Pubkey) -> u64 {
+    let account = context
+        .banks_client
+        .get_account(key)
+        .await
+        .unwrap()
+        .unwrap();
+    u64::from_le_bytes(
+        account.data[TOKEN_ACCOUNT_AMOUNT_OFFSET..TOKEN_ACCOUNT_AMOUNT_OFFSET + 8]
+            .try_into()
+            .unwrap(),
+    )
+}
@@ -0,0 +1,220 @@
+//! In-place parsers of the SPL token and token-2022 accounts the adapter depends on, reading
+//! only the base layout so token-2022 extensions do not matter.
+
+use anyhow::{anyhow, Result};
+use solana_sdk::account::Account;
+use solana_sdk::pubkey::Pubkey;
+
+/// Length of the base SPL token account layout, token-2022 extensions follow it
+const TOKEN_ACCOUNT_LEN: usize = 165;
+/// Offset of the `mint` field in the SPL token (and token-2022) account layout
+const TOKEN_ACCOUNT_MINT_OFFSET: usize = 0;
+/// Offset of the `owner` field in the SPL token (and token-2022) account layout
+const TOKEN_ACCOUNT_OWNER_OFFSET: usize = 32;
+/// Offset of the `amount` field in the SPL token (and token-2022) account layout
+const TOKEN_ACCOUNT_AMOUNT_OFFSET: usize = 64;
+/// Offset of the `state` field in the SPL token (and token-2022) account layout
+const TOKEN_ACCOUNT_STATE_OFFSET: usize = 108;
+/// Length of the base SPL mint layout, token-2022 extensions follow it
+const MINT_LEN: usize = 82;
+/// Offset of the `decimals` field in the SPL token (and token-2022) mint layout
+const MINT_DECIMALS_OFFSET: usize = 44;
+/// Offset of the `is_initialized` field in the SPL token (and token-2022) mint layout
+const MINT_IS_INITIALIZED_OFFSET: usize = 45;
+
+/// The parts of a vault token account needed for quoting, read in place from the account data
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+pub(crate) struct VaultState {
+    pub(crate) amount: u64,
+    pub(crate) frozen: bool,
+}
+
+impl VaultState {
+    pub(crate) fn parse(data: &[u8]) -> Result<Self> {
+        if data.len() < TOKEN_ACCOUNT_LEN {
+            return Err(anyhow!("Token account data is too short"));
+        }
+        let frozen = match data[TOKEN_ACCOUNT_STATE_OFFSET] {
+            1 => false,
+            2 => true,
+            0 => return Err(anyhow!("Token account is not initialized")),
+            state => return Err(anyhow!("Invalid token account state {}", state)),
+        };
+        let amount = u64::from_le_bytes(
+            data[TOKEN_ACCOUNT_AMOUNT_OFFSET..TOKEN_ACCOUNT_AMOUNT_OFFSET + 8]
+                .try_into()
+                .unwrap(),
+        );
+        Ok(Self { amount, frozen })
+    }
+
+    /// Parses the vault `key`, checking it holds `mint` and is owned by `token_program` so a
+    /// substituted account or a stale layout is not quoted against
+    pub(crate) fn parse_vault(
+        key: &Pubkey,
+        account: &Account,
+        mint: &Pubkey,
+        token_program: &Pubkey,
+    ) -> Result<Self> {
+        if account.owner != *token_program {
+            return Err(anyhow!(
+                "Vault {} is owned by {}, not by the token program {}",
+                key,
+                account.owner,
+                token_program
+            ));
+        }
+        Self::parse_vault_data(key, &account.data, mint)
+    }
+
+    /// Parses the data of the vault `key`, checking it holds `mint`. The owner of the account
+    /// is not known here, prefer [`VaultState::parse_vault`] when it is.
+    pub(crate) fn parse_vault_data(key: &Pubkey, data: &[u8], mint: &Pubkey) -> Result<Self> {
+        let vault = Self::parse(data).map_err(|e| anyhow!("Invalid vault {}: {}", key, e))?;
+        let vault_mint = Pubkey::new_from_array(
+            data[TOKEN_ACCOUNT_MINT_OFFSET..TOKEN_ACCOUNT_MINT_OFFSET + 32]
+                .try_into()
+                .unwrap(),
+        );
+        if vault_mint != *mint {
+            return Err(anyhow!(
+                "Vault {} holds mint {}, not the pool mint {}",
+                key,
+                vault_mint,
+                mint
+            ));
+        }
+        Ok(vault)
+    }
+
+    /// Checks the vault `key` is a token account of `authority`, the pool authority that
+    /// signs the transfers out of the vaults
+    pub(crate) fn check_authority(key: &Pubkey, data: &[u8], authority: &Pubkey) -> Result<()> {
+        let owner = data
+            .get(TOKEN_ACCOUNT_OWNER_OFFSET..TOKEN_ACCOUNT_OWNER_OFFSET + 32)
+            .map(|owner| Pubkey::new_from_array(owner.try_into().unwrap()))
+            .ok_or_else(|| anyhow!("Invalid vault {}: Token account data is too short", key))?;
+        if owner != *authority {
+            return Err(anyhow!(
+                "Vault {} belongs to {}, not to the pool authority {}",
+                key,
+                owner,
+                authority
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Reads the balance of an SPL token (or token-2022) account in place
+pub fn parse_token_amount(data: &[u8]) -> Result<u64> {
+    VaultState::parse(data).map(|vault| vault.amount)
+}
+
+/// Reads the decimals of an SPL token (or token-2022) mint in place
+pub fn parse_mint_decimals(data: &[u8]) -> Result<u8> {
+    if data.len() < MINT_LEN {
+        return Err(anyhow!("Mint data is too short"));
+    }
+    if data[MINT_IS_INITIALIZED_OFFSET] != 1 {
+        return Err(anyhow!("Mint is not initialized"));
+    }
+    Ok(data[MINT_DECIMALS_OFFSET])
+}
+
+#[cfg(test)]
+mod tests {
+    use anchor_spl::token::spl_token;
+    use anchor_spl::token::spl_token::state::{Account, AccountState, Mint};
+    use solana_sdk::program_pack::Pack;
+    use solana_sdk::pubkey::Pubkey;
+
+    use super::{parse_mint_decimals, parse_token_amount, VaultState};
+
+    fn packed_account(amount: u64, state: AccountState) -> Vec<u8> {
+        packed_account_of(Pubkey::new_unique(), amount, state)
+    }
+
+    fn packed_account_of(mint: Pubkey, amount: u64, state: AccountState) -> Vec<u8> {
+        let mut data = vec![0; Account::LEN];
+        Account::pack(
+            Account {
+                mint,
+                owner: Pubkey::new_unique(),
+                amount,
+                state,
+                ..Account::default()
+            },
+            &mut data,
+        )
+        .unwrap();
+        data
+    }
+
+    #[test]
+    fn test_vault_state_matches_unpack() {
+        let data = packed_account(123_456_789, AccountState::Initialized);
+        assert_eq!(
+            VaultState::parse(&data).unwrap(),
+            VaultState {
+                amount: Account::unpack(&data).unwrap().amount,
+                frozen: false,
+            }
+        );
+        assert_eq!(parse_token_amount(&data).unwrap(), 123_456_789);
+
+        let frozen = packed_account(1, AccountState::Frozen);
+        assert!(VaultState::parse(&frozen).unwrap().frozen);
+
+        // Token-2022 extensions after the base layout are ignored
+        let mut extended = data.clone();
+        extended.extend_from_slice(&[2, 0, 0, 0]);
+        assert_eq!(
+            VaultState::parse(&extended).unwrap(),
+            VaultState::parse(&data).unwrap()
+        );
+
+        assert!(VaultState::parse(&data[..Account::LEN - 1]).is_err());
+        assert!(VaultState::parse(&vec![0; Account::LEN]).is_err());
+    }
+
+    #[test]
+    fn test_parse_vault_checks_mint_and_owner() {
+        let (key, mint) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let account = solana_sdk::account::Account {
+            lamports: 1,
+            data: packed_account_of(mint, 42, AccountState::Initialized),
+            owner: spl_token::id(),
+            executable: false,
+            rent_epoch: 0,
+        };
+        assert_eq!(
+            VaultState::parse_vault(&key, &account, &mint, &spl_token::id())
+                .unwrap()
+                .amount,
+            42
+        );
+        assert!(
+            VaultState::parse_vault(&key, &account, &Pubkey::new_unique(), &spl_token::id())
+                .is_err()
+        );
+        assert!(VaultState::parse_vault(&key, &account, &mint, &Pubkey::new_unique()).is_err());
+    }
+
+    #[test]
+    fn test_parse_mint_decimals() {
+        let mut data = vec![0; Mint::LEN];
+        Mint::pack(
+            Mint {
+                decimals: 6,
+                is_initialized: true,
+                ..Mint::default()
+            },
+            &mut data,
+        )
+        .unwrap();
+        assert_eq!(parse_mint_decimals(&data).unwrap(), 6);
+        assert!(parse_mint_decimals(&data[..Mint::LEN - 1]).is_err());
+        assert!(parse_mint_decimals(&vec![0; Mint::LEN]).is_err());
+    }
+}
@@ -0,0 +1,433 @@
+//! Builder of swap transactions from a wallet's token accounts.
+
+use anchor_spl::associated_token::get_associated_token_address_with_program_id;
+use anchor_spl::associated_token::spl_associated_token_account::instruction::create_associated_token_account_idempotent;
+use anchor_spl::token::spl_token;
+use anyhow::{anyhow, Result};
+use solana_client::rpc_client::RpcClient;
+use solana_sdk::hash::Hash;
+use solana_sdk::instruction::Instruction;
+use solana_sdk::message::{v0, VersionedMessage};
+use solana_sdk::program_pack::Pack;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::rent::Rent;
+use solana_sdk::signature::Keypair;
+use solana_sdk::system_instruction;
+use solana_sdk::transaction::VersionedTransaction;
+
+use crate::amm::swap_params;
+use crate::priority_fee::{estimate_compute_unit_price, ComputeBudget};
+use crate::registry::fetch_accounts;
+use crate::JupiterRarefish;
+
+/// Seed of the temporary wrapped SOL account derived from the owner, see
+/// [`SwapTransactionBuilder`]
+pub const WRAPPED_SOL_SEED: &str = "rarefish-wsol";
+
+/// Builds the transaction of a swap between the associated token accounts of `owner`.
+///
+/// The ComputeBudget instructions are put first when a compute budget is set, e.g. with a
+/// price estimated from the recent fees of the pool.
+///
+/// With native SOL enabled, a side of the swap in wrapped SOL goes through a temporary wrapped
+/// SOL account of the owner instead of its associated token account: it is created at an
+/// address derived from the owner with [`WRAPPED_SOL_SEED`], funded with its rent and the
+/// input lamports, and closed after the swap so the owner ends up with native SOL. The
+/// owner's own wrapped SOL accounts and their delegations are left untouched.
+#[derive(Clone, Debug)]
+pub struct SwapTransactionBuilder<'a> {
+    jupiter_rarefish: &'a JupiterRarefish,
+    owner: Pubkey,
+    payer: Option<Pubkey>,
+    input_mint: Pubkey,
+    output_mint: Pubkey,
+    source_program: Pubkey,
+    destination_program: Pubkey,
+    in_amount: u64,
+    minimum_out_amount: Option<u64>,
+    compute_budget: Option<ComputeBudget>,
+    native_sol: bool,
+    create_source_token_account: bool,
+    create_destination_token_account: bool,
+}
+
+impl<'a> SwapTransactionBuilder<'a> {
+    /// Swap of `in_amount` of `input_mint`. The minimum output must be set before building
+    /// the transaction, there is no default so no swap is sent without slippage protection.
+    pub fn new(
+        jupiter_rarefish: &'a JupiterRarefish,
+        owner: Pubkey,
+        input_mint: Pubkey,
+        in_amount: u64,
+    ) -> Result<Self> {
+        let [token_a_mint, token_b_mint] = jupiter_rarefish.reserve_mints();
+        let (output_mint, source_program, destination_program) = if input_mint == token_a_mint {
+            (
+                token_b_mint,
+                jupiter_rarefish.token_a_program(),
+                jupiter_rarefish.token_b_program(),
+            )
+        } else if input_mint == token_b_mint {
+            (
+                token_a_mint,
+                jupiter_rarefish.token_b_program(),
+                jupiter_rarefish.token_a_program(),
+            )
+        } else {
+            return Err(anyhow!(
+                "Mint {} is not part of pool {}",
+                input_mint,
+                jupiter_rarefish.market_key
+            ));
+        };
+        Ok(Self {
+            jupiter_rarefish,
+            owner,
+            payer: None,
+            input_mint,
+            output_mint,
+            source_program,
+            destination_program,
+            in_amount,
+            minimum_out_amount: None,
+            compute_budget: None,
+            native_sol: false,
+            create_source_token_account: false,
+            create_destination_token_account: false,
+        })
+    }
+
+    /// Smallest output accepted, the swap fails below it
+    pub fn with_minimum_out_amount(mut self, minimum_out_amount: u64) -> Self {
+        self.minimum_out_amount = Some(minimum_out_amount);
+        self
+    }
+
+    /// Account paying the transaction fee, defaults to the owner
+    pub fn with_payer(mut self, payer: Pubkey) -> Self {
+        self.payer = Some(payer);
+        self
+    }
+
+    pub fn with_compute_budget(mut self, compute_budget: ComputeBudget) -> Self {
+        self.compute_budget = Some(compute_budget);
+        self
+    }
+
+    /// Sets the compute-unit price to `percentile` of the fees recently paid to write-lock
+    /// the pool's accounts, see [`estimate_compute_unit_price`]
+    pub fn with_estimated_priority_fee(mut self, rpc: &RpcClient, percentile: u8) -> Result<Self> {
+        let compute_unit_price =
+            estimate_compute_unit_price(rpc, self.jupiter_rarefish, percentile)?;
+        self.compute_budget = Some(ComputeBudget {
+            compute_unit_price,
+            ..self.compute_budget.unwrap_or_default()
+        });
+        Ok(self)
+    }
+
+    /// Wraps native SOL sold and unwraps native SOL bought, see the type documentation
+    pub fn with_native_sol(mut self, native_sol: bool) -> Self {
+        self.native_sol = native_sol;
+        self
+    }
+
+    /// Checks the owner's source and destination token accounts with `rpc` and creates the
+    /// missing ones before the swap, with the token program of their mint. The creation is
+    /// idempotent so a transaction still succeeds if the account is created meanwhile. The
+    /// temporary wrapped SOL account is always created.
+    pub fn with_missing_token_accounts(mut self, rpc: &RpcClient) -> Result<Self> {
+        let token_accounts = [
+            self.source_token_account(),
+            self.destination_token_account(),
+        ];
+        let accounts_map = fetch_accounts(rpc, &token_accounts)?;
+        self.create_source_token_account = !accounts_map.contains_key(&token_accounts[0]);
+        self.create_destination_token_account = !accounts_map.contains_key(&token_accounts[1]);
+        Ok(self)
+    }
+
+    /// The hyperplane deployment of the pool
+    pub fn program_id(&self) -> Pubkey {
+        self.jupiter_rarefish.program_id
+    }
+
+    pub fn output_mint(&self) -> Pubkey {
+        self.output_mint
+    }
+
+    pub fn payer(&self) -> Pubkey {
+        self.payer.unwrap_or(self.owner)
+    }
+
+    /// The owner's associated token account of the input mint, or the temporary wrapped SOL
+    /// account when wrapping native SOL
+    pub fn source_token_account(&self) -> Pubkey {
+        if self.wrap_input() {
+            self.wrapped_sol_account()
+        } else {
+            get_associated_token_address_with_program_id(
+                &self.owner,
+                &self.input_mint,
+                &self.source_program,
+            )
+        }
+    }
+
+    /// The owner's associated token account of the output mint, or the temporary wrapped SOL
+    /// account when unwrapping native SOL
+    pub fn destination_token_account(&self) -> Pubkey {
+        if self.unwrap_output() {
+            self.wrapped_sol_account()
+        } else {
+            get_associated_token_address_with_program_id(
+                &self.owner,
+                &self.output_mint,
+                &self.destination_program,
+            )
+        }
+    }
+
+    /// The temporary wrapped SOL account of the owner
+    pub fn wrapped_sol_account(&self) -> Pubkey {
+        Pubkey::create_with_seed(&self.owner, WRAPPED_SOL_SEED, &spl_token::id())
+            .expect("Wrapped SOL seed is valid")
+    }
+
+    /// Fails while the minimum output is unset
+    pub fn swap_instruction(&self) -> Result<Instruction> {
+        let minimum_out_amount = self.minimum_out_amount.ok_or_else(|| {
+            anyhow!(
+                "No minimum output set for the swap of {} on pool {}",
+                self.input_mint,
+                self.jupiter_rarefish.market_key
+            )
+        })?;
+        Ok(self.jupiter_rarefish.swap_instruction(&swap_params(
+            self.in_amount,
+            minimum_out_amount,
+            self.input_mint,
+            self.output_mint,
+            self.source_token_account(),
+            self.destination_token_account(),
+            self.owner,
+        )))
+    }
+
+    /// The instructions of the transaction, compute budget first. Fails while the minimum
+    /// output is unset.
+    pub fn instructions(&self) -> Result<Vec<Instruction>> {
+        let mut instructions = self
+            .compute_budget
+            .map(|compute_budget| compute_budget.instructions())
+            .unwrap_or_default();
+        let wrap_input = self.wrap_input();
+        let unwrap_output = self.unwrap_output();
+        if wrap_input {
+            instructions.extend(self.create_wrapped_sol_instructions(self.in_amount)?);
+        } else if self.create_source_token_account {
+            instructions.push(create_associated_token_account_idempotent(
+                &self.payer(),
+                &self.owner,
+                &self.input_mint,
+                &self.source_program,
+            ));
+        }
+        if unwrap_output {
+            instructions.extend(self.create_wrapped_sol_instructions(0)?);
+        } else if self.create_destination_token_account {
+            instructions.push(create_associated_token_account_idempotent(
+                &self.payer(),
+                &self.owner,
+                &self.output_mint,
+                &self.destination_program,
+            ));
+        }
+        instructions.push(self.swap_instruction()?);
+        if wrap_input || unwrap_output {
+            instructions.push(spl_token::instruction::close_account(
+                &spl_token::id(),
+                &self.wrapped_sol_account(),
+                &self.owner,
+                &self.owner,
+                &[],
+            )?);
+        }
+        Ok(instructions)
+    }
+
+    pub fn message(&self, recent_blockhash: Hash) -> Result<VersionedMessage> {
+        Ok(VersionedMessage::V0(v0::Message::try_compile(
+            &self.payer(),
+            &self.instructions()?,
+            &[],
+            recent_blockhash,
+        )?))
+    }
+
+    /// Signs the transaction with the owner's keypair, and the payer's if it is another
+    /// account
+    pub fn build(
+        &self,
+        signers: &[&Keypair],
+        recent_blockhash: Hash,
+    ) -> Result<VersionedTransaction> {
+        Ok(VersionedTransaction::try_new(
+            self.message(recent_blockhash)?,
+            &signers.to_vec(),
+        )?)
+    }
+
+    fn wrap_input(&self) -> bool {
+        self.native_sol && is_wrapped_sol(&self.input_mint, &self.source_program)
+    }
+
+    fn unwrap_output(&self) -> bool {
+        self.native_sol && is_wrapped_sol(&self.output_mint, &self.destination_program)
+    }
+
+    /// Creates the temporary wrapped SOL account funded by the owner with its rent and
+    /// `lamports`. A pool never trades wrapped SOL on both sides, so one account is enough.
+    fn create_wrapped_sol_instructions(&self, lamports: u64) -> Result<[Instruction; 2]> {
+        let wrapped_sol_account = self.wrapped_sol_account();
+        let rent = Rent::default().minimum_balance(spl_token::state::Account::LEN);
+        Ok([
+            system_instruction::create_account_with_seed(
+                &self.owner,
+                &wrapped_sol_account,
+                &self.owner,
+                WRAPPED_SOL_SEED,
+                rent + lamports,
+                spl_token::state::Account::LEN as u64,
+                &spl_token::id(),
+            ),
+            spl_token::instruction::initialize_account3(
+                &spl_token::id(),
+                &wrapped_sol_account,
+                &spl_token::native_mint::id(),
+                &self.owner,
+            )?,
+        ])
+    }
+}
+
+fn is_wrapped_sol(mint: &Pubkey, token_program: &Pubkey) -> bool {
+    *mint == spl_token::native_mint::id() && *token_program == spl_token::id()
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_sdk::compute_budget;
+    use solana_sdk::hash::Hash;
+    use solana_sdk::pubkey::Pubkey;
+    use solana_sdk::signature::Keypair;
+    use solana_sdk::signer::Signer;
+
+    use anchor_spl::associated_token::spl_associated_token_account;
+    use anchor_spl::token::spl_token;
+    use anchor_spl::token_2022::spl_token_2022;
+    use solana_sdk::system_program;
+
+    use super::SwapTransactionBuilder;
+    use crate::priority_fee::ComputeBudget;
+    use crate::test_utils::{LocalPool, LocalPoolConfig};
+
+    #[tokio::test]
+    async fn test_swap_transaction_builder() {
+        let market = LocalPool::new(LocalPoolConfig::default())
+            .await
+            .adapter()
+            .await;
+        let [token_a_mint, token_b_mint] = market.reserve_mints();
+        let owner = Keypair::new();
+        assert!(
+            SwapTransactionBuilder::new(&market, owner.pubkey(), Pubkey::new_unique(), 1).is_err()
+        );
+
+        let builder =
+            SwapTransactionBuilder::new(&market, owner.pubkey(), token_b_mint, 1_000).unwrap();
+        assert!(builder.instructions().is_err());
+        assert!(builder.build(&[&owner], Hash::default()).is_err());
+        let builder = builder.with_minimum_out_amount(990);
+        assert_eq!(builder.output_mint(), token_a_mint);
+        assert_eq!(builder.instructions().unwrap().len(), 1);
+
+        let builder = builder.with_compute_budget(ComputeBudget {
+            compute_unit_limit: 150_000,
+            compute_unit_price: 10_000,
+        });
+        let instructions = builder.instructions().unwrap();
+        assert_eq!(instructions.len(), 3);
+        assert_eq!(instructions[0].program_id, compute_budget::id());
+        assert_eq!(instructions[1].program_id, compute_budget::id());
+        assert_eq!(instructions[2].program_id, hyperplane::id());
+        assert_eq!(
+            instructions[2].accounts[8].pubkey,
+            builder.source_token_account()
+        );
+
+        let transaction = builder.build(&[&owner], Hash::default()).unwrap();
+        assert!(transaction.verify_with_results().iter().all(|ok| *ok));
+    }
+
+    #[tokio::test]
+    async fn test_swap_transaction_builder_creates_token_accounts() {
+        let market = LocalPool::new(LocalPoolConfig {
+            token_b_program: spl_token_2022::id(),
+            ..LocalPoolConfig::default()
+        })
+        .await
+        .adapter()
+        .await;
+        let [token_a_mint, token_b_mint] = market.reserve_mints();
+        let owner = Pubkey::new_unique();
+        let mut builder = SwapTransactionBuilder::new(&market, owner, token_a_mint, 1_000)
+            .unwrap()
+            .with_minimum_out_amount(0);
+        builder.create_destination_token_account = true;
+
+        let instructions = builder.instructions().unwrap();
+        assert_eq!(instructions.len(), 2);
+        let create = &instructions[0];
+        assert_eq!(create.program_id, spl_associated_token_account::id());
+        assert_eq!(
+            create.accounts[1].pubkey,
+            builder.destination_token_account()
+        );
+        assert_eq!(create.accounts[3].pubkey, token_b_mint);
+        assert_eq!(create.accounts[5].pubkey, spl_token_2022::id());
+        assert_eq!(instructions[1].program_id, hyperplane::id());
+    }
+
+    #[tokio::test]
+    async fn test_swap_transaction_builder_wraps_native_sol() {
+        let market = LocalPool::new(LocalPoolConfig::default())
+            .await
+            .adapter()
+            .await;
+        let [token_a_mint, _] = market.reserve_mints();
+        let owner = Pubkey::new_unique();
+        let mut builder = SwapTransactionBuilder::new(&market, owner, token_a_mint, 1_000)
+            .unwrap()
+            .with_minimum_out_amount(0)
+            .with_native_sol(true);
+        // Stands for a pool trading wrapped SOL
+        builder.input_mint = spl_token::native_mint::id();
+        builder.create_source_token_account = true;
+        let wrapped_sol_account = builder.wrapped_sol_account();
+        assert_eq!(builder.source_token_account(), wrapped_sol_account);
+
+        let instructions = builder.instructions().unwrap();
+        assert_eq!(instructions.len(), 4);
+        assert_eq!(instructions[0].program_id, system_program::id());
+        assert_eq!(instructions[0].accounts[1].pubkey, wrapped_sol_account);
+        assert_eq!(instructions[1].program_id, spl_token::id());
+        assert_eq!(instructions[1].accounts[0].pubkey, wrapped_sol_account);
+        assert_eq!(instructions[2].program_id, hyperplane::id());
+        assert_eq!(instructions[2].accounts[8].pubkey, wrapped_sol_account);
+        // Only the temporary account is closed, back to the owner
+        assert_eq!(instructions[3].program_id, spl_token::id());
+        assert_eq!(instructions[3].accounts[0].pubkey, wrapped_sol_account);
+        assert_eq!(instructions[3].accounts[1].pubkey, owner);
+    }
+}
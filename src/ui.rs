@@ -0,0 +1,154 @@
+use std::fmt;
+
+use anyhow::{anyhow, Result};
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::Decimal;
+use solana_sdk::pubkey::Pubkey;
+
+use crate::amm::{Amm, QuoteParams, SwapMode};
+use crate::{CurveParams, JupiterRarefish};
+
+/// Converts a UI amount into raw token units, truncating digits beyond the mint's precision
+pub fn to_raw_amount(ui_amount: Decimal, decimals: u8) -> Result<u64> {
+    if ui_amount.is_sign_negative() {
+        return Err(anyhow!("UI amount {} is negative", ui_amount));
+    }
+    10_u64
+        .checked_pow(decimals as u32)
+        .and_then(|scale| ui_amount.checked_mul(Decimal::from(scale)))
+        .and_then(|raw| raw.trunc().to_u64())
+        .ok_or_else(|| {
+            anyhow!(
+                "UI amount {} overflows with {} decimals",
+                ui_amount,
+                decimals
+            )
+        })
+}
+
+/// Converts raw token units into a UI amount. Decimal holds at most 28 decimals, more is an
+/// error.
+pub fn to_ui_amount(raw_amount: u64, decimals: u8) -> Result<Decimal> {
+    Decimal::try_from_i128_with_scale(raw_amount as i128, decimals as u32).map_err(|e| {
+        anyhow!(
+            "Amount {} cannot be converted with {} decimals: {}",
+            raw_amount,
+            decimals,
+            e
+        )
+    })
+}
+
+impl JupiterRarefish {
+    /// Quotes an exact-in swap of `ui_amount` of `input_mint`, returning the UI output amount.
+    ///
+    /// Requires the mints to have been updated so their decimals are known.
+    pub fn quote_ui(&self, input_mint: &Pubkey, ui_amount: Decimal) -> Result<Decimal> {
+        let decimals = self
+            .token_a_decimals()
+            .zip(self.token_b_decimals())
+            .ok_or_else(|| anyhow!("These mint accounts should be updated first"))?;
+        let (output_mint, input_decimals, output_decimals) =
+            if *input_mint == self.pool.token_a_mint {
+                (self.pool.token_b_mint, decimals.0, decimals.1)
+            } else {
+                (self.pool.token_a_mint, decimals.1, decimals.0)
+            };
+        let quote = self.quote(&QuoteParams {
+            input_mint: *input_mint,
+            output_mint,
+            amount: to_raw_amount(ui_amount, input_decimals)?,
+            swap_mode: SwapMode::ExactIn,
+        })?;
+        to_ui_amount(quote.out_amount, output_decimals)
+    }
+
+    /// [`JupiterRarefish::quote_ui`] for callers working with floating point amounts
+    pub fn quote_ui_f64(&self, input_mint: &Pubkey, ui_amount: f64) -> Result<f64> {
+        let ui_amount = Decimal::try_from(ui_amount)?;
+        let out_amount = self.quote_ui(input_mint, ui_amount)?;
+        out_amount
+            .to_f64()
+            .ok_or_else(|| anyhow!("UI amount {} does not fit in an f64", out_amount))
+    }
+}
+
+/// One line summary of the pool for logs and CLIs: label, key, mints, curve, fee, reserves in
+/// UI units once the decimals are known, and whether it can trade
+impl fmt::Display for JupiterRarefish {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let curve = match self.curve_info() {
+            CurveParams::ConstantProduct => "constant product".to_string(),
+            CurveParams::ConstantPrice { token_b_price } => {
+                format!("constant price {}", token_b_price)
+            }
+            CurveParams::Offset { token_b_offset } => format!("offset {}", token_b_offset),
+            CurveParams::Stable { amp, .. } => format!("stable amp {}", amp),
+        };
+        write!(
+            f,
+            "{} {}: {}/{}, {} curve, {} bps fee",
+            self.label_str(),
+            self.market_key,
+            self.pool.token_a_mint,
+            self.pool.token_b_mint,
+            curve,
+            self.effective_fee_bps().normalize()
+        )?;
+        let Some((token_a_amount, token_b_amount)) = self.reserves() else {
+            return write!(f, ", not updated");
+        };
+        let ui_reserves = self
+            .token_a_decimals()
+            .zip(self.token_b_decimals())
+            .and_then(|(token_a_decimals, token_b_decimals)| {
+                to_ui_amount(token_a_amount, token_a_decimals)
+                    .ok()
+                    .zip(to_ui_amount(token_b_amount, token_b_decimals).ok())
+            });
+        match ui_reserves {
+            Some((token_a_ui_amount, token_b_ui_amount)) => write!(
+                f,
+                ", reserves {} / {}",
+                token_a_ui_amount.normalize(),
+                token_b_ui_amount.normalize()
+            )?,
+            // The decimals are unknown or beyond what a Decimal holds
+            None => write!(f, ", raw reserves {} / {}", token_a_amount, token_b_amount)?,
+        }
+        let status = if self.is_tradeable() {
+            "tradeable"
+        } else if !self.has_liquidity() {
+            "no liquidity"
+        } else {
+            "frozen"
+        };
+        write!(f, ", {}", status)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rust_decimal::Decimal;
+
+    use super::{to_raw_amount, to_ui_amount};
+
+    #[test]
+    fn test_ui_amount_conversions() {
+        assert_eq!(
+            to_raw_amount(Decimal::new(15, 1), 9).unwrap(),
+            1_500_000_000
+        );
+        assert_eq!(
+            to_raw_amount(Decimal::new(1_234_567, 7), 6).unwrap(),
+            123_456
+        );
+        assert_eq!(to_ui_amount(1_500_000_000, 9).unwrap(), Decimal::new(15, 1));
+        assert_eq!(to_ui_amount(123_456, 6).unwrap(), Decimal::new(123_456, 6));
+        assert_eq!(to_ui_amount(1, 28).unwrap(), Decimal::new(1, 28));
+        assert!(to_ui_amount(1, 29).is_err());
+        assert!(to_ui_amount(u64::MAX, u8::MAX).is_err());
+        assert!(to_raw_amount(Decimal::new(-1, 0), 6).is_err());
+        assert!(to_raw_amount(Decimal::from(u64::MAX), 6).is_err());
+    }
+}
@@ -0,0 +1,71 @@
+//! wasm-bindgen exports for the npm package, so TypeScript dapps get byte-exact quotes from
+//! the accounts they fetched with web3.js.
+
+use solana_sdk::account::Account;
+use solana_sdk::pubkey::Pubkey;
+use wasm_bindgen::prelude::*;
+
+use crate::amm::KeyedAccount;
+use crate::token::VaultState;
+use crate::{JupiterRarefish, TradeDirection};
+
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Direction {
+    AtoB,
+    BtoA,
+}
+
+#[wasm_bindgen(getter_with_clone)]
+#[derive(Clone, Debug)]
+pub struct Quote {
+    #[wasm_bindgen(js_name = inAmount)]
+    pub in_amount: u64,
+    #[wasm_bindgen(js_name = outAmount)]
+    pub out_amount: u64,
+    #[wasm_bindgen(js_name = feeAmount)]
+    pub fee_amount: u64,
+    #[wasm_bindgen(js_name = feeMint)]
+    pub fee_mint: String,
+}
+
+/// Quotes an exact-in swap of `amount` from the raw data of the pool and its two vault accounts
+#[wasm_bindgen]
+pub fn quote(
+    pool_account: &[u8],
+    vault_a: &[u8],
+    vault_b: &[u8],
+    amount: u64,
+    direction: Direction,
+) -> Result<Quote, JsError> {
+    let mut jupiter_rarefish = JupiterRarefish::new_from_keyed_account(&KeyedAccount {
+        key: Pubkey::default(),
+        account: Account {
+            lamports: 0,
+            data: pool_account.to_vec(),
+            owner: hyperplane::id(),
+            executable: false,
+            rent_epoch: 0,
+        },
+        params: None,
+    })
+    .map_err(|e| JsError::new(&e.to_string()))?;
+    let vault_a = VaultState::parse(vault_a).map_err(|e| JsError::new(&e.to_string()))?;
+    let vault_b = VaultState::parse(vault_b).map_err(|e| JsError::new(&e.to_string()))?;
+    jupiter_rarefish.update_reserves(vault_a.amount, vault_b.amount);
+
+    let trade_direction = match direction {
+        Direction::AtoB => TradeDirection::AtoB,
+        Direction::BtoA => TradeDirection::BtoA,
+    };
+    let quote = jupiter_rarefish
+        .quote_many(trade_direction, &[amount])
+        .map_err(|e| JsError::new(&e.to_string()))?
+        .remove(0);
+    Ok(Quote {
+        in_amount: quote.in_amount,
+        out_amount: quote.out_amount,
+        fee_amount: quote.fee_amount,
+        fee_mint: quote.fee_mint.to_string(),
+    })
+}